@@ -0,0 +1,323 @@
+use cga2d::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Number of values used to store a camera transform: a parity flag and eight coefficients.
+pub const COMPONENT_COUNT: usize = 9;
+
+/// Flatten a camera transform into plain numbers.
+pub fn to_components(transform: &cga2d::Rotoflector) -> [f64; COMPONENT_COUNT] {
+    match *transform {
+        cga2d::Rotoflector::Rotor(r) => [0., r.s, r.mp, r.mx, r.my, r.px, r.py, r.xy, r.mpxy],
+        cga2d::Rotoflector::Flector(f) => [1., f.m, f.p, f.x, f.y, f.mpx, f.mpy, f.mxy, f.pxy],
+    }
+}
+
+/// Rebuild a camera transform from `to_components`, renormalizing away any rounding.
+pub fn from_components(components: &[f64; COMPONENT_COUNT]) -> cga2d::Rotoflector {
+    exact_from_components(components).normalize()
+}
+
+/// Rebuild a camera transform from `to_components` exactly as given.
+fn exact_from_components(components: &[f64; COMPONENT_COUNT]) -> cga2d::Rotoflector {
+    let [parity, a, b, c, d, e, f, g, h] = *components;
+    if parity == 0. {
+        cga2d::Rotoflector::Rotor(cga2d::Rotor {
+            s: a,
+            mp: b,
+            mx: c,
+            my: d,
+            px: e,
+            py: f,
+            xy: g,
+            mpxy: h,
+        })
+    } else {
+        cga2d::Rotoflector::Flector(cga2d::Flector {
+            m: a,
+            p: b,
+            x: c,
+            y: d,
+            mpx: e,
+            mpy: f,
+            mxy: g,
+            pxy: h,
+        })
+    }
+}
+
+/// A named viewpoint to return to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub name: String,
+    #[serde(with = "crate::camera")]
+    pub transform: cga2d::Rotoflector,
+}
+
+/// The camera gliding from one transform to another.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraAnimation {
+    pub start: cga2d::Rotoflector,
+    pub target: cga2d::Rotoflector,
+    /// Fraction of the way to `target`, from 0 to 1
+    pub progress: f64,
+}
+impl CameraAnimation {
+    /// Seconds taken to reach the target
+    pub const DURATION: f64 = 0.4;
+
+    pub fn new(start: cga2d::Rotoflector, target: cga2d::Rotoflector) -> Self {
+        Self {
+            start,
+            target,
+            progress: 0.,
+        }
+    }
+
+    /// Move on by `dt` seconds, returning the camera transform to show. Eases in and out.
+    pub fn step(&mut self, dt: f64) -> cga2d::Rotoflector {
+        self.progress = (self.progress + dt / Self::DURATION).min(1.);
+        // Land on the target itself, not a blend that's only rounded close to it
+        if self.is_done() {
+            return self.target;
+        }
+        let t = self.progress * self.progress * (3. - 2. * self.progress);
+        crate::geom::interpolate_rotoflector(self.start, self.target, t)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.progress >= 1.
+    }
+}
+
+/// How distorted the view of some mirrors is: how far each moves the unit circle off
+/// centre, plus how much it rescales it.
+fn badness(mirrors: &[cga2d::Blade3]) -> f64 {
+    (mirrors.iter())
+        .map(|m| match m.sandwich(cga2d::circle(NO, 1.)).unpack(0.) {
+            cga2d::LineOrCircle::Line { .. } => f64::INFINITY,
+            cga2d::LineOrCircle::Circle { cx, cy, r } => cx * cx + cy * cy + r.ln() * r.ln(),
+        })
+        .sum()
+}
+
+/// Reflect the camera in the tiling's mirrors, greedily taking whichever makes the view
+/// least distorted until none helps. The result has the same handedness as `camera`.
+pub fn optimize_camera(
+    mirrors: &[cga2d::Blade3],
+    camera: cga2d::Rotoflector,
+) -> cga2d::Rotoflector {
+    const MAX_STEPS: usize = 64;
+    // Mirrors as they're seen on screen
+    let mut view: Vec<cga2d::Blade3> = mirrors.iter().map(|&m| camera.sandwich(m)).collect();
+    let mut badness_now = badness(&view);
+    let mut trans = cga2d::Rotoflector::ident();
+    let mut mirrored = false;
+    for _ in 0..MAX_STEPS {
+        let best = (view.iter())
+            .map(|&mirror| {
+                let moved: Vec<_> = view.iter().map(|&m| mirror.sandwich(m)).collect();
+                (badness(&moved), mirror, moved)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        match best {
+            Some((b, mirror, moved)) if b < badness_now => {
+                badness_now = b;
+                view = moved;
+                trans = (cga2d::Rotoflector::ident() * mirror) * trans;
+                mirrored = !mirrored;
+            }
+            _ => break,
+        }
+    }
+
+    let mut camera = trans * camera;
+    // Reflecting the tiling in one of its own mirrors undoes an odd number of reflections,
+    // only moving the view across one edge
+    if let (true, Some(&m)) = (mirrored, mirrors.first()) {
+        camera = camera * m;
+    }
+    camera.normalize()
+}
+
+/// How far in a transform is zoomed: the reciprocal of the radius of the region of the plane
+/// shown within the unit circle of the screen. `None` if that region isn't a disc.
+pub fn zoom(transform: &cga2d::Rotoflector) -> Option<f64> {
+    match transform.rev().sandwich(cga2d::circle(NO, 1.)).unpack(0.) {
+        cga2d::LineOrCircle::Line { .. } => None,
+        cga2d::LineOrCircle::Circle { r, .. } => Some(1. / r.abs()),
+    }
+}
+
+/// Zoom in by a scroll of `delta` view units, or out for a negative one. Steps leaving
+/// `limits` are refused, but a step heading back into them is always allowed.
+pub fn scroll_zoom(
+    transform: cga2d::Rotoflector,
+    delta: f64,
+    limits: std::ops::RangeInclusive<f64>,
+) -> cga2d::Rotoflector {
+    let scale = (NO ^ NI).connect(cga2d::point(1. + delta / 2., 0.))
+        * (NO ^ NI).connect(cga2d::point(1., 0.));
+    let scaled = scale * transform;
+    let allowed = match (zoom(&transform), zoom(&scaled)) {
+        (Some(old), Some(new)) => {
+            limits.contains(&new)
+                || (new > *limits.end() && new < old)
+                || (new < *limits.start() && new > old)
+        }
+        _ => true,
+    };
+    match allowed {
+        true => scaled,
+        false => transform,
+    }
+}
+
+/// Whether every component of a transform is finite.
+pub fn is_finite(transform: &cga2d::Rotoflector) -> bool {
+    to_components(transform).iter().all(|c| c.is_finite())
+}
+
+/// Whether a transform moves points by a negligible amount.
+pub fn is_near_identity(transform: &cga2d::Rotoflector, tolerance: f64) -> bool {
+    let [parity, s, rest @ ..] = to_components(transform);
+    parity == 0. && rest.iter().all(|c| c.abs() < tolerance * s.abs())
+}
+
+/// Frame rate that pan momentum and friction are measured at, so coasting covers the same
+/// ground in the same time whatever the actual frame rate.
+pub const COAST_FPS: f64 = 60.;
+
+/// Share of the coasting speed kept after `dt` seconds, when `friction` of it is lost every
+/// frame at `COAST_FPS`.
+pub fn coast_decay(friction: f64, dt: f64) -> f64 {
+    (1. - friction).clamp(0., 1.).powf(dt * COAST_FPS)
+}
+
+/// For use with `#[serde(with = "crate::camera")]` on a `cga2d::Rotoflector` field.
+pub fn serialize<S: Serializer>(
+    transform: &cga2d::Rotoflector,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    to_components(transform).serialize(serializer)
+}
+
+/// For use with `#[serde(with = "crate::camera")]` on a `cga2d::Rotoflector` field. The
+/// transform is loaded exactly as saved, so a restored view is the one that was saved.
+pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<cga2d::Rotoflector, D::Error> {
+    Ok(exact_from_components(&Deserialize::deserialize(
+        deserializer,
+    )?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames until a coast from unit speed drops below `stop`.
+    fn frames_to_stop(friction: f64, fps: f64, stop: f64) -> u32 {
+        let mut speed = 1.;
+        let mut frames = 0;
+        while speed >= stop {
+            speed *= coast_decay(friction, 1. / fps);
+            frames += 1;
+        }
+        frames
+    }
+
+    /// Whether two transforms act the same, whatever their sign.
+    fn same(a: cga2d::Rotoflector, b: cga2d::Rotoflector) -> bool {
+        is_near_identity(&(a.rev() * b).normalize(), 1e-9)
+    }
+
+    #[test]
+    fn coast_stops_after_same_time_at_any_frame_rate() {
+        // 0.9^44 is the first power of 0.9 under 0.01
+        assert_eq!(frames_to_stop(0.1, 60., 0.01), 44);
+        assert_eq!(frames_to_stop(0.1, 30., 0.01), 22);
+        assert_eq!(frames_to_stop(0.1, 120., 0.01), 88);
+        assert_eq!(frames_to_stop(1., 60., 0.01), 1);
+        assert_eq!(coast_decay(0.1, 0.), 1.);
+    }
+
+    #[test]
+    fn zooming_stays_finite_and_normalized() {
+        let limits = 0.01..=1e5;
+        for delta in [0.5, -0.5] {
+            let mut transform = cga2d::Rotoflector::ident();
+            // Far more steps than it takes to reach either limit
+            for _ in 0..2000 {
+                transform = scroll_zoom(transform, delta, limits.clone()).normalize();
+                assert!(is_finite(&transform));
+                assert!((transform.mag2().abs() - 1.).abs() < 1e-9);
+            }
+            assert!(limits.contains(&zoom(&transform).unwrap()));
+        }
+    }
+
+    #[test]
+    fn components_round_trip() {
+        let rotor = [0., 0.8, 0.1, -0.2, 0.3, 0.05, -0.4, 0.6, 0.01];
+        let flector = [1., 0.6, 0., 0.1, 0.8, -0.3, 0., 0.2, 0.];
+        for components in [rotor, flector] {
+            let transform = exact_from_components(&components);
+            assert_eq!(to_components(&transform), components);
+            let json = serde_json::to_string(&to_components(&transform)).unwrap();
+            let loaded: [f64; COMPONENT_COUNT] = serde_json::from_str(&json).unwrap();
+            assert_eq!(exact_from_components(&loaded), transform);
+        }
+        assert!(matches!(
+            exact_from_components(&rotor),
+            cga2d::Rotoflector::Rotor(_)
+        ));
+        assert!(matches!(
+            exact_from_components(&flector),
+            cga2d::Rotoflector::Flector(_)
+        ));
+    }
+
+    #[test]
+    fn bookmark_restores_exact_transform() {
+        let transform = exact_from_components(&[0., 0.8, 0.1, -0.2, 0.3, 0.05, -0.4, 0.6, 0.01]);
+        let bookmark = Bookmark {
+            name: "Deep".to_string(),
+            transform,
+        };
+        let json = serde_json::to_string(&bookmark).unwrap();
+        let loaded: Bookmark = serde_json::from_str(&json).unwrap();
+        assert_eq!(to_components(&loaded.transform), to_components(&transform));
+
+        // Gliding back to it ends exactly there
+        let start = exact_from_components(&[1., 0.6, 0., 0., 0.8, 0., 0., 0., 0.]);
+        let mut animation = CameraAnimation::new(start, loaded.transform);
+        let shown = animation.step(CameraAnimation::DURATION);
+        assert!(animation.is_done());
+        assert_eq!(to_components(&shown), to_components(&transform));
+    }
+
+    #[test]
+    fn animation_runs_between_endpoints() {
+        // A rotation about the origin and a motion off to one side
+        let start: cga2d::Rotoflector =
+            (cga2d::line(0., 1., 0.) * cga2d::line(-0.6, 0.8, 0.)).into();
+        let mirror = cga2d::circle(cga2d::point(0.2, 0.1), 0.5);
+        let target: cga2d::Rotoflector = (mirror * cga2d::line(0., 1., 0.)).into();
+
+        let mut animation = CameraAnimation::new(start, target);
+        assert!(same(animation.step(0.), start));
+        // Easing leaves the middle where it is
+        let middle = crate::geom::interpolate_rotoflector(start, target, 0.5);
+        assert!(same(animation.step(CameraAnimation::DURATION / 2.), middle));
+        assert!(same(animation.step(CameraAnimation::DURATION), target));
+    }
+
+    #[test]
+    fn centred_view_stays_put() {
+        // {7,3}, with the fundamental region at the centre of the view
+        let [a, b] = [7, 3].map(|p| Some(crate::config::SchlafliEntry::new(p)));
+        let mirrors = crate::geom::rank_3_mirrors(a, b).unwrap();
+        let camera = optimize_camera(&mirrors, cga2d::Rotoflector::ident());
+        assert!(is_near_identity(&camera, 1e-9));
+    }
+}
@@ -1,75 +1,177 @@
-use std::str::FromStr;
+use std::{fmt, str::FromStr, time::Duration};
 
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    geom::{rank_3_mirrors, rank_4_mirrors},
-    tiling::Tiling,
+    camera::Bookmark,
+    geom::{rank_2_mirrors, rank_3_mirrors, rank_4_mirrors, MirrorError, Projection, Tolerances},
+    group::{Generator, Word},
+    tiling::{Tiling, TilingError},
 };
 
-pub(crate) const RELATION_PATTERN: &'static str = r"^(\d\s*(?:,\s*\d\s*)*);\s*(\d+)\s*$";
-pub(crate) const SCHLAFLI_PATTERN: &'static str =
-    r"^\{(\s*(?:\d+|i)(?:\s*,\s*(?:\d+|i)\s*){1,2})\}$";
-pub(crate) const SUBGROUP_PATTERN: &'static str = r"^\s*(\d(?:\s*,\d)*)?\s*$";
+pub const RELATION_PATTERN: &'static str = r"^(\d\s*(?:,\s*\d\s*)*);\s*(\d+)\s*$";
+pub const SUBGROUP_PATTERN: &'static str =
+    r"^\s*(\d(?:\s*,\s*\d)*(?:\s*;\s*\d(?:\s*,\s*\d)*)*)?\s*$";
 
-pub(crate) fn parse_relation(string: &str) -> Result<Vec<u8>, ()> {
+/// Storage key for the tiling loaded at startup in place of the built-in default
+pub const HOME_KEY: &str = "home_tiling";
+/// Storage key for the settings as they were when the app last closed
+pub const SETTINGS_KEY: &str = "settings";
+/// Storage key for the camera, only saved when `Settings::remember_camera` is set
+pub const CAMERA_KEY: &str = "camera";
+
+/// Largest number of times a relation may repeat. Well past the order of any rotation
+/// worth tiling with, and small enough that the expanded relation stays short.
+pub const MAX_RELATION_REPEAT: usize = 1000;
+
+/// Parse a relation, a comma-separated list of generators below `rank` followed by the
+/// number of times it repeats. Eg. 0,2,1;8
+pub fn parse_relation(string: &str, rank: u8) -> Result<Vec<u8>, RelationError> {
     let r = Regex::new(&RELATION_PATTERN).unwrap();
 
-    if let Some(s) = r.captures(string.trim()) {
-        let rel: Vec<u8> = s
-            .get(1)
-            .unwrap()
-            .as_str()
-            .split(",")
-            .map(|d| d.trim().parse().expect("Guaranteed by regex"))
-            .collect();
-        let rep = s
-            .get(2)
-            .unwrap()
-            .as_str()
-            .parse()
-            .expect("Guaranteed by regex");
-        if rep > 0 {
-            Ok((0..rep).flat_map(|_| rel.clone()).collect())
-        } else {
-            Err(())
+    let s = r.captures(string.trim()).ok_or(RelationError::Malformed)?;
+    // `\d` also matches non-ASCII digits, which don't parse, so this can still fail
+    let rel: Vec<u8> = s
+        .get(1)
+        .ok_or(RelationError::Malformed)?
+        .as_str()
+        .split(",")
+        .map(|d| d.trim().parse().map_err(|_| RelationError::Malformed))
+        .collect::<Result<_, _>>()?;
+    if let Some(&generator) = rel.iter().find(|&&g| g >= rank) {
+        return Err(RelationError::Generator { generator, rank });
+    }
+    let rep = s.get(2).ok_or(RelationError::Malformed)?.as_str();
+    // Digits that don't fit a usize are far too many repeats anyway
+    let rep: usize = rep
+        .parse()
+        .map_err(|_| match rep.bytes().all(|b| b.is_ascii_digit()) {
+            true => RelationError::RepeatTooLarge,
+            false => RelationError::Malformed,
+        })?;
+    match rep {
+        0 => Err(RelationError::ZeroRepeat),
+        1..=MAX_RELATION_REPEAT => Ok((0..rep).flat_map(|_| rel.clone()).collect()),
+        _ => Err(RelationError::RepeatTooLarge),
+    }
+}
+
+/// Why a relation failed to parse.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelationError {
+    /// Not of the form `0,2,1;8`
+    Malformed,
+    ZeroRepeat,
+    /// Repeats more than `MAX_RELATION_REPEAT` times
+    RepeatTooLarge,
+    /// Uses a generator the tiling doesn't have
+    Generator {
+        generator: u8,
+        rank: u8,
+    },
+}
+impl fmt::Display for RelationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "expected generators then a count, eg. 0,2,1;8"),
+            Self::ZeroRepeat => write!(f, "count must be at least 1"),
+            Self::RepeatTooLarge => write!(f, "count must be at most {MAX_RELATION_REPEAT}"),
+            Self::Generator { generator, rank } => {
+                write!(f, "no generator {generator}, only 0 to {} exist", rank - 1)
+            }
         }
-    } else {
-        Err(())
     }
 }
 
-pub(crate) fn parse_subgroup(string: &str) -> Result<Vec<u8>, ()> {
+/// Parse semicolon-separated subgroup words, each a comma-separated list of generators.
+pub fn parse_subgroup(string: &str) -> Result<Vec<Vec<u8>>, ()> {
     if string.is_empty() {
         return Ok(vec![]);
     }
     let r = Regex::new(&SUBGROUP_PATTERN).unwrap();
 
     if let Some(s) = r.captures(string.trim()) {
-        if s.get(0).unwrap().as_str().is_empty() {
-            Ok(vec![])
-        } else {
-            Ok(s.get(1)
-                .unwrap()
-                .as_str()
-                .split(",")
-                .map(|d| d.trim().parse().expect("Guaranteed by regex"))
-                .collect())
+        match s.get(1) {
+            None => Ok(vec![]),
+            Some(words) => (words.as_str().split(";"))
+                .map(|word| {
+                    (word.split(","))
+                        .map(|d| d.trim().parse().map_err(|_| ()))
+                        .collect()
+                })
+                .collect(),
         }
     } else {
         Err(())
     }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct ViewSettings {
+/// Parse a comma-separated list of generators.
+pub fn parse_word(string: &str) -> Result<Word, ()> {
+    let string = string.trim();
+    if string.is_empty() {
+        return Err(());
+    }
+    (string.split(","))
+        .map(|d| d.trim().parse().map(Generator).map_err(|_| ()))
+        .collect::<Result<_, ()>>()
+        .map(Word)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewSettings {
     pub col_scale: f32,
     pub fundamental: bool,
     pub mirrors: bool,
     pub path_debug: bool,
     pub col_tiles: bool,
     pub inverse_col: bool,
-    pub outline_thickness: f32,
+    /// Colour each region by how many reflections its element is from the identity
+    pub col_distance: bool,
+    pub cayley_graph: bool,
+    /// List each generator's colour in a corner of the view
+    pub legend: bool,
+    /// Tint the tile under the pointer, and name its coset in a tooltip
+    pub highlight_tile: bool,
+    /// Black outlines on uniform light gray tiles, for printing and accessibility
+    pub high_contrast: bool,
+    /// Outline the polygon implied by each Schläfli entry
+    pub schlafli_polygons: bool,
+    /// Draw the edges of the tiling and of its dual
+    pub dual_overlay: bool,
+    /// Colour regions by whether they are an odd or even number of reflections from home
+    pub parity_coloring: bool,
+    pub parity_cols: [[f32; 4]; 2],
+    /// Render into a linear texture instead of an sRGB one
+    pub linear_output: bool,
+    /// Thickness of the outlines along tile edges, from 0 to 1
+    #[serde(alias = "outline_thickness")]
+    pub cell_outline_thickness: f32,
+    /// Thickness of the outlines along the fourth mirror, which bounds vertex figures
+    pub vertex_outline_thickness: f32,
+    pub outline_col: [f32; 4],
+    /// Margin trimmed from the edge of each tile, leaving gaps between cells
+    pub inset: f32,
+    /// Sticker colours, indexed by element modulo the length. Empty uses the colour ramp.
+    pub palette: Vec<[f32; 4]>,
+    /// Render at this many times the screen resolution along each axis and average down
+    pub supersampling: u32,
+    /// Range of `camera::zoom` that scrolling is allowed to reach
+    pub min_zoom: f64,
+    pub max_zoom: f64,
+    /// How each generator's mirror and Cayley graph edges are drawn, one per generator
+    pub generators: Vec<GeneratorStyle>,
+    /// Which mirrors bound a tile, in place of those implied by the subgroup
+    pub custom_edges: Option<Vec<bool>>,
+    /// Model hyperbolic tilings are drawn in
+    pub projection: Projection,
+}
+impl Default for ViewSettings {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl ViewSettings {
     pub fn new() -> Self {
@@ -80,37 +182,148 @@ impl ViewSettings {
             path_debug: true,
             col_tiles: false,
             inverse_col: false,
-            outline_thickness: 0.5,
+            col_distance: false,
+            cayley_graph: false,
+            legend: false,
+            highlight_tile: false,
+            high_contrast: false,
+            schlafli_polygons: false,
+            dual_overlay: false,
+            parity_coloring: false,
+            parity_cols: [[0.1, 0.1, 0.1, 1.], [0.9, 0.9, 0.9, 1.]],
+            linear_output: false,
+            cell_outline_thickness: 0.5,
+            vertex_outline_thickness: 0.5,
+            outline_col: [0., 0., 0., 1.],
+            inset: 0.,
+            palette: vec![],
+            supersampling: 1,
+            min_zoom: 0.01,
+            max_zoom: 1e5,
+            generators: vec![],
+            custom_edges: None,
+            projection: Projection::Poincare,
+        }
+    }
+
+    /// Add or drop generator styles to match a tiling of this rank, keeping existing ones.
+    pub fn resize_generators(&mut self, rank: usize) {
+        let len = self.generators.len();
+        self.generators.truncate(rank);
+        self.generators.extend((len..rank).map(GeneratorStyle::new));
+        if let Some(edges) = &mut self.custom_edges {
+            edges.resize(rank, true);
+        }
+    }
+
+    /// How far the cell and vertex outlines are turned from their mirrors, a quarter turn at
+    /// full thickness.
+    pub fn outline_angles(&self) -> (f64, f64) {
+        let angle = |thickness: f32| std::f64::consts::FRAC_PI_2 * thickness as f64;
+        (
+            angle(self.cell_outline_thickness),
+            angle(self.vertex_outline_thickness),
+        )
+    }
+
+    /// Which mirrors bound a tile: the custom edges if set, otherwise the tiling's own.
+    pub fn edges(&self, tiling: &Tiling) -> Vec<bool> {
+        match &self.custom_edges {
+            Some(custom) => (tiling.edges.iter().enumerate())
+                .map(|(i, &edge)| custom.get(i).copied().unwrap_or(edge))
+                .collect(),
+            None => tiling.edges.clone(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct Settings {
+/// Colour and name for one generator in the debug overlays.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeneratorStyle {
+    /// Linear RGBA
+    pub col: [f32; 4],
+    pub label: String,
+}
+impl GeneratorStyle {
+    /// Red, green, blue, yellow, khaki and black, repeating for higher ranks
+    const DEFAULT_COLS: [[f32; 4]; 6] = [
+        [1., 0., 0., 1.],
+        [0., 1., 0., 1.],
+        [0., 0., 1., 1.],
+        [1., 1., 0., 1.],
+        [0.871, 0.791, 0.262, 1.],
+        [0., 0., 0., 1.],
+    ];
+
+    /// The style generator `i` starts with.
+    pub fn new(i: usize) -> Self {
+        Self {
+            col: Self::DEFAULT_COLS[i % Self::DEFAULT_COLS.len()],
+            label: i.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
     pub depth: u32,
     pub tile_limit: u32,
+    /// Maximum number of frames drawn per second
+    pub target_fps: u32,
+    /// Keep panning after a drag is released
+    pub pan_inertia: bool,
+    /// Fraction of the pan speed lost every sixtieth of a second while coasting
+    pub pan_friction: f32,
+    /// Restore the camera position on the next start
+    pub remember_camera: bool,
+    pub camera_bookmarks: Vec<Bookmark>,
+    pub tolerances: Tolerances,
     pub view_settings: ViewSettings,
     pub tiling_settings: TilingSettings,
 }
+impl Default for Settings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl Settings {
     pub fn new() -> Self {
         Self {
             depth: 50,
-            tile_limit: 500,
+            tile_limit: 1000,
+            target_fps: 60,
+            pan_inertia: false,
+            pan_friction: 0.1,
+            remember_camera: false,
+            camera_bookmarks: vec![],
+            tolerances: Tolerances::default(),
             view_settings: ViewSettings::new(),
             tiling_settings: TilingSettings::default(),
         }
     }
+
+    /// Settings saved when the app last closed, or the defaults.
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        storage
+            .and_then(|s| eframe::get_value(s, SETTINGS_KEY))
+            .unwrap_or_default()
+    }
+
+    /// Time to wait between repaints to stay within the target frame rate.
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_secs_f64(1. / self.target_fps.max(1) as f64)
+    }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct TilingSettings {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TilingSettings {
     pub schlafli: String,
     pub relations: Vec<String>,
     pub subgroup: String,
 }
 impl TilingSettings {
-    pub fn generate(&self) -> Result<Tiling, ()> {
+    pub fn generate(&self) -> Result<Tiling, TilingError> {
         Tiling::from_settings(&self)
     }
 }
@@ -119,7 +332,7 @@ impl Default for TilingSettings {
         // Self {
         //     schlafli: "{7,3}".to_string(),
         //     relations: vec!["0,2,1;8".to_string()],
-        //     subgroup: "0,1".to_string(),
+        //     subgroup: "0;1".to_string(),
         // }
         Self {
             schlafli: "{6,5,3}".to_string(),
@@ -128,21 +341,51 @@ impl Default for TilingSettings {
                 "1,3,2;5".to_string(),
                 "1,0,1,2,1,0,2,1,0,2,1,0,2,1,2;1".to_string(),
             ],
-            subgroup: "0,1,2".to_string(),
+            subgroup: "0;1;2".to_string(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct Schlafli(pub Vec<Option<usize>>);
-impl Schlafli {
-    pub fn new(rank: u8) -> Self {
-        match rank {
-            3 => Self::from_str("{7,3}").unwrap(),
-            4 => Self::from_str("{8,3,3}").unwrap(),
-            _ => todo!(),
+/// Entry of a Schläfli symbol, `p` or `p/d` for a star polygon wound `d` times around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchlafliEntry {
+    pub p: usize,
+    /// Density, 1 for an ordinary polygon
+    pub d: usize,
+}
+impl SchlafliEntry {
+    pub fn new(p: usize) -> Self {
+        Self { p, d: 1 }
+    }
+
+    /// Angle between the entry's two mirrors.
+    pub fn angle(&self) -> f64 {
+        std::f64::consts::PI * self.d as f64 / self.p as f64
+    }
+}
+impl fmt::Display for SchlafliEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.d {
+            1 => write!(f, "{}", self.p),
+            d => write!(f, "{}/{d}", self.p),
         }
     }
+}
+
+/// Schläfli symbol, with `None` for infinite entries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schlafli(pub Vec<Option<SchlafliEntry>>);
+impl Schlafli {
+    /// An example symbol of the given rank, or `None` for ranks that can't be drawn.
+    pub fn new(rank: u8) -> Option<Self> {
+        let symbol = match rank {
+            2 => "{6}",
+            3 => "{7,3}",
+            4 => "{8,3,3}",
+            _ => return None,
+        };
+        Self::from_str(symbol).ok()
+    }
 
     pub fn get_rels(&self) -> Vec<Vec<u8>> {
         let mut rels = vec![];
@@ -150,44 +393,506 @@ impl Schlafli {
             for x in 0..i {
                 rels.push((0..2).flat_map(|_| [x as u8, i as u8 + 1]).collect());
             }
+            // A star's mirrors meet at d/p of a half turn, but as d and p are coprime the
+            // rotation they generate still has order p
             if let Some(val) = val {
-                rels.push((0..val).flat_map(|_| [i as u8, i as u8 + 1]).collect());
+                rels.push((0..val.p).flat_map(|_| [i as u8, i as u8 + 1]).collect());
             }
         }
         rels
     }
 
-    pub fn get_mirrors(&self) -> Result<Vec<cga2d::Blade3>, ()> {
-        Ok(match self.rank() {
+    /// Mirrors bounding the fundamental region, drawn conformally: great circles of the
+    /// stereographically projected sphere, lines of the plane or geodesics of the Poincaré
+    /// disk, depending on the curvature returned alongside them.
+    pub fn get_mirrors(&self) -> Result<(GeometryKind, Vec<cga2d::Blade3>), MirrorError> {
+        let kind = self.geometry_kind();
+        // Mirrors are circles in the plane, standing for vectors in a space of signature
+        // (3,1), so no more than four can be independent. Rank 5 would need spheres.
+        let mirrors = match self.rank() {
+            2 => rank_2_mirrors(self.0[0]).to_vec(),
             3 => rank_3_mirrors(self.0[0], self.0[1])?.to_vec(),
+            4 if kind == GeometryKind::Spherical => return Err(MirrorError::SphericalRank4),
             4 => rank_4_mirrors(self.0[0], self.0[1], self.0[2])?.to_vec(),
-            _ => return Err(()),
-        })
+            rank => return Err(MirrorError::Rank(rank)),
+        };
+        Ok((kind, mirrors))
     }
 
     pub fn rank(&self) -> u8 {
         (self.0.len() + 1) as u8
     }
+
+    /// Curvature of the space tiled, from the leading minors of the Gram matrix of the
+    /// mirrors: all positive when spherical, with a vanishing determinant when Euclidean.
+    pub fn geometry_kind(&self) -> GeometryKind {
+        let n = self.rank() as usize;
+        let mut gram = vec![vec![0.; n]; n];
+        for i in 0..n {
+            gram[i][i] = 1.;
+            if i + 1 < n {
+                let angle = self.0[i].map_or(0., |x| x.angle());
+                gram[i][i + 1] = -angle.cos();
+                gram[i + 1][i] = -angle.cos();
+            }
+        }
+
+        let minors: Vec<f64> = (1..=n).map(|k| determinant(&gram, k)).collect();
+        let (det, proper) = minors.split_last().expect("Rank is at least 1");
+        if proper.iter().any(|&m| m <= 1e-9) {
+            GeometryKind::Hyperbolic
+        } else if det.abs() <= 1e-9 {
+            GeometryKind::Euclidean
+        } else if *det > 0. {
+            GeometryKind::Spherical
+        } else {
+            GeometryKind::Hyperbolic
+        }
+    }
+}
+impl fmt::Display for Schlafli {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let entries: Vec<String> = (self.0.iter())
+            .map(|x| x.map_or("i".to_string(), |x| x.to_string()))
+            .collect();
+        write!(f, "{{{}}}", entries.join(","))
+    }
+}
+
+/// Determinant of the leading `k` by `k` block of a matrix, by Gaussian elimination.
+fn determinant(matrix: &[Vec<f64>], k: usize) -> f64 {
+    let mut m: Vec<Vec<f64>> = matrix[..k].iter().map(|row| row[..k].to_vec()).collect();
+    let mut det = 1.;
+    for col in 0..k {
+        let Some(pivot) = (col..k).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))
+        else {
+            return 0.;
+        };
+        if m[pivot][col] == 0. {
+            return 0.;
+        }
+        if pivot != col {
+            m.swap(pivot, col);
+            det = -det;
+        }
+        det *= m[col][col];
+        for row in col + 1..k {
+            let factor = m[row][col] / m[col][col];
+            let pivot_row = m[col].clone();
+            for (x, p) in m[row].iter_mut().zip(pivot_row).skip(col) {
+                *x -= factor * p;
+            }
+        }
+    }
+    det
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeometryKind {
+    Spherical,
+    Euclidean,
+    Hyperbolic,
 }
 impl FromStr for Schlafli {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let r = Regex::new(&SCHLAFLI_PATTERN).unwrap(); // Eg. {6,4}, { 7, 3,  4}, {5,i}
-        if let Some(s) = r.captures(s.trim()) {
-            let s = s
-                .get(1)
-                .expect("Guaranteed by regex")
-                .as_str()
-                .split(",")
-                .map(|d| match d.trim() {
-                    "i" => None,
-                    x => Some(x.parse().expect("Guaranteed by regex")),
-                })
-                .collect();
-            Ok(Self(s))
-        } else {
-            Err(())
+        parse_schlafli(s).map(Self).map_err(|_| ())
+    }
+}
+
+/// Where and why a Schläfli symbol failed to parse.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchlafliError {
+    /// Index of the offending character, counted from 0
+    pub position: usize,
+    pub reason: &'static str,
+}
+impl fmt::Display for SchlafliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.reason, self.position)
+    }
+}
+
+/// Parse a Schläfli symbol with 1 to 3 entries, each a number, a star `p/d` with `d` coprime
+/// to and less than half of `p`, or `i`, `inf` or `∞` for infinity. The fraction slash `⁄`
+/// may stand in for `/`, and whitespace is allowed between any two parts.
+/// Eg. {6,4}, { 7, 3,  4}, {5,i}, {5,∞}, {5 / 2,5}, {5⁄2,5}, {8}
+pub fn parse_schlafli(string: &str) -> Result<Vec<Option<SchlafliEntry>>, SchlafliError> {
+    let chars: Vec<char> = string.chars().collect();
+    let err = |position, reason| SchlafliError { position, reason };
+    let skip_whitespace = |pos: &mut usize| {
+        while chars.get(*pos).is_some_and(|c| c.is_whitespace()) {
+            *pos += 1;
+        }
+    };
+
+    let parse_number = |pos: &mut usize| {
+        let start = *pos;
+        while chars.get(*pos).is_some_and(|c| c.is_ascii_digit()) {
+            *pos += 1;
+        }
+        let digits: String = chars[start..*pos].iter().collect();
+        digits.parse().map_err(|_| err(start, "number too large"))
+    };
+
+    let mut pos = 0;
+    skip_whitespace(&mut pos);
+    if chars.get(pos) != Some(&'{') {
+        return Err(err(pos, "expected '{'"));
+    }
+    pos += 1;
+
+    let mut entries = vec![];
+    loop {
+        skip_whitespace(&mut pos);
+        match chars.get(pos) {
+            Some('i') => {
+                pos += if chars[pos..].starts_with(&['i', 'n', 'f']) {
+                    3
+                } else {
+                    1
+                };
+                entries.push(None);
+            }
+            Some('∞') => {
+                pos += 1;
+                entries.push(None);
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let p = parse_number(&mut pos)?;
+                let mut d = 1;
+                let mut after_p = pos;
+                skip_whitespace(&mut after_p);
+                if matches!(chars.get(after_p), Some('/' | '⁄')) {
+                    pos = after_p + 1;
+                    skip_whitespace(&mut pos);
+                    let d_start = pos;
+                    if !chars.get(pos).is_some_and(|c| c.is_ascii_digit()) {
+                        return Err(err(pos, "expected density after '/'"));
+                    }
+                    d = parse_number(&mut pos)?;
+                    if d == 0 || 2 * d >= p {
+                        return Err(err(d_start, "density must be less than half the entry"));
+                    }
+                    if gcd(p, d) != 1 {
+                        return Err(err(d_start, "density must be coprime to the entry"));
+                    }
+                }
+                entries.push(Some(SchlafliEntry { p, d }));
+            }
+            _ => return Err(err(pos, "expected number, 'i' or '∞'")),
+        }
+
+        skip_whitespace(&mut pos);
+        match chars.get(pos) {
+            Some(',') if entries.len() < 3 => pos += 1,
+            Some(',') => return Err(err(pos, "at most 3 entries are allowed")),
+            Some('}') => break,
+            _ => return Err(err(pos, "expected ',' or '}'")),
+        }
+    }
+    pos += 1;
+
+    skip_whitespace(&mut pos);
+    if pos < chars.len() {
+        return Err(err(pos, "unexpected text after '}'"));
+    }
+    Ok(entries)
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cosine of the angle two mirrors meet at, from the inversive product of the circles
+    /// or lines they unpack to. The sign depends on their orientations.
+    fn cos_between(a: cga2d::Blade3, b: cga2d::Blade3) -> f64 {
+        // Coefficients of A(x² + y²) + Bx + Cy + D = 0
+        let coefficients = |m: cga2d::Blade3| match m.unpack(Tolerances::DEFAULT.line) {
+            cga2d::LineOrCircle::Line { a, b, c } => [0., a, b, c],
+            cga2d::LineOrCircle::Circle { cx, cy, r } => {
+                [1., -2. * cx, -2. * cy, cx * cx + cy * cy - r * r]
+            }
+        };
+        let product = |[a1, b1, c1, d1]: [f64; 4], [a2, b2, c2, d2]: [f64; 4]| {
+            b1 * b2 + c1 * c2 - 2. * a1 * d2 - 2. * a2 * d1
+        };
+        let (a, b) = (coefficients(a), coefficients(b));
+        product(a, b) / (product(a, a) * product(b, b)).sqrt()
+    }
+
+    #[test]
+    fn mirror_angles() {
+        for symbol in [
+            "{6}", "{5/2}", "{i}", "{4,4}", "{7,3}", "{5,3,4}", "{6,5,3}",
+        ] {
+            let schlafli = Schlafli::from_str(symbol).unwrap();
+            let (_, mirrors) = schlafli.get_mirrors().unwrap();
+            assert_eq!(mirrors.len(), schlafli.rank() as usize);
+            for i in 0..mirrors.len() {
+                for j in i + 1..mirrors.len() {
+                    // Mirrors further apart than neighbours in the symbol are perpendicular
+                    let angle = match j - i {
+                        1 => schlafli.0[i].map_or(0., |x| x.angle()),
+                        _ => std::f64::consts::FRAC_PI_2,
+                    };
+                    let cos = cos_between(mirrors[i], mirrors[j]).abs();
+                    assert!(
+                        (cos - angle.cos()).abs() < 1e-9,
+                        "{symbol}: mirrors {i} and {j}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mirrors_report_curvature() {
+        let mirrors = |symbol: &str| Schlafli::from_str(symbol).unwrap().get_mirrors().unwrap();
+        assert_eq!(mirrors("{4,3}").0, GeometryKind::Spherical);
+        assert_eq!(mirrors("{7,3}").0, GeometryKind::Hyperbolic);
+        let (kind, flat) = mirrors("{6,3}");
+        assert_eq!(kind, GeometryKind::Euclidean);
+        // Flat mirrors are drawn straight
+        for mirror in flat {
+            assert!(matches!(
+                mirror.unpack(Tolerances::DEFAULT.line),
+                cga2d::LineOrCircle::Line { .. }
+            ));
+        }
+    }
+
+    #[test]
+    fn curvature_of_typed_symbols() {
+        use GeometryKind::*;
+        for (symbol, kind) in [
+            ("{6}", Spherical),
+            ("{i}", Euclidean),
+            ("{3,5}", Spherical),
+            ("{5/2,5}", Spherical),
+            ("{4,4}", Euclidean),
+            ("{3,6}", Euclidean),
+            ("{7,3}", Hyperbolic),
+            ("{4,i}", Hyperbolic),
+            ("{3,3,5}", Spherical),
+            ("{4,3,4}", Euclidean),
+            ("{5,3,4}", Hyperbolic),
+        ] {
+            let schlafli = Schlafli::from_str(symbol).unwrap();
+            assert_eq!(schlafli.geometry_kind(), kind, "{symbol}");
+        }
+    }
+
+    #[test]
+    fn star_mirrors_meet_at_density_angle() {
+        let star = Schlafli::from_str("{5/2}").unwrap();
+        // The abstract group is that of the pentagon
+        assert_eq!(
+            star.get_rels(),
+            Schlafli::from_str("{5}").unwrap().get_rels()
+        );
+        let (kind, mirrors) = star.get_mirrors().unwrap();
+        assert_eq!(kind, GeometryKind::Spherical);
+        let cos = cos_between(mirrors[0], mirrors[1]).abs();
+        assert!((cos - (0.4 * std::f64::consts::PI).cos()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rank_5_rejected() {
+        let error = parse_schlafli("{4,3,3,5}").unwrap_err();
+        assert_eq!(error.reason, "at most 3 entries are allowed");
+        let schlafli = Schlafli([4, 3, 3, 5].map(|p| Some(SchlafliEntry::new(p))).to_vec());
+        assert_eq!(schlafli.get_mirrors().unwrap_err(), MirrorError::Rank(5));
+        assert!(Schlafli::new(5).is_none());
+        for rank in 2..=4 {
+            assert_eq!(Schlafli::new(rank).unwrap().rank(), rank);
         }
     }
+
+    #[test]
+    fn malformed_relations_rejected() {
+        // Generators are single digits, so a longer number is malformed rather than
+        // overflowing a u8
+        assert_eq!(parse_relation("256,1;2", 3), Err(RelationError::Malformed));
+        assert_eq!(parse_relation("0,100;2", 3), Err(RelationError::Malformed));
+        assert_eq!(parse_relation("0,1;0", 3), Err(RelationError::ZeroRepeat));
+        for count in ["4000000000", "99999999999999999999999"] {
+            let relation = format!("0,1;{count}");
+            assert_eq!(
+                parse_relation(&relation, 3),
+                Err(RelationError::RepeatTooLarge)
+            );
+        }
+        let relation = format!("0,1;{MAX_RELATION_REPEAT}");
+        assert_eq!(
+            parse_relation(&relation, 3).unwrap().len(),
+            2 * MAX_RELATION_REPEAT
+        );
+        // Non-ASCII digits match the pattern but don't parse
+        assert_eq!(parse_relation("0,1;٣", 3), Err(RelationError::Malformed));
+    }
+
+    #[test]
+    fn relation_generators_checked_against_rank() {
+        assert_eq!(parse_relation("0,2,1;2", 3), Ok(vec![0, 2, 1, 0, 2, 1]));
+        assert_eq!(parse_relation(" 0, 1 ;3 ", 2), Ok(vec![0, 1, 0, 1, 0, 1]));
+        let error = parse_relation("0,2,1;8", 2).unwrap_err();
+        assert_eq!(
+            error,
+            RelationError::Generator {
+                generator: 2,
+                rank: 2
+            }
+        );
+        assert_eq!(error.to_string(), "no generator 2, only 0 to 1 exist");
+        // The first out-of-range generator is the one reported
+        assert_eq!(
+            parse_relation("0,4,3;1", 3),
+            Err(RelationError::Generator {
+                generator: 4,
+                rank: 3
+            })
+        );
+        for malformed in ["", "0,1", ";2", "0,,1;2", "a,b;2", "0,1;"] {
+            assert_eq!(
+                parse_relation(malformed, 3),
+                Err(RelationError::Malformed),
+                "{malformed:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn schlafli_forms_accepted() {
+        let star = Some(SchlafliEntry { p: 5, d: 2 });
+        let five = Some(SchlafliEntry::new(5));
+        for (symbol, expected) in [
+            ("{8}", vec![Some(SchlafliEntry::new(8))]),
+            (
+                " { 7, 3,  4} ",
+                [7, 3, 4].map(|p| Some(SchlafliEntry::new(p))).to_vec(),
+            ),
+            ("{5,i}", vec![five, None]),
+            ("{5,inf}", vec![five, None]),
+            ("{5,∞}", vec![five, None]),
+            ("{5/2,5}", vec![star, five]),
+            ("{5 / 2,5}", vec![star, five]),
+            ("{5⁄2,5}", vec![star, five]),
+        ] {
+            assert_eq!(parse_schlafli(symbol), Ok(expected), "{symbol}");
+        }
+    }
+
+    #[test]
+    fn schlafli_error_positions() {
+        for (symbol, position, reason) in [
+            ("", 0, "expected '{'"),
+            ("  4,3}", 2, "expected '{'"),
+            ("{}", 1, "expected number, 'i' or '∞'"),
+            ("{4,,3}", 3, "expected number, 'i' or '∞'"),
+            ("{4 3}", 3, "expected ',' or '}'"),
+            ("{4,3", 4, "expected ',' or '}'"),
+            ("{5/}", 3, "expected density after '/'"),
+            ("{5/3}", 3, "density must be less than half the entry"),
+            ("{6/2}", 3, "density must be coprime to the entry"),
+            ("{4,3,3,5}", 6, "at most 3 entries are allowed"),
+            ("{4,3} x", 6, "unexpected text after '}'"),
+            ("{99999999999999999999999}", 1, "number too large"),
+            // Positions count characters, not bytes
+            ("{∞,∞ x}", 5, "expected ',' or '}'"),
+        ] {
+            let error = parse_schlafli(symbol).unwrap_err();
+            assert_eq!(error, SchlafliError { position, reason }, "{symbol:?}");
+        }
+        let error = parse_schlafli("{4,3} x").unwrap_err();
+        assert_eq!(error.to_string(), "unexpected text after '}' at position 6");
+    }
+
+    #[test]
+    fn outline_thicknesses_kept_apart() {
+        let mut view = ViewSettings {
+            cell_outline_thickness: 0.2,
+            vertex_outline_thickness: 0.6,
+            ..ViewSettings::default()
+        };
+        let (cell, vertex) = view.outline_angles();
+        assert!((cell - 0.1 * std::f64::consts::PI).abs() < 1e-6);
+        assert!((vertex - 0.3 * std::f64::consts::PI).abs() < 1e-6);
+        view.vertex_outline_thickness = 1.;
+        assert_eq!(view.outline_angles().0, cell);
+        assert_eq!(view.outline_angles().1, std::f64::consts::FRAC_PI_2);
+        // Settings saved before the split load as the cell thickness
+        let json = r#"{"outline_thickness": 0.25}"#;
+        let loaded: ViewSettings = serde_json::from_str(json).unwrap();
+        assert_eq!(loaded.cell_outline_thickness, 0.25);
+        assert_eq!(
+            loaded.vertex_outline_thickness,
+            ViewSettings::default().vertex_outline_thickness
+        );
+    }
+
+    #[test]
+    fn frame_interval_from_target_fps() {
+        let interval = |target_fps| {
+            let settings = Settings {
+                target_fps,
+                ..Settings::new()
+            };
+            settings.frame_interval().as_secs_f64()
+        };
+        assert!((interval(60) - 1. / 60.).abs() < 1e-9);
+        assert!((interval(144) - 1. / 144.).abs() < 1e-9);
+        assert_eq!(interval(1), 1.);
+        // Zero would never repaint, so it's treated as 1
+        assert_eq!(interval(0), 1.);
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let mut settings = Settings::new();
+        settings.tile_limit = 100;
+        settings.tiling_settings = TilingSettings {
+            schlafli: "{7,3}".to_string(),
+            relations: vec!["0,2,1;8".to_string(), "0,1;7".to_string()],
+            subgroup: "0;1".to_string(),
+        };
+        let json = serde_json::to_string(&settings).unwrap();
+        let loaded: Settings = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.tile_limit, 100);
+        assert_eq!(loaded.tiling_settings.schlafli, "{7,3}");
+        assert_eq!(
+            loaded.tiling_settings.relations,
+            settings.tiling_settings.relations
+        );
+        assert_eq!(loaded.tiling_settings.subgroup, "0;1");
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+    }
+
+    #[test]
+    fn generator_styles_follow_rank() {
+        let mut view = ViewSettings::default();
+        view.resize_generators(3);
+        assert_eq!(view.generators.len(), 3);
+        view.generators[1].label = "b".to_string();
+        view.custom_edges = Some(vec![false, true, false]);
+
+        view.resize_generators(4);
+        assert_eq!(view.generators.len(), 4);
+        assert_eq!(view.generators[1].label, "b");
+        assert_eq!(view.generators[3].col, GeneratorStyle::new(3).col);
+        assert_eq!(view.custom_edges, Some(vec![false, true, false, true]));
+
+        view.resize_generators(2);
+        assert_eq!(view.generators.len(), 2);
+        assert_eq!(view.generators[1].label, "b");
+        assert_eq!(view.custom_edges, Some(vec![false, true]));
+    }
 }
@@ -1,13 +1,23 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Arc,
+};
 
 use crate::{
+    config::TilingSettings,
     group::{Generator, Point, Word},
-    puzzle::{GripSignature, Puzzle},
+    notation::{parse_notation, NotationError, NotationMove},
+    puzzle::{GripSignature, Piece, Puzzle},
     tiling::{QuotientGroup, Tiling},
 };
 use cga2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Most states `ConformalPuzzle::solve_bfs` searches before giving up
+const BFS_STATE_LIMIT: usize = 200_000;
 
-pub(crate) struct ConformalPuzzle {
+pub struct ConformalPuzzle {
     pub puzzle: Puzzle,
     pub tiling: Arc<Tiling>,
     pub quotient_group: Arc<QuotientGroup>,
@@ -15,6 +25,12 @@ pub(crate) struct ConformalPuzzle {
     pub cut_circles: Vec<cga2d::Blade3>,
     pub cut_map: Vec<Option<usize>>,
     pub editor: Option<PuzzleEditor>,
+    /// Definition this puzzle was generated from
+    pub definition: PuzzleDefinition,
+    /// Moves applied, as `(attitude, twist, inverse)`, oldest first
+    history: Vec<(Word, usize, bool)>,
+    /// Moves undone, most recently undone last
+    redo_stack: Vec<(Word, usize, bool)>,
 }
 impl ConformalPuzzle {
     // pub fn new(tiling: Arc<Tiling>, tile_limit: u32) -> Result<Self, ()> {
@@ -76,7 +92,11 @@ impl ConformalPuzzle {
             quotient_group.tile_group.clone(),
             definition.piece_types.clone(),
         )?;
-        let base_twists = vec![Word(vec![Generator(0), Generator(1)])];
+        let rank = definition.tiling.rank;
+        if (definition.base_twists.iter()).any(|w| w.0.iter().any(|g| g.0 >= rank)) {
+            return Err(());
+        }
+        let base_twists = definition.base_twists.clone();
         Ok(Self {
             puzzle,
             tiling: definition.tiling.clone(),
@@ -85,29 +105,311 @@ impl ConformalPuzzle {
             cut_circles: definition.cut_circles.clone(),
             cut_map: definition.cut_map.clone(),
             editor: None,
+            definition: definition.clone(),
+            history: vec![],
+            redo_stack: vec![],
         })
     }
 
+    /// Apply a move, recording it so it can be undone.
     pub fn apply_move(
         &mut self,
         attitude: Word,
         twist: usize,
+        inverse: bool,
+    ) -> Result<(), MoveError> {
+        self.apply_move_unrecorded(&attitude, twist, inverse)?;
+        self.history.push((attitude, twist, inverse));
+        self.redo_stack.clear();
+        Ok(())
+    }
+
+    /// Whether `apply_move` would accept a move.
+    pub fn can_apply_move(&self, attitude: &Word, twist: usize, inverse: bool) -> bool {
+        self.check_move(attitude, twist, inverse).is_ok()
+    }
+
+    /// Twists `apply_move` would accept about a grip, as `(base_twist_index, inverse)`.
+    pub fn available_twists(&self, grip: &Point) -> Vec<(usize, bool)> {
+        let Some(attitude) = self.grip_attitude(*grip) else {
+            return vec![];
+        };
+        (0..self.base_twists.len())
+            .flat_map(|twist| [(twist, false), (twist, true)])
+            // A reflection is its own inverse, so offering both would only repeat it
+            .filter(|&(twist, inverse)| !(inverse && self.is_reflection(twist)))
+            .filter(|&(twist, inverse)| self.can_apply_move(&attitude, twist, inverse))
+            .collect()
+    }
+
+    /// Whether a base twist is a reflection: an odd word which undoes itself, so mirrors
+    /// the pieces it moves and has no separate inverse.
+    pub fn is_reflection(&self, twist: usize) -> bool {
+        self.base_twists
+            .get(twist)
+            .is_some_and(|word| word.is_odd() && self.puzzle.elem_group.word_order(word) == Some(2))
+    }
+
+    /// Number of pieces left mirrored by reflections.
+    pub fn mirrored_piece_count(&self) -> usize {
+        (self.puzzle.pieces.iter())
+            .filter(|piece| piece.is_mirrored(&self.puzzle.elem_group))
+            .count()
+    }
+
+    /// Counts describing the size of the puzzle.
+    pub fn stats(&self) -> PuzzleStats {
+        let puzzle = &self.puzzle;
+        // Piece types may be images of each other, so count the orbits they fall into
+        let mut seen = HashSet::new();
+        let mut orbits = 0;
+        for sig in &puzzle.piece_types {
+            if seen.contains(&GripSignature::new(sig.0.clone())) {
+                continue;
+            }
+            orbits += 1;
+            let orbit = Puzzle::expand_signatures(
+                &puzzle.elem_group,
+                &puzzle.grip_group,
+                std::slice::from_ref(sig),
+            );
+            seen.extend(orbit.unwrap_or_default());
+        }
+        PuzzleStats {
+            pieces: puzzle.pieces.len(),
+            stickers: (puzzle.pieces.iter()).map(|p| p.home.0.len()).sum(),
+            piece_types: puzzle.piece_types.len(),
+            orbits,
+            grips: puzzle.grip_group.point_count(),
+            elements: puzzle.elem_group.point_count(),
+        }
+    }
+
+    /// Attitude taking the first grip to `grip`, as passed to `apply_move`.
+    pub fn grip_attitude(&self, grip: Point) -> Option<Word> {
+        let word = self.puzzle.grip_group.word_table.get(grip.0 as usize)?;
+        Some(word.inverse(&self.puzzle.grip_group))
+    }
+
+    /// Moves applied so far, as they'd be written in notation. Moves made by clicking may
+    /// have used a different attitude for their grip, flipping their direction if its
+    /// parity differs from the one notation uses.
+    pub fn move_log(&self) -> Vec<NotationMove> {
+        (self.history.iter())
+            .filter_map(|(attitude, twist, inverse)| {
+                let grip_group = &self.puzzle.grip_group;
+                let grip = grip_group.mul_word(&Point::INIT, &attitude.inverse(grip_group))?;
+                let written = self.grip_attitude(grip)?;
+                Some(NotationMove {
+                    grip,
+                    twist: *twist,
+                    inverse: (inverse ^ (attitude.is_odd() != written.is_odd()))
+                        && !self.is_reflection(*twist),
+                    repeat: 1,
+                })
+            })
+            .collect()
+    }
+
+    /// Number of moves that can be undone.
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Moves written in notation (see `notation::parse_notation`), as the
+    /// `(attitude, twist, inverse)` arguments of `apply_move`.
+    pub fn parse_moves(&self, notation: &str) -> Result<Vec<(Word, usize, bool)>, NotationError> {
+        let mut moves = vec![];
+        for (token, m) in parse_notation(notation)?.into_iter().enumerate() {
+            let err = |reason| NotationError { token, reason };
+            let attitude = self.grip_attitude(m.grip).ok_or(err("no such grip"))?;
+            if m.twist >= self.base_twists.len() {
+                return Err(err("no such twist"));
+            }
+            for _ in 0..m.repeat {
+                moves.push((attitude.clone(), m.twist, m.inverse));
+            }
+        }
+        Ok(moves)
+    }
+
+    /// Whether every piece is back where it started.
+    pub fn is_solved(&self) -> bool {
+        self.puzzle.is_solved()
+    }
+
+    /// Save the definition and moves made so far, to be replayed by `import_log`.
+    pub fn export_log(&self, tile_limit: u32) -> Result<String, ()> {
+        let log = SolveLog {
+            tile_limit,
+            definition: self.definition.clone(),
+            moves: self.history.clone(),
+        };
+        serde_json::to_string_pretty(&log).map_err(|_| ())
+    }
+
+    /// Rebuild a puzzle saved by `export_log` and replay its moves. Fails if the saved
+    /// tiling no longer enumerates to a group of the same order.
+    pub fn import_log(json: &str) -> Result<Self, ()> {
+        let log: SolveLog = serde_json::from_str(json).map_err(|_| ())?;
+        let definition = log.definition;
+        let quotient_group = (definition.tiling)
+            .get_quotient_group(log.tile_limit, None, None)
+            .map_err(|_| ())?;
+        let order = |q: &QuotientGroup| (q.element_group.order(), q.tile_group.order());
+        if order(&quotient_group) != order(&definition.quotient_group) {
+            return Err(());
+        }
+        let mut puzzle = definition.generate_puzzle()?;
+        for (attitude, twist, inverse) in log.moves {
+            puzzle
+                .apply_move(attitude, twist, inverse)
+                .map_err(|_| ())?;
+        }
+        Ok(puzzle)
+    }
+
+    /// Take back the last move. Returns whether there was one.
+    pub fn undo(&mut self) -> Result<bool, MoveError> {
+        let Some((attitude, twist, inverse)) = self.history.pop() else {
+            return Ok(false);
+        };
+        if let Err(e) = self.apply_move_unrecorded(&attitude, twist, !inverse) {
+            self.history.push((attitude, twist, inverse));
+            return Err(e);
+        }
+        self.redo_stack.push((attitude, twist, inverse));
+        Ok(true)
+    }
+
+    /// Reapply the last undone move. Returns whether there was one.
+    pub fn redo(&mut self) -> Result<bool, MoveError> {
+        let Some((attitude, twist, inverse)) = self.redo_stack.pop() else {
+            return Ok(false);
+        };
+        if let Err(e) = self.apply_move_unrecorded(&attitude, twist, inverse) {
+            self.redo_stack.push((attitude, twist, inverse));
+            return Err(e);
+        }
+        self.history.push((attitude, twist, inverse));
+        Ok(true)
+    }
+
+    fn apply_move_unrecorded(
+        &mut self,
+        attitude: &Word,
+        twist: usize,
+        inverse: bool,
+    ) -> Result<(), MoveError> {
+        let (grip, turn) = self.check_move(attitude, twist, inverse)?;
+        self.puzzle
+            .apply_move(&grip, &turn)
+            .map_err(|_| MoveError::Invalid)
+    }
+
+    /// The grip a move turns about and the element it turns by, if it can be applied.
+    fn check_move(
+        &self,
+        attitude: &Word,
+        twist: usize,
+        inverse: bool,
+    ) -> Result<(Point, Word), MoveError> {
+        let (grip, turn) = self.resolve_move(attitude, twist, inverse)?;
+        // Check first so a truncated group can't leave the puzzle half moved
+        if !self.puzzle.move_is_enumerated(&grip, &turn) {
+            return Err(MoveError::Unenumerated);
+        }
+        if !self.puzzle.move_is_aligned(&grip, &turn) {
+            return Err(MoveError::Jumbling);
+        }
+        if !self.puzzle.can_apply(&grip, &turn) {
+            return Err(MoveError::Blocked);
+        }
+        Ok((grip, turn))
+    }
+
+    /// The grip a move turns about and the element it turns by, whatever state the puzzle
+    /// is in.
+    fn resolve_move(
+        &self,
+        attitude: &Word,
+        twist: usize,
         mut inverse: bool,
-    ) -> Result<(), ()> {
-        if attitude.0.len() % 2 == 1 {
+    ) -> Result<(Point, Word), MoveError> {
+        if attitude.is_odd() {
             inverse = !inverse;
         }
         let grip = self
             .puzzle
             .grip_group
-            .mul_word(&Point::INIT, &attitude.inverse())
-            .ok_or(())?;
-        let twist = &mut self.base_twists[twist].clone();
+            .mul_word(&Point::INIT, &attitude.inverse(&self.puzzle.grip_group))
+            .ok_or(MoveError::Unenumerated)?;
+        let twist = &mut self
+            .base_twists
+            .get(twist)
+            .ok_or(MoveError::Invalid)?
+            .clone();
         if inverse {
-            *twist = twist.inverse();
+            *twist = twist.inverse(&self.puzzle.elem_group);
         }
-        let turn = &attitude * twist * attitude.inverse();
-        self.puzzle.apply_move(&grip, &turn)
+        let turn = attitude * twist * attitude.inverse(&self.puzzle.elem_group);
+        Ok((grip, turn))
+    }
+
+    /// Shortest sequence of moves solving the puzzle, found by breadth-first search, as the
+    /// `(attitude, twist, inverse)` arguments of `apply_move`. `None` if there's none within
+    /// `max_depth` moves, or more than `BFS_STATE_LIMIT` states would need searching.
+    pub fn solve_bfs(&self, max_depth: usize) -> Option<Vec<(Word, usize, bool)>> {
+        if self.puzzle.is_solved() {
+            return Some(vec![]);
+        }
+        let moves: Vec<_> = (0..self.puzzle.grip_group.point_count())
+            .filter_map(|g| self.grip_attitude(Point(g)))
+            .flat_map(|attitude| {
+                (0..self.base_twists.len())
+                    .flat_map(move |twist| [false, true].map(|inv| (attitude.clone(), twist, inv)))
+            })
+            .filter_map(|m| Some((m.clone(), self.resolve_move(&m.0, m.1, m.2).ok()?)))
+            .collect();
+
+        // Pieces are swapped in and out of a single copy of the puzzle to try each move
+        let mut scratch = self.puzzle.clone();
+        let key = |pieces: &[Piece]| pieces.iter().map(|p| p.attitude).collect::<Vec<_>>();
+        let mut visited = HashSet::from([key(&self.puzzle.pieces)]);
+        // State each state was reached from, and the index of the move taken
+        let mut parents: Vec<Option<(usize, usize)>> = vec![None];
+        let mut frontier = vec![(self.puzzle.pieces.clone(), 0)];
+        for _ in 0..max_depth {
+            let mut next = vec![];
+            for (pieces, state) in &frontier {
+                for (i, (_, (grip, turn))) in moves.iter().enumerate() {
+                    scratch.pieces = pieces.clone();
+                    if !scratch.can_apply(grip, turn) || scratch.apply_move(grip, turn).is_err() {
+                        continue;
+                    }
+                    if !visited.insert(key(&scratch.pieces)) {
+                        continue;
+                    }
+                    parents.push(Some((*state, i)));
+                    if scratch.is_solved() {
+                        let mut path = vec![];
+                        let mut state = parents.len() - 1;
+                        while let Some((parent, i)) = parents[state] {
+                            path.push(moves[i].0.clone());
+                            state = parent;
+                        }
+                        path.reverse();
+                        return Some(path);
+                    }
+                    if visited.len() > BFS_STATE_LIMIT {
+                        return None;
+                    }
+                    next.push((std::mem::take(&mut scratch.pieces), parents.len() - 1));
+                }
+            }
+            frontier = next;
+        }
+        None
     }
 
     pub fn add_piece_types(&mut self, piece_types: Vec<GripSignature>) -> Result<(), ()> {
@@ -143,42 +445,186 @@ impl ConformalPuzzle {
             }
         })
     }
+
+    /// Move the cut circles. The pieces don't depend on where the cuts are, so the state is
+    /// kept.
+    pub fn set_cuts(&mut self, cuts: Vec<Cut>) {
+        self.definition.cuts = cuts;
+        self.definition.update_cut_circles();
+        self.cut_circles = self.definition.cut_circles.clone();
+    }
+}
+
+/// Reasons a move can't be applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    /// The move reaches elements beyond those enumerated within the tile limit
+    Unenumerated,
+    /// The twist doesn't fix its grip, so it would jumble the puzzle
+    Jumbling,
+    /// Pieces on the grip would land on pieces that stay put, as when a bandaged piece
+    /// sticks out of the turning region
+    Blocked,
+    /// The move doesn't make sense for this puzzle
+    Invalid,
+}
+
+/// Size of a puzzle, as counted by `ConformalPuzzle::stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PuzzleStats {
+    pub pieces: usize,
+    pub stickers: usize,
+    pub piece_types: usize,
+    /// Distinct orbits of the piece types under the element group
+    pub orbits: usize,
+    pub grips: u32,
+    /// Enumerated elements of the group the puzzle is built on
+    pub elements: u32,
 }
 
 /// Intermediate information for editing piece types
 pub struct PuzzleEditor {
     pub active_piece_type: Option<usize>,
     pub puzzle_def: PuzzleDefinition,
+    /// Whether the piece types make a sound puzzle, checked after every edit
+    pub validation: Result<(), DefinitionError>,
+    /// Word being typed in to add as a base twist
+    pub new_twist: String,
+    /// Edits made, oldest first
+    undo_stack: Vec<EditorAction>,
+    /// Edits undone, most recently undone last
+    redo_stack: Vec<EditorAction>,
 }
 impl PuzzleEditor {
     pub fn new(puzzle_def: PuzzleDefinition) -> Self {
         Self {
             active_piece_type: None,
+            validation: puzzle_def.validate(),
             puzzle_def,
+            new_twist: String::new(),
+            undo_stack: vec![],
+            redo_stack: vec![],
         }
     }
+
+    /// Add a grip to a piece type, or remove it if it's already there.
+    pub fn toggle_grip(&mut self, piece_type: usize, grip: Point) {
+        self.record(EditorAction::ToggleGrip { piece_type, grip });
+    }
+
+    /// Give a cut region to a piece type, or take it away if the piece type already has it.
+    pub fn toggle_region(&mut self, piece_type: usize, mask: usize) {
+        let Some(&old) = self.puzzle_def.cut_map.get(mask) else {
+            return;
+        };
+        let new = match old == Some(piece_type) {
+            true => None,
+            false => Some(piece_type),
+        };
+        self.record(EditorAction::SetRegion { mask, old, new });
+    }
+
+    pub fn add_piece_type(&mut self) {
+        self.record(EditorAction::AddPieceType);
+    }
+
+    /// Revert the last edit. Returns whether there was one.
+    pub fn undo(&mut self) -> bool {
+        let Some(action) = self.undo_stack.pop() else {
+            return false;
+        };
+        self.perform(&action, true);
+        self.redo_stack.push(action);
+        true
+    }
+
+    /// Make the last undone edit again. Returns whether there was one.
+    pub fn redo(&mut self) -> bool {
+        let Some(action) = self.redo_stack.pop() else {
+            return false;
+        };
+        self.perform(&action, false);
+        self.undo_stack.push(action);
+        true
+    }
+
+    fn record(&mut self, action: EditorAction) {
+        self.perform(&action, false);
+        self.undo_stack.push(action);
+        self.redo_stack.clear();
+    }
+
+    fn perform(&mut self, action: &EditorAction, reverse: bool) {
+        let def = &mut self.puzzle_def;
+        match *action {
+            EditorAction::ToggleGrip { piece_type, grip } => {
+                let sig = &mut def.piece_types[piece_type];
+                if sig.contains(&grip) {
+                    sig.remove(&grip);
+                } else {
+                    sig.insert(grip);
+                }
+            }
+            EditorAction::SetRegion { mask, old, new } => {
+                def.cut_map[mask] = if reverse { old } else { new };
+            }
+            EditorAction::AddPieceType if reverse => {
+                def.piece_types.pop();
+                let count = def.piece_types.len();
+                if self.active_piece_type.is_some_and(|t| t >= count) {
+                    self.active_piece_type = None;
+                }
+            }
+            EditorAction::AddPieceType => def.piece_types.push(GripSignature::CORE),
+        }
+        self.validation = self.puzzle_def.validate();
+    }
+}
+
+/// Edit made in the puzzle definition editor.
+#[derive(Debug, Clone, Copy)]
+enum EditorAction {
+    /// Toggling a grip undoes itself
+    ToggleGrip {
+        piece_type: usize,
+        grip: Point,
+    },
+    SetRegion {
+        mask: usize,
+        old: Option<usize>,
+        new: Option<usize>,
+    },
+    AddPieceType,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "PuzzleDefinitionData", try_from = "PuzzleDefinitionData")]
 pub struct PuzzleDefinition {
     pub tiling: Arc<Tiling>,
     pub quotient_group: Arc<QuotientGroup>,
     pub piece_types: Vec<GripSignature>,
+    /// Settings each cut circle was made from. Empty if the circles were loaded without them.
+    pub cuts: Vec<Cut>,
     pub cut_circles: Vec<cga2d::Blade3>,
     pub cut_map: Vec<Option<usize>>,
+    /// Twists about the first grip, selected by index when applying a move
+    pub base_twists: Vec<Word>,
 }
 impl PuzzleDefinition {
     pub fn new(tiling: Arc<Tiling>, quotient_group: Arc<QuotientGroup>) -> Self {
         let piece_types = vec![GripSignature(vec![Point::INIT])];
 
-        let ms = &tiling.mirrors;
-        let p = ms[0] & ms[1];
-        let cut_circle = -cga2d::slerp(
-            ms[2],
-            -ms[2].connect(p).connect(p),
-            std::f64::consts::PI / 6.,
-        );
-
-        let cut_circles = vec![cut_circle, (ms[1] * ms[0]).sandwich(cut_circle)];
+        let cuts = vec![
+            Cut::default(),
+            Cut {
+                turns: 1,
+                ..Cut::default()
+            },
+        ];
+        let cut_circles = cuts
+            .iter()
+            .map(|cut| cut.circle(&tiling))
+            .collect::<Vec<_>>();
         let cut_map = (0..1 << cut_circles.len())
             .map(|i| if i < 1 { Some(i) } else { None })
             .collect();
@@ -187,15 +633,38 @@ impl PuzzleDefinition {
             tiling,
             quotient_group,
             piece_types,
+            cuts,
             cut_circles,
             cut_map,
+            base_twists: Self::default_base_twists(),
         }
     }
 
+    /// Rebuild the cut circles after their settings change.
+    pub fn update_cut_circles(&mut self) {
+        if self.cuts.len() == self.cut_circles.len() {
+            self.cut_circles = (self.cuts.iter())
+                .map(|cut| cut.circle(&self.tiling))
+                .collect();
+        }
+    }
+
+    fn default_base_twists() -> Vec<Word> {
+        vec![Word(vec![Generator(0), Generator(1)])]
+    }
+
     pub fn generate_puzzle(&self) -> Result<ConformalPuzzle, ()> {
         ConformalPuzzle::from_definition(self)
     }
 
+    pub fn to_json(&self) -> Result<String, ()> {
+        serde_json::to_string_pretty(self).map_err(|_| ())
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ()> {
+        serde_json::from_str(json).map_err(|_| ())
+    }
+
     pub fn get_cut_mask(&self, point: cga2d::Blade1) -> usize {
         self.cut_circles.iter().enumerate().fold(0, |m, (i, c)| {
             if !(*c ^ point) > 0. {
@@ -205,4 +674,560 @@ impl PuzzleDefinition {
             }
         })
     }
+
+    /// Cut regions making up a piece type.
+    pub fn piece_type_masks(&self, piece_type: usize) -> Vec<usize> {
+        (self.cut_map.iter().enumerate())
+            .filter(|(_, t)| **t == Some(piece_type))
+            .map(|(mask, _)| mask)
+            .collect()
+    }
+
+    /// Check that every grip is on some piece, and that no two piece types make the same
+    /// piece, which would leave one of them without pieces of its own.
+    pub fn validate(&self) -> Result<(), DefinitionError> {
+        let element_group = &self.quotient_group.element_group;
+        let tile_group = &self.quotient_group.tile_group;
+        let mut owners = HashMap::new();
+        for (i, sig) in self.piece_types.iter().enumerate() {
+            let sigs =
+                Puzzle::expand_signatures(element_group, tile_group, std::slice::from_ref(sig))
+                    .map_err(|()| DefinitionError::Unenumerated)?;
+            for sig in sigs {
+                if let Some(first) = owners.insert(sig, i) {
+                    return Err(DefinitionError::Overlap { first, second: i });
+                }
+            }
+        }
+        let uncovered = self
+            .uncovered_grips()
+            .map_err(|()| DefinitionError::Unenumerated)?;
+        match uncovered.is_empty() {
+            true => Ok(()),
+            false => Err(DefinitionError::Uncovered(uncovered)),
+        }
+    }
+
+    /// Grips which don't belong to any piece.
+    pub fn uncovered_grips(&self) -> Result<Vec<Point>, ()> {
+        let tile_group = &self.quotient_group.tile_group;
+        let sigs = Puzzle::expand_signatures(
+            &self.quotient_group.element_group,
+            tile_group,
+            &self.piece_types,
+        )?;
+        let mut covered = vec![false; tile_group.point_count() as usize];
+        for grip in sigs.iter().flat_map(|sig| &sig.0) {
+            covered[grip.0 as usize] = true;
+        }
+        Ok((0..tile_group.point_count())
+            .filter(|&g| !covered[g as usize])
+            .map(Point)
+            .collect())
+    }
+}
+
+/// Cut around the vertex where the first two mirrors meet.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Cut {
+    /// How far the circle is swung from the third mirror towards the vertex, in radians.
+    /// Larger angles bring it closer to the vertex.
+    pub angle: f64,
+    /// Number of times the circle is rotated about the vertex
+    pub turns: u32,
+}
+impl Default for Cut {
+    fn default() -> Self {
+        Self {
+            angle: std::f64::consts::PI / 6.,
+            turns: 0,
+        }
+    }
+}
+impl Cut {
+    pub fn circle(&self, tiling: &Tiling) -> cga2d::Blade3 {
+        let ms = &tiling.mirrors;
+        let p = ms[0] & ms[1];
+        // Without a third mirror, cut near the circle around the centre instead
+        let far = ms
+            .get(2)
+            .copied()
+            .unwrap_or_else(|| tiling.orthogonal_circle());
+        let circle = -cga2d::slerp(far, -far.connect(p).connect(p), self.angle);
+        (0..self.turns).fold(circle, |c, _| (ms[1] * ms[0]).sandwich(c))
+    }
+}
+
+/// Why a puzzle definition's piece types don't make a sound puzzle.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefinitionError {
+    /// Moving a piece type around reaches tiles beyond the tile limit
+    Unenumerated,
+    /// Grips which no piece covers, drawn as holes
+    Uncovered(Vec<Point>),
+    /// Two piece types whose pieces coincide
+    Overlap { first: usize, second: usize },
+}
+impl fmt::Display for DefinitionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unenumerated => write!(f, "piece types reach past the tile limit"),
+            Self::Uncovered(grips) => {
+                write!(f, "{} grips are not covered by any piece", grips.len())
+            }
+            Self::Overlap { first, second } => {
+                write!(f, "piece types {first} and {second} make the same pieces")
+            }
+        }
+    }
+}
+
+/// Stored form of a solve in progress.
+#[derive(Serialize, Deserialize)]
+struct SolveLog {
+    tile_limit: u32,
+    definition: PuzzleDefinition,
+    /// Moves as passed to `apply_move`, oldest first
+    moves: Vec<(Word, usize, bool)>,
+}
+
+/// Stored form of a `PuzzleDefinition`. The tiling is rebuilt from its settings, and the
+/// group kept as is so grips still refer to the same tiles.
+#[derive(Serialize, Deserialize)]
+struct PuzzleDefinitionData {
+    tiling_settings: TilingSettings,
+    quotient_group: QuotientGroup,
+    piece_types: Vec<GripSignature>,
+    #[serde(default)]
+    cuts: Vec<Cut>,
+    /// Components of each cut circle
+    cut_circles: Vec<[f64; 4]>,
+    cut_map: Vec<Option<usize>>,
+    #[serde(default = "PuzzleDefinition::default_base_twists")]
+    base_twists: Vec<Word>,
+}
+impl From<PuzzleDefinition> for PuzzleDefinitionData {
+    fn from(def: PuzzleDefinition) -> Self {
+        Self {
+            tiling_settings: def.tiling.settings.clone(),
+            quotient_group: (*def.quotient_group).clone(),
+            piece_types: def.piece_types,
+            cuts: def.cuts,
+            cut_circles: (def.cut_circles.iter())
+                .map(|c| [c.mpx, c.mpy, c.mxy, c.pxy])
+                .collect(),
+            cut_map: def.cut_map,
+            base_twists: def.base_twists,
+        }
+    }
+}
+impl TryFrom<PuzzleDefinitionData> for PuzzleDefinition {
+    type Error = &'static str;
+
+    fn try_from(data: PuzzleDefinitionData) -> Result<Self, Self::Error> {
+        let tiling = (data.tiling_settings.generate()).map_err(|_| "invalid tiling settings")?;
+        let tile_group = &data.quotient_group.tile_group;
+        if tile_group.generator_count() != tiling.rank {
+            return Err("saved groups don't match the tiling");
+        }
+        let mut grips = data.piece_types.iter().flat_map(|sig| &sig.0);
+        if grips.any(|g| g.0 >= tile_group.point_count()) {
+            return Err("piece type on a tile outside the saved groups");
+        }
+        Ok(Self {
+            tiling: Arc::new(tiling),
+            quotient_group: Arc::new(data.quotient_group),
+            piece_types: data.piece_types,
+            cuts: data.cuts,
+            cut_circles: (data.cut_circles.iter())
+                .map(|&[mpx, mpy, mxy, pxy]| cga2d::Blade3 { mpx, mpy, mxy, pxy })
+                .collect(),
+            cut_map: data.cut_map,
+            base_twists: data.base_twists,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{presets, todd_coxeter::get_element_table_bounded};
+
+    /// The cube preset, with centres and edges.
+    fn cube() -> PuzzleDefinition {
+        let preset = (presets::builtin().into_iter())
+            .find(|p| p.name == "Cube")
+            .unwrap();
+        let tiling = Arc::new(preset.tiling_settings.generate().unwrap());
+        let quotient_group = tiling.get_quotient_group(1000, None, None).unwrap();
+        let neighbor = (quotient_group.tile_group)
+            .mul_gen(&Point::INIT, &Generator(2))
+            .unwrap();
+        let mut definition = PuzzleDefinition::new(tiling, quotient_group);
+        (definition.piece_types).push(GripSignature::new(vec![Point::INIT, neighbor]));
+        definition
+    }
+
+    #[test]
+    fn definition_json_round_trip() {
+        let definition = cube();
+        let loaded = PuzzleDefinition::from_json(&definition.to_json().unwrap()).unwrap();
+        let [puzzle, loaded] = [definition, loaded].map(|d| d.generate_puzzle().unwrap());
+        assert_eq!(puzzle.stats(), loaded.stats());
+        // JSON only keeps floats to within rounding
+        assert_eq!(puzzle.cut_circles.len(), loaded.cut_circles.len());
+        for (a, b) in puzzle.cut_circles.iter().zip(&loaded.cut_circles) {
+            let diff = [a.mpx - b.mpx, a.mpy - b.mpy, a.mxy - b.mxy, a.pxy - b.pxy];
+            assert!(diff.iter().all(|d| d.abs() < 1e-12));
+        }
+        assert_eq!(puzzle.cut_map, loaded.cut_map);
+        assert_eq!(puzzle.base_twists, loaded.base_twists);
+        assert_eq!(puzzle.puzzle.piece_types, loaded.puzzle.piece_types);
+        for (piece, loaded) in puzzle.puzzle.pieces.iter().zip(&loaded.puzzle.pieces) {
+            assert_eq!(piece.grips, loaded.grips);
+            assert_eq!(piece.attitude, loaded.attitude);
+        }
+    }
+
+    #[test]
+    fn notation_inverse_returns_to_solved() {
+        let mut puzzle = cube().generate_puzzle().unwrap();
+        for (attitude, twist, inverse) in puzzle.parse_moves("A B' C2 A3 D E'").unwrap() {
+            puzzle.apply_move(attitude, twist, inverse).unwrap();
+        }
+        assert_eq!(puzzle.history_len(), 9);
+        assert!(!puzzle.is_solved());
+
+        // Undo by writing out the inverse sequence
+        let undo: Vec<String> = (puzzle.move_log().into_iter().rev())
+            .map(|m| {
+                let inverse = !m.inverse;
+                NotationMove { inverse, ..m }.to_string()
+            })
+            .collect();
+        for (attitude, twist, inverse) in puzzle.parse_moves(&undo.join(" ")).unwrap() {
+            puzzle.apply_move(attitude, twist, inverse).unwrap();
+        }
+        assert!(puzzle.is_solved());
+    }
+
+    #[test]
+    fn bfs_solves_short_scramble() {
+        let mut puzzle = cube().generate_puzzle().unwrap();
+        assert_eq!(puzzle.solve_bfs(3), Some(vec![]));
+        for (attitude, twist, inverse) in puzzle.parse_moves("A B' C").unwrap() {
+            puzzle.apply_move(attitude, twist, inverse).unwrap();
+        }
+        assert!(!puzzle.is_solved());
+
+        let solution = puzzle.solve_bfs(3).unwrap();
+        assert!(solution.len() <= 3);
+        for (attitude, twist, inverse) in solution {
+            puzzle.apply_move(attitude, twist, inverse).unwrap();
+        }
+        assert!(puzzle.is_solved());
+    }
+
+    #[test]
+    fn move_past_enumerated_elements_reported() {
+        let definition = cube();
+        let mut puzzle = definition.generate_puzzle().unwrap();
+        // Only the generators themselves are known, so no turn can be followed
+        let rels = vec![[0, 1].repeat(4), [0, 2].repeat(2), [1, 2].repeat(3)];
+        puzzle.puzzle.elem_group = get_element_table_bounded(3, &rels, 1, 1000);
+        assert_eq!(
+            puzzle.apply_move(Word(vec![]), 0, false),
+            Err(MoveError::Unenumerated)
+        );
+        assert_eq!(
+            puzzle.apply_move(Word(vec![]), 1, false),
+            Err(MoveError::Invalid)
+        );
+        assert_eq!(puzzle.history_len(), 0);
+        assert!(puzzle.is_solved());
+    }
+
+    #[test]
+    fn undo_restores_pieces() {
+        let mut puzzle = cube().generate_puzzle().unwrap();
+        let positions = |puzzle: &ConformalPuzzle| {
+            (puzzle.puzzle.pieces.iter())
+                .map(|p| (p.attitude, p.grips.clone()))
+                .collect::<Vec<_>>()
+        };
+        for (attitude, twist, inverse) in puzzle.parse_moves("A B'").unwrap() {
+            puzzle.apply_move(attitude, twist, inverse).unwrap();
+        }
+        let before = positions(&puzzle);
+        let (attitude, twist, inverse) = puzzle.parse_moves("C").unwrap().remove(0);
+        puzzle.apply_move(attitude, twist, inverse).unwrap();
+        assert_ne!(positions(&puzzle), before);
+
+        assert_eq!(puzzle.undo(), Ok(true));
+        assert_eq!(positions(&puzzle), before);
+        assert_eq!(puzzle.history_len(), 2);
+        // Redoing and undoing again lands in the same place
+        assert_eq!(puzzle.redo(), Ok(true));
+        assert_eq!(puzzle.redo(), Ok(false));
+        assert_eq!(puzzle.undo(), Ok(true));
+        assert_eq!(positions(&puzzle), before);
+    }
+
+    #[test]
+    fn solve_log_round_trip() {
+        let mut puzzle = cube().generate_puzzle().unwrap();
+        for (attitude, twist, inverse) in puzzle.parse_moves("A B' C2 D").unwrap() {
+            puzzle.apply_move(attitude, twist, inverse).unwrap();
+        }
+        let loaded = ConformalPuzzle::import_log(&puzzle.export_log(1000).unwrap()).unwrap();
+        assert_eq!(loaded.history_len(), 5);
+        assert_eq!(loaded.puzzle.pieces.len(), puzzle.puzzle.pieces.len());
+        for (piece, loaded) in puzzle.puzzle.pieces.iter().zip(&loaded.puzzle.pieces) {
+            assert_eq!(piece.attitude, loaded.attitude);
+            assert_eq!(piece.grips, loaded.grips);
+            assert_eq!(piece.home, loaded.home);
+        }
+        assert!(ConformalPuzzle::import_log("{}").is_err());
+    }
+
+    #[test]
+    fn twist_order_returns_to_solved() {
+        let mut definition = cube();
+        // A quarter and a half turn of the front face
+        let quarter_turn = Word(vec![Generator(0), Generator(1)]);
+        definition.base_twists = vec![quarter_turn.clone(), &quarter_turn * &quarter_turn];
+        let mut puzzle = definition.generate_puzzle().unwrap();
+        for (twist, order) in [(0, 4), (1, 2)] {
+            assert_eq!(
+                (puzzle.puzzle.elem_group).word_order(&puzzle.base_twists[twist]),
+                Some(order)
+            );
+            for _ in 1..order {
+                puzzle.apply_move(Word(vec![]), twist, false).unwrap();
+                assert!(!puzzle.is_solved());
+            }
+            puzzle.apply_move(Word(vec![]), twist, false).unwrap();
+            assert!(puzzle.is_solved());
+        }
+    }
+
+    #[test]
+    fn available_twists_match_face_symmetry() {
+        let mut definition = cube();
+        let quarter_turn = Word(vec![Generator(0), Generator(1)]);
+        definition.base_twists = vec![quarter_turn.clone(), &quarter_turn * &quarter_turn];
+        let mut puzzle = definition.generate_puzzle().unwrap();
+        for grip in puzzle.puzzle.grip_group.points() {
+            let twists = puzzle.available_twists(&grip);
+            assert_eq!(twists.len(), 4);
+            // A half turn is its own inverse, leaving the three turns of a square
+            let attitude = puzzle.grip_attitude(grip).unwrap();
+            let mut results = HashSet::new();
+            for (twist, inverse) in twists {
+                puzzle.apply_move(attitude.clone(), twist, inverse).unwrap();
+                let attitudes: Vec<_> = puzzle.puzzle.pieces.iter().map(|p| p.attitude).collect();
+                results.insert(attitudes);
+                puzzle.undo().unwrap();
+            }
+            assert_eq!(results.len(), 3);
+        }
+    }
+
+    #[test]
+    fn move_log_counts_successful_moves() {
+        let mut puzzle = cube().generate_puzzle().unwrap();
+        let mut applied = 0;
+        for (attitude, twist, inverse) in puzzle.parse_moves("A B' C2").unwrap() {
+            // Only the one base twist exists, so the second of each pair fails
+            for twist in [twist, twist + 1] {
+                if puzzle.apply_move(attitude.clone(), twist, inverse).is_ok() {
+                    applied += 1;
+                }
+            }
+        }
+        assert_eq!(applied, 4);
+        assert_eq!(puzzle.history_len(), applied);
+        assert_eq!(puzzle.move_log().len(), applied);
+        puzzle.undo().unwrap();
+        assert_eq!(puzzle.move_log().len(), applied - 1);
+    }
+
+    #[test]
+    fn editor_undo_reverts_toggles() {
+        let mut editor = PuzzleEditor::new(cube());
+        let before = editor.puzzle_def.piece_types.clone();
+        let grip = Point(3);
+        assert!(!before[0].contains(&grip));
+
+        editor.toggle_grip(0, grip);
+        assert!(editor.puzzle_def.piece_types[0].contains(&grip));
+        assert!(editor.undo());
+        assert_eq!(editor.puzzle_def.piece_types, before);
+
+        // Taking a grip away is undone too, and redone
+        editor.toggle_grip(0, Point::INIT);
+        assert!(editor.undo());
+        assert_eq!(editor.puzzle_def.piece_types, before);
+        assert!(editor.redo());
+        assert!(!editor.puzzle_def.piece_types[0].contains(&Point::INIT));
+        assert!(editor.undo());
+        assert!(!editor.undo());
+        assert_eq!(editor.puzzle_def.piece_types, before);
+    }
+
+    #[test]
+    fn definition_validation() {
+        let mut definition = cube();
+        assert_eq!(definition.validate(), Ok(()));
+
+        // A piece type on no grips leaves every face a hole
+        definition.piece_types = vec![GripSignature::CORE];
+        let error = definition.validate().unwrap_err();
+        assert_eq!(
+            error,
+            DefinitionError::Uncovered((0..6).map(Point).collect())
+        );
+        assert_eq!(error.to_string(), "6 grips are not covered by any piece");
+
+        // Every face is an image of the first, so a second centre repeats the first
+        let other_face = Point(1);
+        definition.piece_types = vec![
+            GripSignature::new(vec![Point::INIT]),
+            GripSignature::new(vec![other_face]),
+        ];
+        assert_eq!(
+            definition.validate(),
+            Err(DefinitionError::Overlap {
+                first: 0,
+                second: 1
+            })
+        );
+    }
+
+    #[test]
+    fn reflection_twice_solves() {
+        let mut definition = cube();
+        // The front face's quarter turn, and its reflection in the first mirror
+        definition.base_twists.push(Word(vec![Generator(0)]));
+        let mut puzzle = definition.generate_puzzle().unwrap();
+        assert!(!puzzle.is_reflection(0));
+        assert!(puzzle.is_reflection(1));
+        assert_eq!(
+            puzzle.available_twists(&Point::INIT),
+            vec![(0, false), (0, true), (1, false)]
+        );
+
+        // The front centre and its four edges are flipped over
+        puzzle.apply_move(Word(vec![]), 1, false).unwrap();
+        assert_eq!(puzzle.mirrored_piece_count(), 5);
+        assert!(!puzzle.is_solved());
+        puzzle.apply_move(Word(vec![]), 1, false).unwrap();
+        assert_eq!(puzzle.mirrored_piece_count(), 0);
+        assert!(puzzle.is_solved());
+    }
+
+    #[test]
+    fn cube_stats() {
+        let mut definition = cube();
+        // A second centre type only repeats the first
+        (definition.piece_types).push(GripSignature::new(vec![Point(1)]));
+        let puzzle = definition.generate_puzzle().unwrap();
+        assert_eq!(
+            puzzle.stats(),
+            PuzzleStats {
+                pieces: 6 + 12,
+                stickers: 6 + 12 * 2,
+                piece_types: 3,
+                orbits: 2,
+                grips: 6,
+                elements: 48,
+            }
+        );
+    }
+
+    #[test]
+    fn uncovered_grips_listed() {
+        let mut definition = cube();
+        assert_eq!(definition.uncovered_grips(), Ok(vec![]));
+        // The edges alone still touch every face
+        definition.piece_types.remove(0);
+        assert_eq!(definition.uncovered_grips(), Ok(vec![]));
+        definition.piece_types = vec![GripSignature::CORE];
+        assert_eq!(
+            definition.uncovered_grips(),
+            Ok((0..6).map(Point).collect())
+        );
+    }
+
+    #[test]
+    fn jumbling_twist_rejected() {
+        let mut definition = cube();
+        // A third of a turn about a corner of the front face, which carries the face off
+        // itself, so the cut would have to pass through the middle of the faces
+        definition
+            .base_twists
+            .push(Word(vec![Generator(1), Generator(2)]));
+        let mut puzzle = definition.generate_puzzle().unwrap();
+        assert_eq!(
+            puzzle.apply_move(Word(vec![]), 1, false),
+            Err(MoveError::Jumbling)
+        );
+        assert_eq!(
+            puzzle.available_twists(&Point::INIT),
+            vec![(0, false), (0, true)]
+        );
+        assert!(puzzle.is_solved());
+        assert_eq!(puzzle.apply_move(Word(vec![]), 0, false), Ok(()));
+    }
+
+    #[test]
+    fn moved_cuts_keep_state() {
+        let mut puzzle = cube().generate_puzzle().unwrap();
+        for (attitude, twist, inverse) in puzzle.parse_moves("A B'").unwrap() {
+            puzzle.apply_move(attitude, twist, inverse).unwrap();
+        }
+        let attitudes: Vec<_> = puzzle.puzzle.pieces.iter().map(|p| p.attitude).collect();
+        let before = puzzle.cut_circles.clone();
+
+        let cuts = vec![
+            Cut {
+                angle: std::f64::consts::PI / 4.,
+                turns: 0,
+            },
+            Cut {
+                turns: 1,
+                ..Cut::default()
+            },
+        ];
+        puzzle.set_cuts(cuts.clone());
+        assert_eq!(puzzle.definition.cuts, cuts);
+        assert_ne!(puzzle.cut_circles[0], before[0]);
+        assert_eq!(puzzle.cut_circles[1], before[1]);
+        assert_eq!(puzzle.cut_circles, puzzle.definition.cut_circles);
+        assert_eq!(puzzle.history_len(), 2);
+        let moved: Vec<_> = puzzle.puzzle.pieces.iter().map(|p| p.attitude).collect();
+        assert_eq!(moved, attitudes);
+
+        // The puzzle and its definition agree on which cut region a point is in
+        let center = puzzle.tiling.fundamental_center();
+        let ms = &puzzle.tiling.mirrors;
+        for point in [center, ms[2].sandwich(center), ms[1].sandwich(center)] {
+            assert_eq!(
+                puzzle.get_cut_mask(point),
+                puzzle.definition.get_cut_mask(point)
+            );
+        }
+
+        // Four turns take a cut around the face and back
+        let turned = Cut {
+            turns: 4,
+            ..Cut::default()
+        };
+        let (a, b) = (
+            turned.circle(&puzzle.tiling),
+            Cut::default().circle(&puzzle.tiling),
+        );
+        let diff = [a.mpx - b.mpx, a.mpy - b.mpy, a.mxy - b.mxy, a.pxy - b.pxy];
+        assert!(diff.iter().all(|d| d.abs() < 1e-9));
+    }
 }
@@ -0,0 +1,29 @@
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> params: Params;
+
+struct Params {
+    /// Source texels per output pixel along each axis
+    factor: u32,
+}
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+}
+
+@vertex
+fn vertex(in: VertexInput) -> @builtin(position) vec4<f32> {
+    return vec4(in.position, 0.0, 1.0);
+}
+
+/// Average the block of source texels covering this pixel
+@fragment
+fn fragment(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let base = vec2<u32>(position.xy) * params.factor;
+    var sum = vec4(0.);
+    for (var i = 0u; i < params.factor; i++) {
+        for (var j = 0u; j < params.factor; j++) {
+            sum += textureLoad(source, base + vec2(i, j), 0);
+        }
+    }
+    return sum / f32(params.factor * params.factor);
+}
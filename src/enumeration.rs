@@ -0,0 +1,155 @@
+use std::sync::{atomic::AtomicBool, Arc};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{sync::atomic::Ordering, thread::JoinHandle};
+
+#[cfg(target_arch = "wasm32")]
+use discrete::todd_coxeter::EnumState;
+use discrete::{
+    tiling::{QuotientGroup, QuotientTables, Tiling},
+    todd_coxeter::TCError,
+};
+
+/// Groups of a tiling, and the tables behind them, as left by a finished enumeration.
+pub(crate) struct Enumerated {
+    pub tiling: Arc<Tiling>,
+    pub tables: QuotientTables,
+    pub result: Result<Arc<QuotientGroup>, TCError>,
+}
+impl Enumerated {
+    /// Enumerate the groups of a tiling up to `tile_limit`, carrying on from `tables`. Fresh
+    /// tables may be answered from the disk cache instead.
+    pub fn run(
+        tiling: Arc<Tiling>,
+        tile_limit: u32,
+        mut tables: QuotientTables,
+        cancel: Option<&AtomicBool>,
+    ) -> Self {
+        let result = if tables.element.iterations() > 0 {
+            tiling.get_quotient_group(tile_limit, cancel, Some(&mut tables))
+        } else {
+            tiling.get_quotient_group_cached(tile_limit, cancel, Some(&mut tables))
+        };
+        Self {
+            tiling,
+            tables,
+            result,
+        }
+    }
+}
+
+/// Enumeration running on a background thread. Dropping it cancels the enumeration.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct Enumeration {
+    cancel: Arc<AtomicBool>,
+    handle: Option<JoinHandle<Enumerated>>,
+}
+#[cfg(not(target_arch = "wasm32"))]
+impl Enumeration {
+    pub fn start(tiling: Arc<Tiling>, tile_limit: u32, tables: QuotientTables) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let thread_cancel = cancel.clone();
+        let handle = std::thread::spawn(move || {
+            Enumerated::run(tiling, tile_limit, tables, Some(&thread_cancel))
+        });
+        Self {
+            cancel,
+            handle: Some(handle),
+        }
+    }
+
+    /// Take the result once the enumeration has finished, or an error if its thread panicked.
+    /// `None` while it's still running.
+    pub fn poll(&mut self) -> Option<Result<Enumerated, ()>> {
+        if !self.handle.as_ref().is_some_and(|h| h.is_finished()) {
+            return None;
+        }
+        Some(self.handle.take()?.join().map_err(|_| ()))
+    }
+}
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for Enumeration {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Most cosets defined per frame on the web, which has no threads to enumerate on.
+#[cfg(target_arch = "wasm32")]
+const STEP_BUDGET: u32 = 256;
+
+/// Enumeration spread over frames, a few cosets each time it's polled.
+#[cfg(target_arch = "wasm32")]
+pub(crate) struct Enumeration {
+    tiling: Arc<Tiling>,
+    tile_limit: u32,
+    tables: Option<QuotientTables>,
+}
+#[cfg(target_arch = "wasm32")]
+impl Enumeration {
+    pub fn start(tiling: Arc<Tiling>, tile_limit: u32, tables: QuotientTables) -> Self {
+        Self {
+            tiling,
+            tile_limit,
+            tables: Some(tables),
+        }
+    }
+
+    /// Carry on with the enumeration, taking the result once it has finished. `None` while
+    /// there's more to do.
+    pub fn poll(&mut self) -> Option<Result<Enumerated, ()>> {
+        let tables = self.tables.as_mut()?;
+        let mut budget = STEP_BUDGET;
+        for table in [&mut tables.element, &mut tables.tile] {
+            let room = self.tile_limit.saturating_sub(table.iterations());
+            let steps = budget.min(room);
+            let start = table.iterations();
+            if table.step(steps) == EnumState::Done {
+                budget -= table.iterations() - start;
+                continue;
+            }
+            // Out of room, which `Enumerated::run` reports as reaching the limit
+            if steps == room {
+                break;
+            }
+            return None;
+        }
+        // The tables are as far along as they'll go, so this only builds the groups
+        let tables = self.tables.take()?;
+        Some(Ok(Enumerated::run(
+            self.tiling.clone(),
+            self.tile_limit,
+            tables,
+            None,
+        )))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use discrete::config::TilingSettings;
+
+    #[test]
+    fn background_matches_synchronous() {
+        let settings = TilingSettings {
+            schlafli: "{7,3}".to_string(),
+            relations: vec!["0,2,1;8".to_string()],
+            subgroup: "0;1".to_string(),
+        };
+        let tiling = Arc::new(settings.generate().unwrap());
+        let tables = QuotientTables::new(&tiling);
+        let mut enumeration = Enumeration::start(tiling, 1000, tables);
+        let background = loop {
+            match enumeration.poll() {
+                Some(enumerated) => break enumerated.unwrap().result.unwrap(),
+                None => std::thread::sleep(std::time::Duration::from_millis(1)),
+            }
+        };
+
+        // From a tiling of its own, so nothing is shared with the background one
+        let tiling = settings.generate().unwrap();
+        let synchronous = tiling.get_quotient_group(1000, None, None).unwrap();
+        assert_eq!(background.tile_group.point_count(), 24);
+        assert_eq!(background.to_bincode(), synchronous.to_bincode());
+    }
+}
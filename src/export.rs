@@ -0,0 +1,169 @@
+use eframe::egui;
+
+use discrete::geom;
+
+/// Settings for exporting a camera path as a numbered PNG sequence.
+pub(crate) struct AnimationExport {
+    pub start: Option<cga2d::Rotoflector>,
+    pub end: Option<cga2d::Rotoflector>,
+    /// Length of the path in seconds
+    pub duration: f32,
+    pub fps: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Directory the frames are written into, as `frame_00000.png` etc.
+    pub directory: String,
+    /// Set by the UI, handled once the view is next drawn
+    pub requested: bool,
+}
+impl AnimationExport {
+    pub fn new() -> Self {
+        Self {
+            start: None,
+            end: None,
+            duration: 2.,
+            fps: 30,
+            width: 1024,
+            height: 1024,
+            directory: "frames".to_string(),
+            requested: false,
+        }
+    }
+
+    /// Number of frames in the exported sequence, including both endpoints.
+    pub fn frame_count(&self) -> u32 {
+        ((self.duration * self.fps as f32).round() as u32).max(1) + 1
+    }
+
+    /// Camera at frame `i` of the sequence, or `None` if the path is incomplete.
+    pub fn camera_at(&self, i: u32) -> Option<cga2d::Rotoflector> {
+        let t = i as f64 / (self.frame_count() - 1) as f64;
+        Some(geom::interpolate_rotoflector(self.start?, self.end?, t))
+    }
+
+    pub fn frame_path(&self, i: u32) -> std::path::PathBuf {
+        std::path::Path::new(&self.directory).join(format!("frame_{i:05}.png"))
+    }
+}
+
+/// Settings for saving a single still of the current view, rendered at its own resolution.
+pub(crate) struct ImageExport {
+    pub width: u32,
+    pub height: u32,
+    pub path: String,
+    /// Set by the UI, handled once the view is next drawn
+    pub requested: bool,
+}
+impl ImageExport {
+    pub fn new() -> Self {
+        Self {
+            width: 2048,
+            height: 2048,
+            path: "view.png".to_string(),
+            requested: false,
+        }
+    }
+}
+
+/// Settings for writing the visible mirrors and cut circles as SVG.
+pub(crate) struct SvgExport {
+    /// Points sampled along each arc crossing the view
+    pub sample_count: usize,
+    pub path: String,
+    /// Set by the UI, handled once the view is next drawn
+    pub requested: bool,
+}
+impl SvgExport {
+    pub fn new() -> Self {
+        Self {
+            sample_count: 200,
+            path: "view.svg".to_string(),
+            requested: false,
+        }
+    }
+}
+
+/// SVG document of stroked curves, in view units with y pointing up, cropped to the
+/// view rectangle.
+pub(crate) struct SvgWriter {
+    half_width: f64,
+    half_height: f64,
+    /// Pixels per view unit, used for the document size and stroke widths
+    unit: f64,
+    body: String,
+}
+impl SvgWriter {
+    pub fn new(half_width: f64, half_height: f64, unit: f64) -> Self {
+        Self {
+            half_width,
+            half_height,
+            unit,
+            body: String::new(),
+        }
+    }
+
+    fn stroke(&self, color: egui::Color32, stroke_width: f32) -> String {
+        let [r, g, b, _] = color.to_array();
+        format!(
+            r##"fill="none" stroke="#{r:02x}{g:02x}{b:02x}" stroke-width="{}""##,
+            stroke_width as f64 / self.unit
+        )
+    }
+
+    pub fn polyline(
+        &mut self,
+        points: impl IntoIterator<Item = (f64, f64)>,
+        color: egui::Color32,
+        stroke_width: f32,
+    ) {
+        let points: Vec<String> = (points.into_iter())
+            .map(|(x, y)| format!("{x:.6},{y:.6}"))
+            .collect();
+        self.body += &format!(
+            "  <polyline points=\"{}\" {}/>\n",
+            points.join(" "),
+            self.stroke(color, stroke_width)
+        );
+    }
+
+    pub fn circle(&mut self, cx: f64, cy: f64, r: f64, color: egui::Color32, stroke_width: f32) {
+        self.body += &format!(
+            "  <circle cx=\"{cx:.6}\" cy=\"{cy:.6}\" r=\"{r:.6}\" {}/>\n",
+            self.stroke(color, stroke_width)
+        );
+    }
+
+    pub fn finish(self) -> String {
+        let (w, h) = (2. * self.half_width, 2. * self.half_height);
+        format!(
+            concat!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" ",
+                "viewBox=\"{} {} {} {}\">\n",
+                "<g transform=\"scale(1,-1)\">\n{}</g>\n</svg>\n"
+            ),
+            w * self.unit,
+            h * self.unit,
+            -self.half_width,
+            -self.half_height,
+            w,
+            h,
+            self.body
+        )
+    }
+}
+
+/// Write tightly packed 8-bit RGBA pixels to a PNG file.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn write_png(
+    path: &std::path::Path,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+) -> Result<(), ()> {
+    let file = std::fs::File::create(path).map_err(|_| ())?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|_| ())?;
+    writer.write_image_data(rgba).map_err(|_| ())
+}
@@ -0,0 +1,160 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+};
+
+use discrete::config::{GeometryKind, Schlafli, SchlafliEntry, TilingSettings};
+
+/// Every Schläfli symbol with `entry_count` entries between 3 and `max_entry` of the given
+/// kind whose element group is fully enumerated within `tile_limit`, when combined with the
+/// relations and subgroup of `template`. Stops early once `cancel` is set.
+pub(crate) fn enumerate_tilings(
+    template: &TilingSettings,
+    entry_count: usize,
+    max_entry: usize,
+    kind: GeometryKind,
+    tile_limit: u32,
+    cancel: &AtomicBool,
+) -> Vec<Schlafli> {
+    let mut symbols = vec![vec![]];
+    for _ in 0..entry_count {
+        symbols = (symbols.into_iter())
+            .flat_map(|s: Vec<Option<SchlafliEntry>>| {
+                (3..=max_entry).map(move |x| {
+                    let mut s = s.clone();
+                    s.push(Some(SchlafliEntry::new(x)));
+                    s
+                })
+            })
+            .collect();
+    }
+
+    let mut found = vec![];
+    for schlafli in symbols.into_iter().map(Schlafli) {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        if schlafli.geometry_kind() != kind {
+            continue;
+        }
+        let settings = TilingSettings {
+            schlafli: schlafli.to_string(),
+            ..template.clone()
+        };
+        let Ok(tiling) = settings.generate() else {
+            continue;
+        };
+        if tiling
+            .get_quotient_group(tile_limit, Some(cancel), None)
+            .is_ok()
+        {
+            found.push(schlafli);
+        }
+    }
+    found
+}
+
+/// Bounds for the gallery search, and the last search made.
+pub(crate) struct Gallery {
+    pub entry_count: usize,
+    pub max_entry: usize,
+    pub kind: GeometryKind,
+    pub search: Option<GallerySearch>,
+}
+impl Gallery {
+    pub fn new() -> Self {
+        Self {
+            entry_count: 2,
+            max_entry: 8,
+            kind: GeometryKind::Spherical,
+            search: None,
+        }
+    }
+}
+
+/// Search for tilings running on a background thread.
+pub(crate) struct GallerySearch {
+    cancel: Arc<AtomicBool>,
+    handle: Option<JoinHandle<Vec<Schlafli>>>,
+    pub results: Vec<Schlafli>,
+}
+impl GallerySearch {
+    pub fn start(
+        template: TilingSettings,
+        entry_count: usize,
+        max_entry: usize,
+        kind: GeometryKind,
+        tile_limit: u32,
+    ) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let thread_cancel = cancel.clone();
+        let handle = std::thread::spawn(move || {
+            enumerate_tilings(
+                &template,
+                entry_count,
+                max_entry,
+                kind,
+                tile_limit,
+                &thread_cancel,
+            )
+        });
+        Self {
+            cancel,
+            handle: Some(handle),
+            results: vec![],
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    /// Collect the results if the search has finished. Returns whether it is still running.
+    pub fn poll(&mut self) -> bool {
+        if self.handle.as_ref().is_some_and(|h| h.is_finished()) {
+            let handle = self.handle.take().expect("Checked above");
+            self.results = handle.join().unwrap_or_default();
+        }
+        self.handle.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(entry_count: usize, max_entry: usize, kind: GeometryKind) -> Vec<String> {
+        let template = TilingSettings {
+            schlafli: String::new(),
+            relations: vec![],
+            subgroup: String::new(),
+        };
+        let cancel = AtomicBool::new(false);
+        enumerate_tilings(&template, entry_count, max_entry, kind, 10_000, &cancel)
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn small_bounds_give_known_symbols() {
+        let platonic = search(2, 5, GeometryKind::Spherical);
+        assert_eq!(platonic, ["{3,3}", "{3,4}", "{3,5}", "{4,3}", "{5,3}"]);
+        // Without relations, flat and hyperbolic groups are infinite
+        assert!(search(2, 6, GeometryKind::Euclidean).is_empty());
+        assert!(search(2, 5, GeometryKind::Hyperbolic).is_empty());
+        // Finite rank 4 groups have no mirrors in the plane, so are skipped
+        assert!(search(3, 4, GeometryKind::Spherical).is_empty());
+    }
+
+    #[test]
+    fn cancelled_search_finds_nothing() {
+        let cancel = AtomicBool::new(true);
+        let template = TilingSettings::default();
+        let found = enumerate_tilings(&template, 2, 8, GeometryKind::Spherical, 10_000, &cancel);
+        assert!(found.is_empty());
+    }
+}
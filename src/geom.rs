@@ -1,22 +1,213 @@
 use core::f64;
+use std::fmt;
 
 use cga2d::prelude::*;
+use serde::{Deserialize, Serialize};
 
-fn angle(x: Option<usize>) -> f64 {
-    f64::consts::PI / x.map_or(f64::INFINITY, |x| x as f64)
+use crate::config::SchlafliEntry;
+
+/// Thresholds for deciding geometry near degenerate cases.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tolerances {
+    /// How far a point may sit outside a mirror and still count as inside it
+    pub inside: f64,
+    /// Passed to `Blade3::unpack` to decide whether a mirror is drawn as a line
+    pub line: f64,
+    /// Points closer together than this are treated as the same point
+    pub coincident: f64,
+    /// Largest coefficient of a motion that still counts as standing still
+    pub identity: f64,
+    /// Smallest drag, in pixels, that pans the camera
+    pub drag: f32,
+    /// Smallest scroll, in view units, that zooms the camera
+    pub scroll: f32,
+}
+impl Tolerances {
+    pub const DEFAULT: Self = Self {
+        inside: 1e-6,
+        line: 0.001,
+        coincident: 1e-9,
+        identity: 1e-5,
+        drag: 0.1,
+        scroll: 0.001,
+    };
+
+    /// Whether a point lies inside a mirror, allowing for rounding.
+    pub fn is_inside(&self, mirror: Blade3, point: Blade1) -> bool {
+        !(mirror ^ point) >= -self.inside
+    }
+}
+impl Default for Tolerances {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Motion taking `root_pos` to `end_pos` while fixing `boundary`, or `None` if the points
+/// are too close together to define one.
+pub fn drag_motion(
+    root_pos: Blade1,
+    end_pos: Blade1,
+    boundary: Blade3,
+    tolerances: &Tolerances,
+) -> Option<cga2d::Rotor> {
+    let (x1, y1) = root_pos.unpack_point();
+    let (x2, y2) = end_pos.unpack_point();
+    let distance = (x2 - x1).hypot(y2 - y1);
+    if distance.is_nan() || distance <= tolerances.coincident {
+        return None;
+    }
+
+    let init_refl = !(root_pos ^ end_pos) ^ !boundary; // get root_pos to end_pos
+    let f = end_pos ^ !boundary;
+    let final_refl = !(!init_refl ^ f) ^ f; // restore orientation fixing the "straight line" from root_pos to end_pos
+    let motion = final_refl * init_refl;
+
+    let mag2 = motion.mag2();
+    (mag2.is_finite() && mag2.abs() > 0.).then_some(motion)
+}
+
+/// Blend from `a` at `t = 0` to `b` at `t = 1`, following the motion between them. The
+/// motion is blended relative to `a`, so the path doesn't depend on where the two sit in
+/// the plane. Transforms of opposite handedness can't be joined continuously, so those
+/// jump halfway through.
+pub fn interpolate_rotoflector(
+    a: cga2d::Rotoflector,
+    b: cga2d::Rotoflector,
+    t: f64,
+) -> cga2d::Rotoflector {
+    let relative = crate::camera::to_components(&(a.rev() * b).normalize());
+    if relative[0] != 0. {
+        return if t < 0.5 { a } else { b };
+    }
+
+    // Both signs of a rotor act the same, so take the nearer one to avoid a half turn
+    let sign = if relative[1] < 0. { -1. } else { 1. };
+    let identity = crate::camera::to_components(&cga2d::Rotoflector::ident());
+    let mut components = identity;
+    for (c, (x, y)) in components
+        .iter_mut()
+        .zip(identity.iter().zip(&relative))
+        .skip(1)
+    {
+        *c = (1. - t) * x + t * sign * y;
+    }
+    (a * crate::camera::from_components(&components)).normalize()
+}
+
+/// Distance between two points of the hyperbolic plane of curvature -1, drawn as the
+/// Poincaré disk bounded by `boundary`. `None` if the boundary is a line or the points lie
+/// on opposite sides of it.
+pub fn hyperbolic_distance(
+    a: Blade1,
+    b: Blade1,
+    boundary: Blade3,
+    tolerances: &Tolerances,
+) -> Option<f64> {
+    Disk::new(boundary, tolerances)?.distance(a.unpack_point(), b.unpack_point())
+}
+
+/// Model of the hyperbolic plane that the view is drawn in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Projection {
+    /// Conformal, with mirrors drawn as circles
+    #[default]
+    Poincare,
+    /// Beltrami–Klein, with mirrors drawn as straight chords
+    Klein,
+}
+
+/// Disk bounding the hyperbolic plane as drawn, for converting between its models.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Disk {
+    pub cx: f64,
+    pub cy: f64,
+    pub r: f64,
+}
+impl Disk {
+    /// Disk bounded by a circle, or `None` for a line.
+    pub fn new(boundary: Blade3, tolerances: &Tolerances) -> Option<Self> {
+        match boundary.unpack(tolerances.line) {
+            cga2d::LineOrCircle::Circle { cx, cy, r } => Some(Self { cx, cy, r }),
+            cga2d::LineOrCircle::Line { .. } => None,
+        }
+    }
+
+    /// Distance between two points of the Poincaré disk, in the plane of curvature -1.
+    /// `None` if they lie on opposite sides of the boundary.
+    pub fn distance(&self, (x1, y1): (f64, f64), (x2, y2): (f64, f64)) -> Option<f64> {
+        let Self { cx, cy, r } = *self;
+        // Power of each point about the boundary, standing in for 1 - |p|² on the unit disk
+        let power = |x: f64, y: f64| (x - cx).powi(2) + (y - cy).powi(2) - r * r;
+        let chord2 = (x2 - x1).powi(2) + (y2 - y1).powi(2);
+        let cosh = 1. + 2. * r * r * chord2 / (power(x1, y1) * power(x2, y2));
+        (cosh.is_finite() && cosh >= 1.).then(|| cosh.acosh())
+    }
+
+    /// Position in the Klein model of a point in the Poincaré disk. A point outside the disk
+    /// lands on the same place as its reflection in the boundary.
+    pub fn poincare_to_klein(&self, x: f64, y: f64) -> (f64, f64) {
+        let (ux, uy) = ((x - self.cx) / self.r, (y - self.cy) / self.r);
+        let s = 2. / (1. + ux * ux + uy * uy);
+        (self.cx + self.r * s * ux, self.cy + self.r * s * uy)
+    }
+
+    /// Position in the Poincaré disk of a point in the Klein model. Points outside the disk
+    /// are left where they are.
+    pub fn klein_to_poincare(&self, x: f64, y: f64) -> (f64, f64) {
+        let (kx, ky) = ((x - self.cx) / self.r, (y - self.cy) / self.r);
+        let s = 1. / (1. + (1. - kx * kx - ky * ky).max(0.).sqrt());
+        (self.cx + self.r * s * kx, self.cy + self.r * s * ky)
+    }
+}
+
+/// Why no mirrors could be built for a Schläfli symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MirrorError {
+    /// Only ranks 2 to 4 can be drawn with circles in the plane
+    Rank(u8),
+    /// Four circles in the plane can't all meet at the angles of a finite group
+    SphericalRank4,
+    /// No mirror meets the ones before it at the angle of the entry at this index
+    Unrealizable { entry: usize },
+}
+impl fmt::Display for MirrorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rank(rank) => write!(f, "rank {rank} can't be drawn, only ranks 2 to 4"),
+            Self::SphericalRank4 => write!(f, "finite rank 4 groups would need spheres"),
+            Self::Unrealizable { entry } => write!(
+                f,
+                "entry {} can't be realized alongside the ones before it in any geometry",
+                entry + 1
+            ),
+        }
+    }
+}
+
+fn angle(x: Option<SchlafliEntry>) -> f64 {
+    x.map_or(0., |x| x.angle())
 }
 
-pub(crate) fn rank_3_mirrors(a: Option<usize>, b: Option<usize>) -> Result<[Blade3; 3], ()> {
+pub fn rank_2_mirrors(a: Option<SchlafliEntry>) -> [Blade3; 2] {
+    rank_2_mirrors_internal(angle(a)).map(|m| m.normalize())
+}
+
+pub fn rank_3_mirrors(
+    a: Option<SchlafliEntry>,
+    b: Option<SchlafliEntry>,
+) -> Result<[Blade3; 3], MirrorError> {
     let a1 = angle(a);
     let a2 = angle(b);
     rank_3_mirrors_internal(a1, a2)
 }
 
-pub(crate) fn rank_4_mirrors(
-    a: Option<usize>,
-    b: Option<usize>,
-    c: Option<usize>,
-) -> Result<[Blade3; 4], ()> {
+pub fn rank_4_mirrors(
+    a: Option<SchlafliEntry>,
+    b: Option<SchlafliEntry>,
+    c: Option<SchlafliEntry>,
+) -> Result<[Blade3; 4], MirrorError> {
     let a1 = angle(a);
     let a2 = angle(b);
     let a3 = angle(c);
@@ -28,7 +219,8 @@ pub(crate) fn rank_4_mirrors(
     Ok(mirrors)
 }
 
-fn rank_3_mirrors_internal(a1: f64, a2: f64) -> Result<[Blade3; 3], ()> {
+/// Two mirrors meeting at the origin, or parallel for a zero angle.
+fn rank_2_mirrors_internal(a1: f64) -> [Blade3; 2] {
     let x_unit = cga2d::point(1., 0.);
     let mirror1 = NO ^ x_unit ^ NI;
     let mirror2 = if a1 > 0. {
@@ -36,11 +228,19 @@ fn rank_3_mirrors_internal(a1: f64, a2: f64) -> Result<[Blade3; 3], ()> {
     } else {
         (NO << mirror1) ^ cga2d::point(0., 1.)
     };
+    [mirror1, mirror2]
+}
+
+fn rank_3_mirrors_internal(a1: f64, a2: f64) -> Result<[Blade3; 3], MirrorError> {
+    let x_unit = cga2d::point(1., 0.);
+    let [mirror1, mirror2] = rank_2_mirrors_internal(a1);
 
     // this is kind of magic? u is symmetric with the desired mirror3
     let q3 = (x_unit << mirror1) ^ !mirror2;
     let u = (mirror2 & q3).rotate(a2) ^ x_unit;
-    let vertex_2_3 = (u & mirror2).unpack_point_pair().ok_or(())?[0];
+    let vertex_2_3 = (u & mirror2)
+        .unpack_point_pair()
+        .ok_or(MirrorError::Unrealizable { entry: 1 })?[0];
     let mirror3 = !mirror1 ^ x_unit ^ vertex_2_3;
     // let mirror3 = mirror1.connect(u & mirror2);
     Ok([
@@ -57,11 +257,154 @@ fn rank_4_last_mirror_internal(
     a1: f64,
     a2: f64,
     a3: f64,
-) -> Result<Blade3, ()> {
+) -> Result<Blade3, MirrorError> {
+    let unrealizable = MirrorError::Unrealizable { entry: 2 };
     let mutual_perpendicular = !(mirror1 & mirror2 & mirror3);
+    // Out of the range of asin when the last angle is too wide for the first two
     let temp_angle = (a3.sin() * a1.sin() / a2.cos()).asin();
+    if !temp_angle.is_finite() {
+        return Err(unrealizable);
+    }
     let temp_line = cga2d::slerp(mirror1, !mutual_perpendicular ^ !mirror1 ^ NO, temp_angle);
-    let vertex_3_4 = (temp_line & mirror3).unpack_point_pair().ok_or(())?[1];
+    let vertex_3_4 = (temp_line & mirror3)
+        .unpack_point_pair()
+        .ok_or(unrealizable)?[1];
     let mirror4 = !mirror1 ^ !mirror2 ^ vertex_3_4;
     Ok(mirror4.normalize())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::is_near_identity;
+
+    /// Whether two transforms act the same, whatever their sign.
+    fn same(a: cga2d::Rotoflector, b: cga2d::Rotoflector) -> bool {
+        is_near_identity(&(a.rev() * b).normalize(), 1e-9)
+    }
+
+    /// Line through the origin at `angle` to the x axis.
+    fn line_at(angle: f64) -> Blade3 {
+        cga2d::line(-angle.sin(), angle.cos(), 0.)
+    }
+
+    #[test]
+    fn coincident_drag_leaves_camera() {
+        let tolerances = Tolerances::DEFAULT;
+        let boundary = cga2d::circle(NO, 1.);
+        let root_pos = cga2d::point(0.3, -0.2);
+        let camera = cga2d::Rotoflector::ident();
+        let unchanged = crate::camera::to_components(&camera);
+        let drag = |end_pos: Blade1| {
+            let moved = match drag_motion(root_pos, end_pos, boundary, &tolerances) {
+                Some(motion) => (motion * camera).normalize(),
+                None => camera,
+            };
+            crate::camera::to_components(&moved)
+        };
+        assert_eq!(drag(root_pos), unchanged);
+        let nudged = cga2d::point(0.3 + tolerances.coincident / 2., -0.2);
+        assert_eq!(drag(nudged), unchanged);
+        assert_ne!(drag(cga2d::point(0.4, -0.2)), unchanged);
+    }
+
+    #[test]
+    fn interpolation_endpoints_and_half_angle() {
+        let ident = cga2d::Rotoflector::ident();
+        let rotation: cga2d::Rotoflector = (line_at(0.6) * line_at(0.)).into();
+        let motion: cga2d::Rotoflector =
+            (cga2d::circle(cga2d::point(0.2, 0.1), 0.5) * line_at(0.)).into();
+        for (a, b) in [(ident, rotation), (rotation, motion), (motion, ident)] {
+            assert!(same(interpolate_rotoflector(a, b, 0.), a));
+            assert!(same(interpolate_rotoflector(a, b, 1.), b));
+        }
+        // Transforms of opposite handedness jump between them, but still end on each
+        let flector = rotation * line_at(0.3);
+        assert!(same(
+            interpolate_rotoflector(rotation, flector, 0.),
+            rotation
+        ));
+        assert!(same(
+            interpolate_rotoflector(rotation, flector, 1.),
+            flector
+        ));
+
+        // Halfway through a rotation is the rotation by half the angle
+        let half: cga2d::Rotoflector = (line_at(0.3) * line_at(0.)).into();
+        assert!(same(interpolate_rotoflector(ident, rotation, 0.5), half));
+    }
+
+    #[test]
+    fn inside_within_tolerance_of_mirror() {
+        let tolerances = Tolerances {
+            inside: 0.01,
+            ..Tolerances::DEFAULT
+        };
+        let mirror = line_at(0.);
+        // Whichever side of the x axis the mirror keeps, step out the other way
+        let outward = match tolerances.is_inside(mirror, cga2d::point(0., 1.)) {
+            true => -1.,
+            false => 1.,
+        };
+        assert!(!tolerances.is_inside(mirror, cga2d::point(0., outward)));
+        assert!(tolerances.is_inside(mirror, cga2d::point(0.5, 0.)));
+        assert!(tolerances.is_inside(mirror, cga2d::point(0., 0.005 * outward)));
+        assert!(!tolerances.is_inside(mirror, cga2d::point(0., 0.02 * outward)));
+        // The default is far stricter
+        let strict = Tolerances::DEFAULT;
+        assert!(!strict.is_inside(mirror, cga2d::point(0., 0.005 * outward)));
+        assert!(strict.is_inside(mirror, cga2d::point(0., 1e-7 * outward)));
+    }
+
+    #[test]
+    fn hyperbolic_distances() {
+        let unit = Disk {
+            cx: 0.,
+            cy: 0.,
+            r: 1.,
+        };
+        let distance = |disk: &Disk, a, b| disk.distance(a, b).unwrap();
+        assert_eq!(distance(&unit, (0.3, 0.4), (0.3, 0.4)), 0.);
+        // From the centre, a point at Euclidean radius t is 2 artanh(t) away
+        assert!((distance(&unit, (0., 0.), (0.5, 0.)) - 3f64.ln()).abs() < 1e-12);
+        assert!((distance(&unit, (0., 0.), (0., -0.9)) - 19f64.ln()).abs() < 1e-12);
+        let (a, b) = ((0.1, -0.6), (-0.7, 0.2));
+        assert!((distance(&unit, a, b) - distance(&unit, b, a)).abs() < 1e-12);
+        // Moving and scaling the disk along with the points leaves distances alone
+        let moved = Disk {
+            cx: 1.,
+            cy: -2.,
+            r: 3.,
+        };
+        let place = |(x, y): (f64, f64)| (1. + 3. * x, -2. + 3. * y);
+        assert!((distance(&moved, place(a), place(b)) - distance(&unit, a, b)).abs() < 1e-12);
+        // Points either side of the boundary aren't in the same plane
+        assert_eq!(unit.distance((0., 0.), (2., 0.)), None);
+    }
+
+    #[test]
+    fn klein_poincare_conversion() {
+        let disk = Disk {
+            cx: 1.,
+            cy: 2.,
+            r: 2.,
+        };
+        let near = |a: (f64, f64), b: (f64, f64)| (a.0 - b.0).hypot(a.1 - b.1) < 1e-12;
+        // Halfway out in the Poincaré disk is 4/5 of the way out in the Klein model
+        assert!(near(disk.poincare_to_klein(2., 2.), (2.6, 2.)));
+        assert!(near(disk.klein_to_poincare(2.6, 2.), (2., 2.)));
+        // The centre and the boundary stay put
+        assert!(near(disk.poincare_to_klein(1., 2.), (1., 2.)));
+        assert!(near(disk.poincare_to_klein(1., 0.), (1., 0.)));
+        assert!(near(disk.klein_to_poincare(1., 4.), (1., 4.)));
+        for point in [(0.3, 1.1), (2.2, 3.), (-0.4, 1.9)] {
+            let klein = disk.poincare_to_klein(point.0, point.1);
+            assert!(near(disk.klein_to_poincare(klein.0, klein.1), point));
+        }
+        // A point outside lands on its reflection in the boundary
+        assert!(near(
+            disk.poincare_to_klein(5., 2.),
+            disk.poincare_to_klein(2., 2.)
+        ));
+    }
+}
@@ -16,24 +16,45 @@ use eframe::{
 };
 use wgpu::TextureFormat;
 
-use crate::{
+use discrete::{
     config::ViewSettings,
     conformal_puzzle::ConformalPuzzle,
-    group::{Generator, Point},
+    geom::Disk,
+    group::{Point, Word},
+    puzzle::{GripSignature, Puzzle},
+    tiling::QuotientGroup,
 };
 
 pub(crate) struct GfxData {
     pub device: Arc<Device>,
     pub queue: Arc<Queue>,
+    /// Output texture, shown by egui and read back for exports
     pub texture: Texture,
     pub texture_id: TextureId,
-    pub pipeline: RenderPipeline,
+    /// Texels rendered per output pixel along each axis
+    supersampling: u32,
+    /// Larger render target averaged down into `texture` when supersampling
+    render_texture: Option<Texture>,
+    downsample_pipeline: RenderPipeline,
+    downsample_buffer: Buffer,
+    /// Shader compilation error in place of the pipeline if it failed to build
+    pub pipeline: Result<RenderPipeline, String>,
     pub vertex_buffer: Buffer,
     pub param_buffer: Buffer,
-    pub coset_buffer: Option<Buffer>,
+    pub coset_buffer: StorageBuffer,
+    /// Word length of each element, or `u32::MAX` if it's unreachable
+    pub distance_buffer: StorageBuffer,
     pub sticker_buffer: Option<Buffer>,
-    pub cut_buffer: Option<Buffer>,
-    pub outline_buffer: Option<Buffer>,
+    /// Previous sticker buffer, refilled and swapped in on the next regeneration
+    back_sticker_buffer: Option<Buffer>,
+    pub cut_buffer: StorageBuffer,
+    pub outline_buffer: StorageBuffer,
+    /// Bound in place of the sticker and cut buffers while there's no puzzle
+    empty_buffer: Buffer,
+    /// Sticker colours, never empty so it can always be bound
+    pub palette_buffer: Buffer,
+    /// Palette last uploaded, to skip unchanged uploads
+    palette: Vec<[f32; 4]>,
     pub renderer: Arc<RwLock<Renderer>>,
 }
 impl GfxData {
@@ -42,8 +63,19 @@ impl GfxData {
             .wgpu_render_state
             .as_ref()
             .expect("We're not using wgpu, so we're screwed");
-        let device = render_state.device.clone();
+        Self::from_device(
+            render_state.device.clone(),
+            render_state.queue.clone(),
+            render_state.renderer.clone(),
+        )
+    }
 
+    /// Set up on a device outside of eframe, such as a headless one.
+    pub fn from_device(
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        renderer: Arc<RwLock<Renderer>>,
+    ) -> Self {
         // Create and register the texture
         let texture = create_texture(
             &device,
@@ -52,17 +84,16 @@ impl GfxData {
                 height: 100,
                 depth_or_array_layers: 1,
             },
+            output_format(false),
         );
-        let renderer = render_state.renderer.clone();
         let texture_id = renderer.write().register_native_texture(
             &device,
             &texture.create_view(&TextureViewDescriptor::default()),
             eframe::wgpu::FilterMode::Nearest,
         );
 
-        let queue = render_state.queue.clone();
-
         let pipeline = create_pipeline(&device, texture.format());
+        let downsample_pipeline = create_downsample_pipeline(&device, texture.format());
 
         // Create buffers
         let vertex_buffer = device.create_buffer_init(&eframe::wgpu::util::BufferInitDescriptor {
@@ -81,24 +112,47 @@ impl GfxData {
             usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
             mapped_at_creation: false,
         });
+        let downsample_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Fewer pixels please"),
+            size: 16,
+            usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+            mapped_at_creation: false,
+        });
 
-        let coset_buffer = None;
+        let coset_buffer = StorageBuffer::new("It's big");
+        let distance_buffer = StorageBuffer::new("How far");
         let sticker_buffer = None;
-        let cut_buffer = None;
-        let outline_buffer = None;
+        let cut_buffer = StorageBuffer::new("It's small");
+        let outline_buffer = StorageBuffer::new("It's small");
+        let palette_buffer = create_palette_buffer(&device, &[]);
+        let empty_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Nothing to see here"),
+            size: 16,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
 
         GfxData {
             device,
             queue,
             texture,
             texture_id,
+            supersampling: 1,
+            render_texture: None,
+            downsample_pipeline,
+            downsample_buffer,
             pipeline,
             vertex_buffer,
             param_buffer,
             coset_buffer,
+            distance_buffer,
             sticker_buffer,
+            back_sticker_buffer: None,
             cut_buffer,
             outline_buffer,
+            empty_buffer,
+            palette_buffer,
+            palette: vec![],
             renderer,
         }
     }
@@ -108,38 +162,36 @@ impl GfxData {
         camera_transform: cga2d::Rotoflector,
         puzzle: &ConformalPuzzle,
     ) {
-        // Generate puzzle buffer (TODO: only when changed)
+        self.regenerate_group_buffers(&puzzle.quotient_group);
+        self.regenerate_cut_buffer(camera_transform, puzzle);
+        self.regenerate_sticker_buffer(puzzle);
+    }
 
+    /// Upload the tables the shader needs to colour regions by element, without a puzzle.
+    pub fn regenerate_group_buffers(&mut self, quotient_group: &QuotientGroup) {
         // LUT to multiply group elements and find C0*E' from E
-        let coset_buffer: Vec<u32> = (0..puzzle.puzzle.elem_group.point_count())
+        let coset_buffer: Vec<u32> = (quotient_group.element_group.points())
             .flat_map(|x| {
-                let mut v = vec![
-                    if let Some(p) = puzzle.quotient_group.inverse_map[x as usize] {
-                        p.0 as u32
-                    } else {
-                        u32::MAX
-                    },
-                ];
-                v.extend((0..puzzle.puzzle.elem_group.generator_count()).map(|y| {
-                    if let Some(p) = puzzle.puzzle.elem_group.mul_gen(&Point(x), &Generator(y)) {
-                        p.0 as u32
-                    } else {
-                        u32::MAX
-                    }
-                }));
-                v
+                let coset = quotient_group.element_to_coset(x);
+                let neighbors = quotient_group.element_group.neighbors(x).map(|(_, p)| p);
+                std::iter::once(coset)
+                    .chain(neighbors)
+                    .map(|p| p.map_or(u32::MAX, |p| p.0))
             })
             .collect();
-        self.coset_buffer = Some(self.device.create_buffer_init(
-            &eframe::wgpu::util::BufferInitDescriptor {
-                label: Some("It's big"),
-                contents: bytemuck::cast_slice(&coset_buffer),
-                usage: BufferUsages::STORAGE,
-            },
-        ));
-
-        self.regenerate_cut_buffer(camera_transform, puzzle);
-        self.regenerate_sticker_buffer(puzzle);
+        self.coset_buffer.update(
+            &self.device,
+            &self.queue,
+            bytemuck::cast_slice(&coset_buffer),
+        );
+        let distance_buffer: Vec<u32> = (quotient_group.element_group.distances().into_iter())
+            .map(|d| d.unwrap_or(u32::MAX))
+            .collect();
+        self.distance_buffer.update(
+            &self.device,
+            &self.queue,
+            bytemuck::cast_slice(&distance_buffer),
+        );
     }
 
     pub fn regenerate_cut_buffer(
@@ -148,13 +200,8 @@ impl GfxData {
         puzzle: &ConformalPuzzle,
     ) {
         let cut_buffer = get_cut_buffer(camera_transform, puzzle);
-        self.cut_buffer = Some(self.device.create_buffer_init(
-            &eframe::wgpu::util::BufferInitDescriptor {
-                label: Some("It's small"),
-                contents: bytemuck::cast_slice(&cut_buffer),
-                usage: BufferUsages::STORAGE,
-            },
-        ));
+        self.cut_buffer
+            .update(&self.device, &self.queue, bytemuck::cast_slice(&cut_buffer));
     }
 
     pub fn regenerate_outline_buffer(
@@ -163,25 +210,86 @@ impl GfxData {
         outlines: &Vec<cga2d::Blade3>,
     ) {
         let outline_buffer = get_outline_buffer(camera_transform, &outlines);
-        self.outline_buffer = Some(self.device.create_buffer_init(
-            &eframe::wgpu::util::BufferInitDescriptor {
-                label: Some("It's small"),
-                contents: bytemuck::cast_slice(&outline_buffer),
-                usage: BufferUsages::STORAGE,
-            },
-        ))
+        self.outline_buffer.update(
+            &self.device,
+            &self.queue,
+            bytemuck::cast_slice(&outline_buffer),
+        );
     }
 
     pub fn regenerate_sticker_buffer(&mut self, puzzle: &ConformalPuzzle) {
         // LUT to get sticker colours from circle inclusion in the fundamental region
-        let sticker_buffer: Vec<u32> = get_sticker_buffer(puzzle);
-        self.sticker_buffer = Some(self.device.create_buffer_init(
-            &eframe::wgpu::util::BufferInitDescriptor {
-                label: Some("It's big"),
-                contents: bytemuck::cast_slice(&sticker_buffer),
-                usage: BufferUsages::STORAGE,
-            },
-        ));
+        let sticker_buffer: Vec<u32> =
+            get_sticker_buffer(&puzzle.puzzle, &puzzle.cut_map, puzzle.cut_circles.len());
+        self.upload_sticker_buffer(&sticker_buffer);
+    }
+
+    fn upload_sticker_buffer(&mut self, sticker_buffer: &[u32]) {
+        let contents: &[u8] = bytemuck::cast_slice(sticker_buffer);
+
+        // Fill the back buffer completely before swapping it to the front, so the bound
+        // buffer is always valid
+        let back_buffer = match self.back_sticker_buffer.take() {
+            Some(buffer) if buffer.size() == contents.len() as u64 => {
+                self.queue.write_buffer(&buffer, 0, contents);
+                buffer
+            }
+            _ => self
+                .device
+                .create_buffer_init(&eframe::wgpu::util::BufferInitDescriptor {
+                    label: Some("It's big"),
+                    contents,
+                    usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+                }),
+        };
+        self.back_sticker_buffer = self.sticker_buffer.replace(back_buffer);
+    }
+
+    /// Upload the sticker palette if it changed, reusing the buffer when the length matches.
+    pub fn set_palette(&mut self, palette: &[[f32; 4]]) {
+        if self.palette == palette {
+            return;
+        }
+        if !palette.is_empty() && self.palette.len() == palette.len() {
+            self.queue
+                .write_buffer(&self.palette_buffer, 0, bytemuck::cast_slice(palette));
+        } else {
+            self.palette_buffer = create_palette_buffer(&self.device, palette);
+        }
+        self.palette = palette.to_vec();
+    }
+
+    /// Switch between sRGB and linear output, rebuilding the texture and pipeline if needed.
+    pub fn set_linear_output(&mut self, linear: bool) {
+        let format = output_format(linear);
+        if self.texture.format() == format {
+            return;
+        }
+        self.texture = create_texture(&self.device, self.texture.size(), format);
+        self.render_texture = None;
+        self.pipeline = create_pipeline(&self.device, format);
+        self.downsample_pipeline = create_downsample_pipeline(&self.device, format);
+        self.renderer.write().update_egui_texture_from_wgpu_texture(
+            &self.device,
+            &self.texture.create_view(&TextureViewDescriptor::default()),
+            eframe::wgpu::FilterMode::Nearest,
+            self.texture_id,
+        );
+    }
+
+    /// Render at `factor` times the output size along each axis and average down, to smooth
+    /// the edges of curves.
+    pub fn set_supersampling(&mut self, factor: u32) {
+        self.supersampling = factor.max(1);
+    }
+
+    /// Size of the texture actually rendered into for an output of the given size.
+    pub fn render_size(&self, width: u32, height: u32) -> Extent3d {
+        Extent3d {
+            width: width * self.supersampling,
+            height: height * self.supersampling,
+            depth_or_array_layers: 1,
+        }
     }
 
     pub fn frame(&mut self, params: Params, width: u32, height: u32) {
@@ -192,7 +300,7 @@ impl GfxData {
             depth_or_array_layers: 1,
         };
         if self.texture.size() != new_size {
-            self.texture = create_texture(&self.device, new_size);
+            self.texture = create_texture(&self.device, new_size, self.texture.format());
             self.renderer.write().update_egui_texture_from_wgpu_texture(
                 &self.device,
                 &self.texture.create_view(&TextureViewDescriptor::default()),
@@ -201,6 +309,26 @@ impl GfxData {
             );
         }
 
+        let render_size = self.render_size(width, height);
+        if self.supersampling > 1
+            && self.render_texture.as_ref().map(|t| t.size()) != Some(render_size)
+        {
+            self.render_texture = Some(create_texture(
+                &self.device,
+                render_size,
+                self.texture.format(),
+            ));
+        }
+        let target = match self.supersampling {
+            1 => &self.texture,
+            _ => self.render_texture.as_ref().expect("Made just above"),
+        };
+
+        // Leave the canvas blank if the shader didn't compile
+        let Ok(pipeline) = &self.pipeline else {
+            return;
+        };
+
         // Write params to the buffer
         self.queue
             .write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&params));
@@ -213,10 +341,10 @@ impl GfxData {
 
         // RENDER PASS HOURS
         {
-            let binding = self.texture.create_view(&TextureViewDescriptor::default());
+            let binding = target.create_view(&TextureViewDescriptor::default());
             let bind_group = self.device.create_bind_group(&BindGroupDescriptor {
                 label: Some("That's nice"),
-                layout: &self.pipeline.get_bind_group_layout(0),
+                layout: &pipeline.get_bind_group_layout(0),
                 entries: &[
                     BindGroupEntry {
                         binding: 0,
@@ -229,7 +357,7 @@ impl GfxData {
                     BindGroupEntry {
                         binding: 1,
                         resource: eframe::wgpu::BindingResource::Buffer(BufferBinding {
-                            buffer: self.coset_buffer.as_ref().expect("How did we get here?"),
+                            buffer: self.coset_buffer.buffer().expect("How did we get here?"),
                             offset: 0,
                             size: None,
                         }),
@@ -237,7 +365,7 @@ impl GfxData {
                     BindGroupEntry {
                         binding: 2,
                         resource: eframe::wgpu::BindingResource::Buffer(BufferBinding {
-                            buffer: self.sticker_buffer.as_ref().expect("How did we get here?"),
+                            buffer: self.sticker_buffer.as_ref().unwrap_or(&self.empty_buffer),
                             offset: 0,
                             size: None,
                         }),
@@ -245,7 +373,7 @@ impl GfxData {
                     BindGroupEntry {
                         binding: 3,
                         resource: eframe::wgpu::BindingResource::Buffer(BufferBinding {
-                            buffer: self.cut_buffer.as_ref().expect("How did we get here?"),
+                            buffer: self.cut_buffer.buffer().unwrap_or(&self.empty_buffer),
                             offset: 0,
                             size: None,
                         }),
@@ -253,7 +381,23 @@ impl GfxData {
                     BindGroupEntry {
                         binding: 4,
                         resource: eframe::wgpu::BindingResource::Buffer(BufferBinding {
-                            buffer: self.outline_buffer.as_ref().expect("How did we get here?"),
+                            buffer: self.outline_buffer.buffer().expect("How did we get here?"),
+                            offset: 0,
+                            size: None,
+                        }),
+                    },
+                    BindGroupEntry {
+                        binding: 5,
+                        resource: eframe::wgpu::BindingResource::Buffer(BufferBinding {
+                            buffer: &self.palette_buffer,
+                            offset: 0,
+                            size: None,
+                        }),
+                    },
+                    BindGroupEntry {
+                        binding: 6,
+                        resource: eframe::wgpu::BindingResource::Buffer(BufferBinding {
+                            buffer: self.distance_buffer.buffer().expect("How did we get here?"),
                             offset: 0,
                             size: None,
                         }),
@@ -271,7 +415,7 @@ impl GfxData {
                 timestamp_writes: None,
                 occlusion_query_set: None,
             });
-            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_pipeline(pipeline);
             render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
             render_pass.set_bind_group(0, &bind_group, &[]);
 
@@ -280,8 +424,160 @@ impl GfxData {
             drop(render_pass);
         }
 
+        if self.supersampling > 1 {
+            self.queue.write_buffer(
+                &self.downsample_buffer,
+                0,
+                bytemuck::cast_slice(&[self.supersampling, 0, 0, 0]),
+            );
+            let source = target.create_view(&TextureViewDescriptor::default());
+            let bind_group = self.device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Shrinking"),
+                layout: &self.downsample_pipeline.get_bind_group_layout(0),
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: eframe::wgpu::BindingResource::TextureView(&source),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: eframe::wgpu::BindingResource::Buffer(BufferBinding {
+                            buffer: &self.downsample_buffer,
+                            offset: 0,
+                            size: None,
+                        }),
+                    },
+                ],
+            });
+            let output = self.texture.create_view(&TextureViewDescriptor::default());
+            let mut render_pass = ce.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Downsample"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &output,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&self.downsample_pipeline);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
         self.queue.submit([ce.finish()]);
     }
+
+    pub fn shader_error(&self) -> Option<&str> {
+        self.pipeline.as_ref().err().map(|e| e.as_str())
+    }
+
+    /// Write the last rendered frame to a PNG file. An sRGB texture already holds encoded
+    /// bytes, so they're written as is; a linear one is written unchanged for compositing.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn export_png(&self, path: &std::path::Path) -> Result<(), ()> {
+        let Extent3d { width, height, .. } = self.texture.size();
+        crate::export::write_png(path, width, height, &self.read_texture())
+    }
+
+    /// Copy the last rendered frame back from the GPU as tightly packed RGBA bytes.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_texture(&self) -> Vec<u8> {
+        let Extent3d { width, height, .. } = self.texture.size();
+        let row_bytes = 4 * width;
+        // Rows in the copy must be padded to the alignment wgpu requires
+        let align = eframe::wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row_bytes = row_bytes.div_ceil(align) * align;
+
+        let buffer = self.device.create_buffer(&BufferDescriptor {
+            label: Some("Coming back"),
+            size: (padded_row_bytes * height) as _,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut ce = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Read it back"),
+            });
+        ce.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            eframe::wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: eframe::wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(height),
+                },
+            },
+            self.texture.size(),
+        );
+        self.queue.submit([ce.finish()]);
+
+        let slice = buffer.slice(..);
+        slice.map_async(eframe::wgpu::MapMode::Read, |_| ());
+        self.device.poll(eframe::wgpu::Maintain::Wait);
+
+        let data = slice.get_mapped_range();
+        let pixels = data
+            .chunks(padded_row_bytes as usize)
+            .flat_map(|row| &row[..row_bytes as usize])
+            .copied()
+            .collect();
+        drop(data);
+        buffer.unmap();
+        pixels
+    }
+}
+
+/// Storage buffer that's only written when its contents change, reusing the allocation
+/// while the size stays the same. The cut and outline buffers are refreshed every frame,
+/// but only change while the camera moves.
+pub(crate) struct StorageBuffer {
+    label: &'static str,
+    buffer: Option<Buffer>,
+    /// Contents last uploaded
+    contents: Vec<u8>,
+}
+impl StorageBuffer {
+    fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            buffer: None,
+            contents: vec![],
+        }
+    }
+
+    fn update(&mut self, device: &Device, queue: &Queue, contents: &[u8]) {
+        // An empty buffer can't be bound, so pad it to one entry the shader won't read
+        let contents = match contents.is_empty() {
+            true => &[0; 16],
+            false => contents,
+        };
+        match &self.buffer {
+            Some(_) if self.contents == contents => return,
+            Some(buffer) if buffer.size() == contents.len() as u64 => {
+                queue.write_buffer(buffer, 0, contents);
+            }
+            _ => {
+                self.buffer = Some(device.create_buffer_init(
+                    &eframe::wgpu::util::BufferInitDescriptor {
+                        label: Some(self.label),
+                        contents,
+                        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+                    },
+                ));
+            }
+        }
+        self.contents.clear();
+        self.contents.extend_from_slice(contents);
+    }
+
+    pub fn buffer(&self) -> Option<&Buffer> {
+        self.buffer.as_ref()
+    }
 }
 
 #[derive(Debug, Default, Copy, Clone, bytemuck::NoUninit, bytemuck::Zeroable)]
@@ -312,10 +608,20 @@ pub(crate) struct Params {
     pub outline_count: u32,
     pub col_scale: f32,
     pub depth: u32,
-    /// fundamental = 1, col_tiles = 2, inverse_col = 4
+    /// fundamental = 1, col_tiles = 2, inverse_col = 4, high_contrast = 8, parity = 16
     pub flags: u32,
     pub mirror_count: u32,
-    padding: [f32; 1],
+    pub parity_cols: [[f32; 4]; 2],
+    pub inset: f32,
+    /// Number of sticker colours, 0 to use the colour ramp
+    pub palette_len: u32,
+    /// Tile to tint, or `u32::MAX` for none
+    pub highlight: u32,
+    pub piece_type_count: u32,
+    pub outline_col: [f32; 4],
+    /// Center and radius of the disk redrawn in the Klein model, with a zero radius to
+    /// leave the view conformal
+    pub klein_disk: [f32; 4],
 }
 impl Params {
     pub fn new(
@@ -323,7 +629,7 @@ impl Params {
         edges: Vec<bool>,
         point: cga2d::Blade1,
         scale: [f32; 2],
-        cut_circle_count: usize,
+        puzzle: Option<&ConformalPuzzle>,
         outline_count: usize,
         depth: u32,
         view_settings: &ViewSettings,
@@ -348,6 +654,15 @@ impl Params {
         if view_settings.inverse_col {
             flags |= 1 << 2
         }
+        if view_settings.high_contrast {
+            flags |= 1 << 3
+        }
+        if view_settings.parity_coloring {
+            flags |= 1 << 4
+        }
+        if view_settings.col_distance {
+            flags |= 1 << 5
+        }
 
         Self {
             mirrors: out_mirrors,
@@ -359,15 +674,35 @@ impl Params {
                 point.y as f32,
             ],
             scale,
-            cut_circle_count: cut_circle_count as u32,
+            cut_circle_count: puzzle.map_or(0, |p| p.cut_circles.len() as u32),
             outline_count: outline_count as u32,
             col_scale: view_settings.col_scale,
             depth,
             flags,
             mirror_count,
-            padding: [0.; 1],
+            parity_cols: view_settings.parity_cols,
+            inset: view_settings.inset,
+            palette_len: view_settings.palette.len() as u32,
+            highlight: u32::MAX,
+            piece_type_count: puzzle.map_or(0, |p| p.puzzle.piece_types.len() as u32),
+            outline_col: view_settings.outline_col,
+            klein_disk: [0.; 4],
         }
     }
+
+    /// Draw the hyperbolic plane bounded by `disk` in the Klein model.
+    pub fn with_klein_disk(mut self, disk: Option<Disk>) -> Self {
+        if let Some(Disk { cx, cy, r }) = disk {
+            self.klein_disk = [cx as f32, cy as f32, r as f32, 0.];
+        }
+        self
+    }
+
+    /// Tint every copy of a tile.
+    pub fn with_highlight(mut self, tile: Option<Point>) -> Self {
+        self.highlight = tile.map_or(u32::MAX, |t| t.0);
+        self
+    }
 }
 
 fn rep_mirror(mirror: cga2d::Blade3) -> [f32; 4] {
@@ -375,41 +710,41 @@ fn rep_mirror(mirror: cga2d::Blade3) -> [f32; 4] {
     [m.m as f32, m.p as f32, m.x as f32, m.y as f32]
 }
 
-fn get_sticker_buffer(puzzle: &ConformalPuzzle) -> Vec<u32> {
-    (0..puzzle.puzzle.elem_group.point_count())
-        .flat_map(|x| {
-            (0..(1 << puzzle.cut_circles.len())).map(move |i| {
-                if i < puzzle.cut_map.len() {
-                    if let Some(i) = puzzle.cut_map[i] {
-                        if i < puzzle.puzzle.piece_types.len() {
-                            let sig = &puzzle.puzzle.piece_types[i];
-                            // Does this have to use the attitude in element form?
-                            let word = &puzzle.puzzle.elem_group.word_table[x as usize];
-                            if let Ok(sig) = puzzle.puzzle.transform_signature(sig, &word.inverse())
-                            {
-                                if let Some(piece) = puzzle.puzzle.find_piece(sig) {
-                                    // dbg!(piece);
-                                    if let Some(attitude) =
-                                        puzzle.puzzle.elem_group.mul_word(&piece.attitude, &word)
-                                    {
-                                        if let Some(res) = puzzle.puzzle.elem_group.mul_word(
-                                            &Point::INIT,
-                                            &puzzle.puzzle.elem_group.word_table
-                                                [attitude.0 as usize],
-                                        ) {
-                                            return res.0 as u32;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        return u32::MAX;
-                    }
-                }
-                x as u32
-            })
-        })
-        .collect()
+/// Sticker of a region whose piece can't be followed within the enumerated groups, which the
+/// shader draws in gray.
+const UNKNOWN_STICKER: u32 = u32::MAX;
+
+/// The piece type in each of the `2^cut_count` cut regions, `u32::MAX` where there's none,
+/// followed by the sticker of each piece type in each element. Only the first part grows with
+/// the number of cuts.
+fn get_sticker_buffer(puzzle: &Puzzle, cut_map: &[Option<usize>], cut_count: usize) -> Vec<u32> {
+    let piece_types = &puzzle.piece_types;
+    let regions = (0..1 << cut_count).map(|i| match cut_map.get(i) {
+        Some(&Some(t)) if t < piece_types.len() => t as u32,
+        _ => u32::MAX,
+    });
+    let stickers = (0..puzzle.elem_group.point_count()).flat_map(|x| {
+        let word = &puzzle.elem_group.word_table[x as usize];
+        (piece_types.iter())
+            .map(move |sig| get_piece_sticker(puzzle, sig, word).unwrap_or(UNKNOWN_STICKER))
+    });
+    regions.chain(stickers).collect()
+}
+
+/// Colour of the piece of a given type seen in the region reached by `word`, or `None` if
+/// the group was truncated before reaching it.
+fn get_piece_sticker(puzzle: &Puzzle, sig: &GripSignature, word: &Word) -> Option<u32> {
+    // Does this have to use the attitude in element form?
+    let sig = puzzle
+        .transform_signature(sig, &word.inverse(&puzzle.elem_group))
+        .ok()?;
+    let piece = puzzle.find_piece(sig)?;
+    let attitude = puzzle.elem_group.mul_word(&piece.attitude, word)?;
+    let res = puzzle.elem_group.mul_word(
+        &Point::INIT,
+        &puzzle.elem_group.word_table[attitude.0 as usize],
+    )?;
+    Some(res.0)
 }
 
 fn get_cut_buffer(camera_transform: cga2d::Rotoflector, puzzle: &ConformalPuzzle) -> Vec<[f32; 4]> {
@@ -430,22 +765,67 @@ fn get_outline_buffer(
         .collect()
 }
 
-fn create_texture(device: &Device, size: Extent3d) -> Texture {
+/// Storage buffer holding the palette, padded to one entry if it's empty.
+fn create_palette_buffer(device: &Device, palette: &[[f32; 4]]) -> Buffer {
+    let placeholder = [[0.; 4]];
+    let contents = match palette.is_empty() {
+        true => &placeholder[..],
+        false => palette,
+    };
+    device.create_buffer_init(&eframe::wgpu::util::BufferInitDescriptor {
+        label: Some("Pretty colours"),
+        contents: bytemuck::cast_slice(contents),
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+    })
+}
+
+/// Format of the render target. The shader works in linear colour, so an sRGB target
+/// encodes it for display, while a linear target stores it unchanged for compositing.
+pub(crate) fn output_format(linear: bool) -> TextureFormat {
+    match linear {
+        true => TextureFormat::Rgba8Unorm,
+        false => TextureFormat::Rgba8UnormSrgb,
+    }
+}
+
+fn create_texture(device: &Device, size: Extent3d, format: TextureFormat) -> Texture {
     device.create_texture(&TextureDescriptor {
         label: Some("Placeholder"),
         size,
         mip_level_count: 1,
         sample_count: 1,
         dimension: eframe::wgpu::TextureDimension::D2,
-        format: eframe::wgpu::TextureFormat::Rgba8UnormSrgb,
+        format,
         usage: TextureUsages::TEXTURE_BINDING
             | TextureUsages::RENDER_ATTACHMENT
-            | TextureUsages::COPY_DST,
-        view_formats: &[eframe::wgpu::TextureFormat::Rgba8UnormSrgb],
+            | TextureUsages::COPY_DST
+            | TextureUsages::COPY_SRC,
+        view_formats: &[format],
     })
 }
 
-fn create_pipeline(device: &Device, texture_format: TextureFormat) -> RenderPipeline {
+/// Build the render pipeline, returning the error instead of panicking if the shader fails
+/// to compile. On the web the error can't be waited for, so it goes to the console instead.
+fn create_pipeline(
+    device: &Device,
+    texture_format: TextureFormat,
+) -> Result<RenderPipeline, String> {
+    catch_validation(device, || create_pipeline_unchecked(device, texture_format))
+}
+
+/// Run `build`, returning the first validation error it raises instead of panicking.
+fn catch_validation<T>(device: &Device, build: impl FnOnce() -> T) -> Result<T, String> {
+    #[cfg(not(target_arch = "wasm32"))]
+    device.push_error_scope(eframe::wgpu::ErrorFilter::Validation);
+    let result = build();
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+        return Err(error.to_string());
+    }
+    Ok(result)
+}
+
+fn create_pipeline_unchecked(device: &Device, texture_format: TextureFormat) -> RenderPipeline {
     let module = device.create_shader_module(include_wgsl!("shader.wgsl"));
 
     device.create_render_pipeline(&RenderPipelineDescriptor {
@@ -506,6 +886,26 @@ fn create_pipeline(device: &Device, texture_format: TextureFormat) -> RenderPipe
                             },
                             count: None,
                         },
+                        BindGroupLayoutEntry {
+                            binding: 5,
+                            visibility: ShaderStages::FRAGMENT,
+                            ty: eframe::wgpu::BindingType::Buffer {
+                                ty: eframe::wgpu::BufferBindingType::Storage { read_only: true },
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        BindGroupLayoutEntry {
+                            binding: 6,
+                            visibility: ShaderStages::FRAGMENT,
+                            ty: eframe::wgpu::BindingType::Buffer {
+                                ty: eframe::wgpu::BufferBindingType::Storage { read_only: true },
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
                     ],
                 }),
             ],
@@ -540,3 +940,447 @@ fn create_pipeline(device: &Device, texture_format: TextureFormat) -> RenderPipe
         multiview: None,
     })
 }
+
+/// Pipeline averaging a supersampled render down into the output texture. The shader is
+/// fixed, so unlike the main one it can't fail to compile.
+fn create_downsample_pipeline(device: &Device, texture_format: TextureFormat) -> RenderPipeline {
+    let module = device.create_shader_module(include_wgsl!("downsample.wgsl"));
+
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Downsample"),
+        layout: None,
+        vertex: VertexState {
+            module: &module,
+            entry_point: "vertex",
+            compilation_options: PipelineCompilationOptions::default(),
+            buffers: &[VertexBufferLayout {
+                array_stride: 32,
+                step_mode: eframe::wgpu::VertexStepMode::Vertex,
+                attributes: &vertex_attr_array![0 => Float32x2],
+            }],
+        },
+        primitive: PrimitiveState {
+            topology: eframe::wgpu::PrimitiveTopology::TriangleList,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        fragment: Some(FragmentState {
+            module: &module,
+            entry_point: "fragment",
+            compilation_options: PipelineCompilationOptions::default(),
+            targets: &[Some(ColorTargetState {
+                format: texture_format,
+                blend: None,
+                write_mask: ColorWrites::all(),
+            })],
+        }),
+        multiview: None,
+    })
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use discrete::{
+        group::{Generator, Group},
+        presets,
+        todd_coxeter::{get_coset_table, get_element_table, get_element_table_bounded, Strategy},
+    };
+
+    use super::*;
+
+    fn word(generators: &[u8]) -> Word {
+        Word(generators.iter().map(|&g| Generator(g)).collect())
+    }
+
+    /// Default {6,5,3} presentation
+    fn rels() -> Vec<Vec<u8>> {
+        let mut rels = vec![];
+        for (i, val) in [6, 5, 3].into_iter().enumerate() {
+            for x in 0..i as u8 {
+                rels.push(vec![x, i as u8 + 1, x, i as u8 + 1]);
+            }
+            rels.push([i as u8, i as u8 + 1].repeat(val));
+        }
+        rels.extend([
+            [0, 1].repeat(3),
+            [1, 3, 2].repeat(5),
+            vec![1, 0, 1, 2, 1, 0, 2, 1, 0, 2, 1, 0, 2, 1, 2],
+        ]);
+        rels
+    }
+
+    /// {6,5,3} with cells and the pieces between two neighbouring cells.
+    fn puzzle_from(elem_group: Group) -> Puzzle {
+        let subgroup = vec![vec![0], vec![1], vec![2]];
+        let grip_group = get_coset_table(4, &rels(), &subgroup, 1000, Strategy::Hlt, None).unwrap();
+        let neighbor = grip_group.mul_gen(&Point::INIT, &Generator(3)).unwrap();
+        let piece_types = vec![
+            GripSignature::new(vec![Point::INIT]),
+            GripSignature::new(vec![Point::INIT, neighbor]),
+        ];
+        Puzzle::new(elem_group, grip_group, piece_types).unwrap()
+    }
+
+    fn puzzle() -> Puzzle {
+        puzzle_from(get_element_table(4, &rels(), 1000, Strategy::Hlt, None).unwrap())
+    }
+
+    /// Renderer on the first adapter found, or `None` on a machine without one.
+    fn headless() -> Option<GfxData> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&Default::default(), None)).ok()?;
+        let renderer = Renderer::new(&device, output_format(false), None, 1);
+        Some(GfxData::from_device(
+            Arc::new(device),
+            Arc::new(queue),
+            Arc::new(RwLock::new(renderer)),
+        ))
+    }
+
+    /// Fill every buffer the shader binds, for the puzzle with no cuts.
+    fn fill_buffers(gfx: &mut GfxData) {
+        let puzzle = puzzle();
+        let quotient = (puzzle.elem_group).quotient_by(&[word(&[0]), word(&[1]), word(&[2])]);
+        gfx.regenerate_group_buffers(&quotient);
+        gfx.upload_sticker_buffer(&get_sticker_buffer(&puzzle, &[Some(0)], 0));
+        (gfx.cut_buffer).update(&gfx.device, &gfx.queue, &[]);
+        (gfx.outline_buffer).update(&gfx.device, &gfx.queue, &[]);
+    }
+
+    /// Params for a view with no puzzle. Without mirrors it doesn't need any geometry.
+    fn params(mirrors: Vec<cga2d::Blade3>, edges: Vec<bool>, view: &ViewSettings) -> Params {
+        let point = cga2d::Blade1 {
+            m: 0.,
+            p: 0.,
+            x: 0.,
+            y: 0.,
+        };
+        Params::new(mirrors, edges, point, [1., 1.], None, 0, 8, view)
+    }
+
+    /// Sticker the shader draws in a cut region of an element, following the region's piece
+    /// type into the second half of the buffer.
+    fn shown(buffer: &[u32], cut_count: usize, type_count: usize, region: usize, x: usize) -> u32 {
+        let t = buffer[region] as usize;
+        buffer[(1 << cut_count) + x * type_count + t]
+    }
+
+    #[test]
+    fn solved_stickers_match_regions() {
+        let puzzle = puzzle();
+
+        // Each region of a solved puzzle shows the sticker coloured for that region
+        for x in puzzle.elem_group.points() {
+            let word = &puzzle.elem_group.word_table[x.0 as usize];
+            for sig in &puzzle.piece_types {
+                assert_eq!(get_piece_sticker(&puzzle, sig, word), Some(x.0));
+            }
+        }
+    }
+
+    #[test]
+    fn regions_of_one_piece_type_move_together() {
+        let mut puzzle = puzzle();
+        let type_count = puzzle.piece_types.len();
+        let elements = puzzle.elem_group.point_count() as usize;
+        // Two cuts, with the regions inside just one of them making up a single piece type
+        let cut_map = [None, Some(1), Some(1), Some(0)];
+        let solved = get_sticker_buffer(&puzzle, &cut_map, 2);
+        assert_eq!(solved[..4], [u32::MAX, 1, 1, 0]);
+
+        puzzle.apply_move(&Point::INIT, &word(&[0, 1])).unwrap();
+        let twisted = get_sticker_buffer(&puzzle, &cut_map, 2);
+        assert_eq!(twisted[..4], solved[..4]);
+        // Both regions always show the same colour, which the twist changes somewhere
+        for x in 0..elements {
+            let sticker = shown(&twisted, 2, type_count, 1, x);
+            assert_eq!(shown(&twisted, 2, type_count, 2, x), sticker);
+        }
+        assert!((0..elements)
+            .any(|x| shown(&twisted, 2, type_count, 1, x) != shown(&solved, 2, type_count, 1, x)));
+    }
+
+    #[test]
+    fn cut_grows_only_region_table() {
+        let puzzle = puzzle();
+        let cut_map = [None, Some(1), Some(1), Some(0)];
+        let two_cuts = get_sticker_buffer(&puzzle, &cut_map, 2);
+        // A third cut that only splits off more of the second piece type
+        let cut_map = [None, Some(1), Some(1), Some(0), None, Some(1), None, None];
+        let three_cuts = get_sticker_buffer(&puzzle, &cut_map, 3);
+        assert_eq!(three_cuts.len(), two_cuts.len() + 4);
+        assert_eq!(three_cuts[8..], two_cuts[4..]);
+    }
+
+    #[test]
+    fn regenerating_gives_same_stickers() {
+        let cut_map = [None, Some(1), Some(1), Some(0)];
+        let mut first = puzzle();
+        let mut second = puzzle();
+        assert_eq!(
+            get_sticker_buffer(&first, &cut_map, 2),
+            get_sticker_buffer(&second, &cut_map, 2)
+        );
+        for puzzle in [&mut first, &mut second] {
+            puzzle.apply_move(&Point::INIT, &word(&[0, 1])).unwrap();
+        }
+        assert_eq!(
+            get_sticker_buffer(&first, &cut_map, 2),
+            get_sticker_buffer(&second, &cut_map, 2)
+        );
+    }
+
+    #[test]
+    fn truncated_group_gives_unknown_stickers() {
+        let elem_group = get_element_table_bounded(4, &rels(), 3, 1000);
+        let mut puzzle = puzzle_from(elem_group);
+        let cut_map = [None, Some(1), Some(1), Some(0)];
+        let buffer = get_sticker_buffer(&puzzle, &cut_map, 2);
+        assert!(!buffer[4..].contains(&UNKNOWN_STICKER));
+
+        // Turned as far as the table reaches, the pieces can't be followed much further
+        let deepest = (puzzle.elem_group.points())
+            .max_by_key(|x| puzzle.elem_group.word_table[x.0 as usize].0.len())
+            .unwrap();
+        for piece in &mut puzzle.pieces {
+            piece.attitude = deepest;
+        }
+        let buffer = get_sticker_buffer(&puzzle, &cut_map, 2);
+        assert!(buffer[4..].contains(&UNKNOWN_STICKER));
+        assert!(buffer[4..].iter().any(|&s| s != UNKNOWN_STICKER));
+    }
+
+    #[test]
+    fn sticker_buffer_always_bound() {
+        let Some(mut gfx) = headless() else {
+            return;
+        };
+        assert!(gfx.sticker_buffer.is_none());
+        // Same size, then a different one, then the same size as the back buffer again
+        for len in [8, 8, 12, 8] {
+            gfx.upload_sticker_buffer(&vec![1; len]);
+            let front = gfx.sticker_buffer.as_ref().unwrap();
+            assert_eq!(front.size(), 4 * len as u64);
+        }
+        assert!(gfx.back_sticker_buffer.is_some());
+    }
+
+    #[test]
+    fn palette_resized_and_uploaded_on_change() {
+        let Some(mut gfx) = headless() else {
+            return;
+        };
+        // An empty palette still has one placeholder entry to bind
+        assert_eq!(gfx.palette_buffer.size(), 16);
+
+        let red = [1., 0., 0., 1.];
+        let blue = [0., 0., 1., 1.];
+        gfx.set_palette(&[red, blue, red]);
+        assert_eq!(gfx.palette_buffer.size(), 3 * 16);
+        let id = gfx.palette_buffer.global_id();
+
+        // Same length reuses the buffer, while a new length replaces it
+        gfx.set_palette(&[blue, red, blue]);
+        assert_eq!(gfx.palette_buffer.global_id(), id);
+        assert_eq!(gfx.palette, [blue, red, blue]);
+        gfx.set_palette(&[blue, red]);
+        assert_eq!(gfx.palette_buffer.size(), 2 * 16);
+        assert_ne!(gfx.palette_buffer.global_id(), id);
+        assert_eq!(gfx.palette, [blue, red]);
+    }
+
+    #[test]
+    fn storage_buffer_reallocated_only_on_resize() {
+        let Some(gfx) = headless() else {
+            return;
+        };
+        let mut buffer = StorageBuffer::new("Test");
+        assert!(buffer.buffer().is_none());
+        buffer.update(&gfx.device, &gfx.queue, &[1; 16]);
+        let id = buffer.buffer().unwrap().global_id();
+
+        // Unchanged frames and same-size changes keep the allocation
+        buffer.update(&gfx.device, &gfx.queue, &[1; 16]);
+        assert_eq!(buffer.buffer().unwrap().global_id(), id);
+        buffer.update(&gfx.device, &gfx.queue, &[2; 16]);
+        assert_eq!(buffer.buffer().unwrap().global_id(), id);
+        assert_eq!(buffer.contents, [2; 16]);
+
+        buffer.update(&gfx.device, &gfx.queue, &[2; 32]);
+        assert_ne!(buffer.buffer().unwrap().global_id(), id);
+        assert_eq!(buffer.buffer().unwrap().size(), 32);
+    }
+
+    #[test]
+    fn linear_output_rebuilds_texture_and_pipeline() {
+        let Some(mut gfx) = headless() else {
+            return;
+        };
+        assert_eq!(gfx.texture.format(), TextureFormat::Rgba8UnormSrgb);
+        let size = gfx.texture.size();
+        for linear in [true, false] {
+            gfx.set_linear_output(linear);
+            assert_eq!(gfx.texture.format(), output_format(linear));
+            assert_eq!(gfx.texture.size(), size);
+            assert!(gfx.shader_error().is_none());
+        }
+    }
+
+    #[test]
+    fn supersampling_scales_render_target() {
+        let Some(mut gfx) = headless() else {
+            return;
+        };
+        fill_buffers(&mut gfx);
+        gfx.set_supersampling(2);
+        gfx.frame(Params::default(), 10, 6);
+        assert_eq!((gfx.texture.width(), gfx.texture.height()), (10, 6));
+        let render_texture = gfx.render_texture.as_ref().unwrap();
+        assert_eq!((render_texture.width(), render_texture.height()), (20, 12));
+        // Averaged down, the output is still the requested size
+        assert_eq!(gfx.read_texture().len(), 10 * 6 * 4);
+    }
+
+    #[test]
+    fn inset_reaches_params() {
+        let mut view_settings = ViewSettings::new();
+        assert_eq!(params(vec![], vec![], &view_settings).inset, 0.);
+        view_settings.inset = 0.05;
+        assert_eq!(params(vec![], vec![], &view_settings).inset, 0.05);
+    }
+
+    #[test]
+    fn custom_edge_flips_params_edge() {
+        let preset = (presets::builtin().into_iter())
+            .find(|p| p.name == "Cube")
+            .unwrap();
+        let tiling = preset.tiling_settings.generate().unwrap();
+        let mut view_settings = ViewSettings::new();
+        let edges =
+            |view: &ViewSettings| params(tiling.mirrors.clone(), view.edges(&tiling), view).edges;
+        let before = edges(&view_settings);
+
+        let mut custom = tiling.edges.clone();
+        custom[0] = !custom[0];
+        view_settings.custom_edges = Some(custom);
+        let after = edges(&view_settings);
+        assert_ne!(after[0], before[0]);
+        assert_eq!(after[1..], before[1..]);
+    }
+
+    #[test]
+    fn broken_shader_gives_error() {
+        let Some(gfx) = headless() else {
+            return;
+        };
+        let broken = catch_validation(&gfx.device, || {
+            gfx.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("Broken"),
+                    source: wgpu::ShaderSource::Wgsl("fn fragment( -> {".into()),
+                })
+        });
+        assert!(broken.is_err());
+        assert!(create_pipeline(&gfx.device, output_format(false)).is_ok());
+    }
+
+    #[test]
+    fn renders_frame_to_png() {
+        let Some(mut gfx) = headless() else {
+            return;
+        };
+        fill_buffers(&mut gfx);
+        gfx.frame(params(vec![], vec![], &ViewSettings::new()), 16, 8);
+        let pixels = gfx.read_texture();
+        assert_eq!(pixels.len(), 16 * 8 * 4);
+        assert!(pixels.iter().any(|&b| b != 0));
+
+        let path = std::env::temp_dir().join("discrete-test-frame.png");
+        gfx.export_png(&path).unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn animation_writes_every_frame() {
+        let Some(mut gfx) = headless() else {
+            return;
+        };
+        fill_buffers(&mut gfx);
+        let mut export = crate::export::AnimationExport::new();
+        export.duration = 0.1;
+        export.fps = 30;
+        (export.width, export.height) = (24, 16);
+        let directory = std::env::temp_dir().join("discrete-test-animation");
+        export.directory = directory.to_string_lossy().into_owned();
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+
+        // Both endpoints and the two frames between them
+        assert_eq!(export.frame_count(), 4);
+        let params = params(vec![], vec![], &ViewSettings::new());
+        for i in 0..export.frame_count() {
+            gfx.frame(params, export.width, export.height);
+            gfx.export_png(&export.frame_path(i)).unwrap();
+        }
+        let frames = std::fs::read_dir(&directory).unwrap().count();
+        assert_eq!(frames, export.frame_count() as usize);
+        for i in 0..export.frame_count() {
+            let file = std::fs::File::open(export.frame_path(i)).unwrap();
+            let reader = png::Decoder::new(file).read_info().unwrap();
+            assert_eq!((reader.info().width, reader.info().height), (24, 16));
+        }
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn high_contrast_ignores_palette() {
+        let Some(mut gfx) = headless() else {
+            return;
+        };
+        fill_buffers(&mut gfx);
+        let render = |gfx: &mut GfxData, view: &ViewSettings| {
+            gfx.set_palette(&view.palette);
+            gfx.frame(params(vec![], vec![], view), 4, 4);
+            gfx.read_texture()
+        };
+        let mut view = ViewSettings::new();
+        view.fundamental = false;
+        view.col_tiles = true;
+        view.palette = vec![[1., 0., 0., 1.]];
+        let red = render(&mut gfx, &view);
+        view.palette = vec![[0., 0., 1., 1.]];
+        assert_ne!(render(&mut gfx, &view), red);
+
+        view.high_contrast = true;
+        assert_eq!(params(vec![], vec![], &view).flags & 8, 8);
+        let gray = render(&mut gfx, &view);
+        view.palette = vec![[1., 0., 0., 1.]];
+        assert_eq!(render(&mut gfx, &view), gray);
+        assert_ne!(gray, red);
+    }
+
+    #[test]
+    fn parity_renders_without_puzzle() {
+        let Some(mut gfx) = headless() else {
+            return;
+        };
+        // Only what's uploaded while previewing a tiling
+        let elem_group = puzzle().elem_group;
+        gfx.regenerate_group_buffers(&elem_group.quotient_by(&[word(&[0]), word(&[1])]));
+        gfx.outline_buffer.update(&gfx.device, &gfx.queue, &[]);
+
+        let mut view = ViewSettings::new();
+        view.fundamental = false;
+        view.parity_coloring = true;
+        view.parity_cols[0] = [1., 0., 0., 1.];
+        let params = params(vec![], vec![], &view);
+        assert_eq!(params.flags & 16, 16);
+        gfx.frame(params, 4, 4);
+        let pixels = gfx.read_texture();
+        assert!(pixels.chunks(4).all(|p| p == [255, 0, 0, 255]));
+    }
+}
@@ -1,22 +1,38 @@
-use std::{collections::HashMap, fmt, ops::Mul};
+use std::{fmt, ops::Mul};
+
+use serde::{Deserialize, Serialize};
+
+use crate::tiling::QuotientGroup;
 
 /// Point acted on by the group.
-#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct Point(pub u16);
+#[derive(
+    Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct Point(pub u32);
 impl Point {
     pub const INIT: Self = Point(0);
 }
 
 /// Group generator.
-#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct Generator(pub u8);
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Generator(pub u8);
 
 /// Word in generators, applied left to right.
-#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct Word(pub Vec<Generator>);
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Word(pub Vec<Generator>);
 impl Word {
-    pub fn inverse(&self) -> Word {
-        Word(self.0.iter().copied().rev().collect()) //TODO: Invert generators
+    /// Word undoing this one, using the generator inverses of the group it acts in.
+    pub fn inverse(&self, group: &Group) -> Word {
+        Word(
+            (self.0.iter().rev())
+                .map(|&g| group.inverse_generator(g))
+                .collect(),
+        )
+    }
+
+    /// Whether this is an odd number of reflections, reversing orientation.
+    pub fn is_odd(&self) -> bool {
+        self.0.len() % 2 == 1
     }
 }
 impl Mul for Word {
@@ -61,30 +77,51 @@ impl fmt::Display for Word {
 }
 
 /// Permutation group multiplication table. Possibly incomplete.
-#[derive(Debug, Clone)]
-pub(crate) struct Group {
-    point_count: u16,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "GroupData")]
+pub struct Group {
+    point_count: u32,
     generator_count: u8,
-    mul_table: HashMap<(Point, Generator), Option<Point>>,
+    /// Result of each point and generator, indexed by `point * generator_count + generator`
+    mul_table: Vec<Option<Point>>,
     pub word_table: Vec<Word>,
+    /// Inverse of each generator
+    generator_inverses: Vec<Generator>,
 }
 impl Group {
     pub fn new(
-        point_count: u16,
+        point_count: u32,
         generator_count: u8,
-        mul_table: HashMap<(Point, Generator), Option<Point>>,
+        mul_table: Vec<Option<Point>>,
         word_table: Vec<Word>,
     ) -> Self {
+        assert_eq!(
+            mul_table.len(),
+            point_count as usize * generator_count as usize
+        );
         Self {
             point_count,
             mul_table,
             generator_count,
             word_table,
+            // Coset enumeration treats every generator as a mirror, so each is its own inverse
+            generator_inverses: (0..generator_count).map(Generator).collect(),
         }
     }
 
+    /// Use a presentation where generators aren't all involutions.
+    pub fn with_generator_inverses(mut self, generator_inverses: Vec<Generator>) -> Self {
+        assert_eq!(generator_inverses.len(), self.generator_count as usize);
+        self.generator_inverses = generator_inverses;
+        self
+    }
+
+    pub fn inverse_generator(&self, gen: Generator) -> Generator {
+        self.generator_inverses[gen.0 as usize]
+    }
+
     pub fn mul_gen(&self, point: &Point, gen: &Generator) -> Option<Point> {
-        self.mul_table[&(*point, *gen)]
+        self.mul_table[point.0 as usize * self.generator_count as usize + gen.0 as usize]
     }
 
     pub fn mul_word(&self, point: &Point, word: &Word) -> Option<Point> {
@@ -95,14 +132,185 @@ impl Group {
         Some(result)
     }
 
-    pub fn point_count(&self) -> u16 {
+    /// Like `mul_word`, but each generator may be applied inverted, as `(generator, inverse)`.
+    pub fn mul_word_signed(&self, point: &Point, word: &[(Generator, bool)]) -> Option<Point> {
+        let mut result = *point;
+        for &(gen, inverse) in word {
+            let gen = if inverse {
+                self.inverse_generator(gen)
+            } else {
+                gen
+            };
+            result = self.mul_gen(&result, &gen)?;
+        }
+        Some(result)
+    }
+
+    /// Smallest number of times `word` must be applied to return every point to itself,
+    /// checked from the identity. Assumes this is an element group.
+    pub fn word_order(&self, word: &Word) -> Option<u32> {
+        let mut point = Point::INIT;
+        for n in 1..=self.point_count {
+            point = self.mul_word(&point, word)?;
+            if point == Point::INIT {
+                return Some(n);
+            }
+        }
+        None
+    }
+
+    /// Result of each generator on a point, in generator order, `None` where it wasn't
+    /// enumerated.
+    pub fn neighbors(&self, point: Point) -> impl Iterator<Item = (Generator, Option<Point>)> + '_ {
+        (0..self.generator_count).map(move |g| (Generator(g), self.mul_gen(&point, &Generator(g))))
+    }
+
+    /// Every defined `(point, generator, result)` entry of the multiplication table.
+    pub fn edges(&self) -> impl Iterator<Item = (Point, Generator, Point)> + '_ {
+        (0..self.point_count).flat_map(move |p| {
+            (0..self.generator_count).filter_map(move |g| {
+                let (p, g) = (Point(p), Generator(g));
+                self.mul_gen(&p, &g).map(|q| (p, g, q))
+            })
+        })
+    }
+
+    /// Edges of the Cayley graph, each once from its lower numbered end. Every generator is
+    /// an involution, so `p * g = q` exactly when `q * g = p`.
+    pub fn cayley_edges(&self) -> impl Iterator<Item = (Point, Generator, Point)> + '_ {
+        self.edges().filter(|(p, _, q)| p.0 <= q.0)
+    }
+
+    /// Quotient of an element group by the subgroup generated by `subgroup_words`, acting on
+    /// the right cosets. Elements whose cosets can't be followed within the enumerated
+    /// table are left in cosets of their own.
+    pub fn quotient_by(&self, subgroup_words: &[Word]) -> QuotientGroup {
+        let (classes, class_count) = self.coset_classes(subgroup_words);
+
+        // Every element in a coset acts the same, so take whichever ones are enumerated
+        let gen_count = self.generator_count as usize;
+        let mut mul_table = vec![None; class_count as usize * gen_count];
+        for (p, g, q) in self.edges() {
+            mul_table[classes[p.0 as usize] as usize * gen_count + g.0 as usize] =
+                Some(Point(classes[q.0 as usize]));
+        }
+
+        let tile_group = Group::new(
+            class_count,
+            self.generator_count,
+            mul_table,
+            representatives(&classes, class_count)
+                .iter()
+                .map(|p| self.word_table[p.0 as usize].clone())
+                .collect(),
+        )
+        .with_generator_inverses(self.generator_inverses.clone());
+        QuotientGroup::new(self.clone(), tile_group)
+    }
+
+    /// The first enumerated element of each right coset of the subgroup generated by
+    /// `subgroup_words`. Assumes this is an element group.
+    pub fn coset_representatives(&self, subgroup_words: &[Word]) -> Vec<Point> {
+        let (classes, class_count) = self.coset_classes(subgroup_words);
+        representatives(&classes, class_count)
+    }
+
+    /// Coset of each element, numbered in order of first appearance, and the number of
+    /// cosets. Elements `e` and `w * e` share a coset for each subgroup word `w`.
+    fn coset_classes(&self, subgroup_words: &[Word]) -> (Vec<u32>, u32) {
+        fn find(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+
+        let n = self.point_count as usize;
+        let mut parent: Vec<usize> = (0..n).collect();
+        for p in 0..n {
+            for w in subgroup_words {
+                if let Some(q) = self.mul_word(&Point::INIT, &(w * &self.word_table[p])) {
+                    let (a, b) = (find(&mut parent, p), find(&mut parent, q.0 as usize));
+                    parent[a.max(b)] = a.min(b);
+                }
+            }
+        }
+
+        let mut classes = vec![0; n];
+        let mut class_count = 0;
+        for p in 0..n {
+            let root = find(&mut parent, p);
+            classes[p] = if root == p {
+                class_count += 1;
+                class_count - 1
+            } else {
+                classes[root]
+            };
+        }
+        (classes, class_count)
+    }
+
+    /// Compact binary encoding of the table.
+    pub fn to_bincode(&self) -> Result<Vec<u8>, ()> {
+        bincode::serialize(self).map_err(|_| ())
+    }
+
+    pub fn from_bincode(bytes: &[u8]) -> Result<Self, ()> {
+        bincode::deserialize(bytes).map_err(|_| ())
+    }
+
+    pub fn point_count(&self) -> u32 {
         self.point_count
     }
 
+    /// Number of elements, or `None` if the table isn't complete, as the group may then be
+    /// larger than the points enumerated.
+    pub fn order(&self) -> Option<u32> {
+        self.is_complete().then_some(self.point_count)
+    }
+
+    /// Fewest generators taking the initial point to each point, found by breadth-first
+    /// search. `None` for points it can't reach.
+    pub fn distances(&self) -> Vec<Option<u32>> {
+        self.distances_from(Point::INIT)
+    }
+
+    /// Fewest generators taking `start` to each point. `None` for points it can't reach.
+    pub fn distances_from(&self, start: Point) -> Vec<Option<u32>> {
+        let mut distances = vec![None; self.point_count as usize];
+        distances[start.0 as usize] = Some(0);
+        let mut queue = std::collections::VecDeque::from([start]);
+        while let Some(p) = queue.pop_front() {
+            let d = distances[p.0 as usize].expect("Queued points have a distance");
+            for g in 0..self.generator_count {
+                if let Some(q) = self.mul_gen(&p, &Generator(g)) {
+                    if distances[q.0 as usize].is_none() {
+                        distances[q.0 as usize] = Some(d + 1);
+                        queue.push_back(q);
+                    }
+                }
+            }
+        }
+        distances
+    }
+
+    /// Every point, in the order they were enumerated.
+    pub fn points(&self) -> impl Iterator<Item = Point> {
+        (0..self.point_count).map(Point)
+    }
+
+    /// Whether every point has a result for every generator, so the group wasn't cut off
+    /// by the enumeration limit.
+    pub fn is_complete(&self) -> bool {
+        self.mul_table.iter().all(|p| p.is_some())
+    }
+
     pub fn generator_count(&self) -> u8 {
         self.generator_count
     }
 }
+
 impl fmt::Display for Group {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Points: {}", self.point_count)?;
@@ -111,7 +319,7 @@ impl fmt::Display for Group {
         for g in 0..self.generator_count {
             write!(f, "G{g:_>2x} ")?;
         }
-        writeln!(f)?;
+        writeln!(f, "Word")?;
         for p in 0..self.point_count {
             write!(f, "P{p:_>2x} ")?;
             for g in 0..self.generator_count {
@@ -121,8 +329,271 @@ impl fmt::Display for Group {
                     write!(f, "P?? ")?;
                 }
             }
+            // The word reaching each point, as found by the enumerator
+            if let Some(word) = self.word_table.get(p as usize) {
+                write!(f, "{word}")?;
+            }
             writeln!(f)?;
         }
         Ok(())
     }
 }
+
+/// The first element in each class, given the class of every element as numbered by
+/// `Group::coset_classes`.
+fn representatives(classes: &[u32], class_count: u32) -> Vec<Point> {
+    let mut representatives = vec![None; class_count as usize];
+    for (p, &c) in classes.iter().enumerate() {
+        representatives[c as usize].get_or_insert(Point(p as u32));
+    }
+    representatives.into_iter().flatten().collect()
+}
+
+/// Fields of a `Group` as stored, checked before use since a stale or corrupt file
+/// could otherwise index outside the table.
+#[derive(Deserialize)]
+struct GroupData {
+    point_count: u32,
+    generator_count: u8,
+    mul_table: Vec<Option<Point>>,
+    word_table: Vec<Word>,
+    generator_inverses: Vec<Generator>,
+}
+impl TryFrom<GroupData> for Group {
+    type Error = InvalidGroup;
+
+    fn try_from(data: GroupData) -> Result<Self, Self::Error> {
+        let GroupData {
+            point_count,
+            generator_count,
+            mul_table,
+            word_table,
+            generator_inverses,
+        } = data;
+        if mul_table.len() != point_count as usize * generator_count as usize
+            || word_table.len() != point_count as usize
+            || generator_inverses.len() != generator_count as usize
+        {
+            return Err(InvalidGroup::Size);
+        }
+        let valid_point = |p: &Point| p.0 < point_count;
+        let valid_gen = |g: &Generator| g.0 < generator_count;
+        if !mul_table.iter().flatten().all(valid_point)
+            || !word_table.iter().flat_map(|w| &w.0).all(valid_gen)
+            || !generator_inverses.iter().all(valid_gen)
+        {
+            return Err(InvalidGroup::OutOfRange);
+        }
+        Ok(Self {
+            point_count,
+            generator_count,
+            mul_table,
+            word_table,
+            generator_inverses,
+        })
+    }
+}
+
+/// Why a stored group was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidGroup {
+    /// A table doesn't have one entry per point or generator
+    Size,
+    /// An entry refers to a point or generator the group doesn't have
+    OutOfRange,
+}
+impl fmt::Display for InvalidGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Size => write!(f, "group tables have the wrong size"),
+            Self::OutOfRange => write!(f, "group table refers to a missing point or generator"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todd_coxeter::{
+        get_coset_table, get_element_table, get_element_table_bounded, Strategy,
+    };
+
+    /// Element group of the dodecahedron, {5,3}.
+    fn dodecahedral() -> Group {
+        let rels = vec![[0, 1].repeat(5), [0, 2].repeat(2), [1, 2].repeat(3)];
+        get_element_table(3, &rels, 1000, Strategy::Hlt, None).unwrap()
+    }
+
+    /// Cyclic group of order `n`, generated by a step forward and its inverse, a step back.
+    fn cyclic(n: u32) -> Group {
+        let mul_table = (0..n)
+            .flat_map(|p| [Some(Point((p + 1) % n)), Some(Point((p + n - 1) % n))])
+            .collect();
+        let word_table = (0..n)
+            .map(|p| Word(vec![Generator(0); p as usize]))
+            .collect();
+        Group::new(n, 2, mul_table, word_table)
+            .with_generator_inverses(vec![Generator(1), Generator(0)])
+    }
+
+    #[test]
+    fn order_only_when_complete() {
+        assert_eq!(dodecahedral().order(), Some(120));
+        let rels = vec![[0, 1].repeat(5), [0, 2].repeat(2), [1, 2].repeat(3)];
+        let truncated = get_element_table_bounded(3, &rels, 3, 1000);
+        assert!(truncated.point_count() < 120);
+        assert_eq!(truncated.order(), None);
+    }
+
+    #[test]
+    fn bincode_round_trip() {
+        let group = dodecahedral();
+        let loaded = Group::from_bincode(&group.to_bincode().unwrap()).unwrap();
+        assert_eq!(loaded.point_count(), 120);
+        assert_eq!(loaded.word_table, group.word_table);
+        for p in group.points() {
+            for g in (0..3).map(Generator) {
+                assert_eq!(loaded.mul_gen(&p, &g), group.mul_gen(&p, &g));
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_group_rejected() {
+        // Serialized in the order the group stores its fields, with two points and one
+        // generator
+        let load = |mul_table: Vec<Option<Point>>, word_table: Vec<Word>, inverses: Vec<u8>| {
+            let inverses: Vec<Generator> = inverses.into_iter().map(Generator).collect();
+            let bytes = bincode::serialize(&(2u32, 1u8, mul_table, word_table, inverses));
+            Group::from_bincode(&bytes.unwrap())
+        };
+        let words = || vec![Word(vec![]), Word(vec![Generator(0)])];
+        let swap = || vec![Some(Point(1)), Some(Point(0))];
+
+        assert!(load(swap(), words(), vec![0]).is_ok());
+        assert!(load(vec![Some(Point(1))], words(), vec![0]).is_err());
+        assert!(load(vec![Some(Point(1)), Some(Point(2))], words(), vec![0]).is_err());
+        assert!(load(swap(), words(), vec![]).is_err());
+        assert!(load(swap(), words(), vec![1]).is_err());
+        assert!(load(swap(), vec![Word(vec![])], vec![0]).is_err());
+        assert!(load(
+            swap(),
+            vec![Word(vec![]), Word(vec![Generator(1)])],
+            vec![0]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn cayley_edges_once_each() {
+        let group = dodecahedral();
+        let edges: Vec<_> = group.cayley_edges().collect();
+        assert_eq!(edges.len(), 120 * 3 / 2);
+
+        // Together with their reverses, they're exactly the multiplication table
+        let mut both_ways: Vec<_> = (edges.iter())
+            .flat_map(|&(p, g, q)| [(p, g, q), (q, g, p)])
+            .collect();
+        both_ways.sort_by_key(|&(p, g, _)| (p, g.0));
+        assert_eq!(both_ways, group.edges().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn cayley_edges_among_visible_cells() {
+        // Part of {7,3}, with the cells near the identity standing in for those in view
+        let rels = vec![[0, 1].repeat(7), [0, 2].repeat(2), [1, 2].repeat(3)];
+        let group = get_element_table_bounded(3, &rels, 6, 10000);
+        let distances = group.distances();
+        let visible = |p: Point| distances[p.0 as usize].is_some_and(|d| d <= 3);
+
+        let mut drawn: Vec<_> = (group.cayley_edges())
+            .filter(|&(p, _, q)| visible(p) || visible(q))
+            .collect();
+        let mut defined: Vec<_> = (group.points().filter(|&p| visible(p)))
+            .flat_map(|p| group.neighbors(p).map(move |(g, q)| (p, g, q)))
+            .filter_map(|(p, g, q)| Some((p.min(q?), g, p.max(q?))))
+            .collect();
+        defined.sort_by_key(|&(p, g, q)| (p, g.0, q));
+        defined.dedup();
+        drawn.sort_by_key(|&(p, g, q)| (p, g.0, q));
+        assert_eq!(drawn, defined);
+        assert!(drawn.len() < group.cayley_edges().count());
+    }
+
+    #[test]
+    fn quotients_by_trivial_and_generator_subgroups() {
+        let group = dodecahedral();
+
+        // Nothing to identify, so the tiles are the elements
+        let trivial = group.quotient_by(&[]);
+        assert_eq!(trivial.tile_group.point_count(), 120);
+        assert_eq!(trivial.tile_group.word_table, group.word_table);
+        assert!(trivial.tile_group.edges().eq(group.edges()));
+
+        // Quotienting by a vertex's stabilizer gives the 12 faces, acting as the coset
+        // table built by enumeration does
+        let faces = group.quotient_by(&[Word(vec![Generator(0)]), Word(vec![Generator(1)])]);
+        let rels = vec![[0, 1].repeat(5), [0, 2].repeat(2), [1, 2].repeat(3)];
+        let cosets =
+            get_coset_table(3, &rels, &vec![vec![0], vec![1]], 1000, Strategy::Hlt, None).unwrap();
+        assert_eq!(faces.tile_group.point_count(), 12);
+        assert_eq!(cosets.point_count(), 12);
+        let coset_of = |p: Point| {
+            let word = &faces.tile_group.word_table[p.0 as usize];
+            cosets.mul_word(&Point::INIT, word).unwrap()
+        };
+        let mut seen: Vec<_> = faces.tile_group.points().map(coset_of).collect();
+        seen.sort_by_key(|p| p.0);
+        seen.dedup();
+        assert_eq!(seen.len(), 12);
+        for (p, g, q) in faces.tile_group.edges() {
+            assert_eq!(cosets.mul_gen(&coset_of(p), &g), Some(coset_of(q)));
+        }
+    }
+
+    #[test]
+    fn inverse_word_undoes_non_involutions() {
+        let group = cyclic(5);
+        let word = Word(vec![Generator(0), Generator(0), Generator(1), Generator(0)]);
+        let inverse = word.inverse(&group);
+        assert_eq!(
+            inverse,
+            Word(vec![Generator(1), Generator(0), Generator(1), Generator(1)])
+        );
+        // Merely reversing the word would step forward twice more
+        assert_eq!(group.mul_word(&Point::INIT, &word), Some(Point(2)));
+        for p in group.points() {
+            assert_eq!(group.mul_word(&p, &(&word * &inverse)), Some(p));
+        }
+    }
+
+    #[test]
+    fn signed_word_then_its_inverse_returns() {
+        let group = cyclic(7);
+        let word = [
+            (Generator(0), false),
+            (Generator(0), false),
+            (Generator(1), true),
+            (Generator(1), false),
+        ];
+        // Inverting the step back makes it a third step forward
+        assert_eq!(group.mul_word_signed(&Point::INIT, &word), Some(Point(2)));
+        let inverse: Vec<_> = word.iter().rev().map(|&(g, inv)| (g, !inv)).collect();
+        for p in group.points() {
+            let there = group.mul_word_signed(&p, &word).unwrap();
+            assert_eq!(group.mul_word_signed(&there, &inverse), Some(p));
+        }
+    }
+
+    #[test]
+    fn distances_match_word_lengths() {
+        let group = dodecahedral();
+        let distances = group.distances();
+        for p in group.points() {
+            let word = &group.word_table[p.0 as usize];
+            assert_eq!(distances[p.0 as usize], Some(word.0.len() as u32));
+        }
+        // The longest element of {5,3} takes 15 reflections
+        assert_eq!(distances.iter().flatten().max(), Some(&15));
+    }
+}
@@ -0,0 +1,19 @@
+//! Tilings, their groups and the puzzles built on them, usable without the app.
+
+// Failures that need no explanation are reported as `Err(())` throughout
+#![allow(clippy::result_unit_err)]
+
+pub mod camera;
+pub mod config;
+pub mod conformal_puzzle;
+pub mod geom;
+pub mod group;
+pub mod notation;
+pub mod presets;
+pub mod puzzle;
+pub mod tiling;
+pub mod todd_coxeter;
+
+pub use conformal_puzzle::{ConformalPuzzle, PuzzleDefinition};
+pub use puzzle::Puzzle;
+pub use tiling::{QuotientGroup, Tiling};
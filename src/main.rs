@@ -1,26 +1,29 @@
 use std::sync::Arc;
 
 use cga2d::prelude::*;
-use config::Settings;
-use conformal_puzzle::{ConformalPuzzle, PuzzleDefinition, PuzzleEditor};
+use discrete::{
+    camera,
+    config::{self, parse_word, GeneratorStyle, Settings, TilingSettings},
+    conformal_puzzle::{ConformalPuzzle, MoveError, PuzzleDefinition, PuzzleEditor},
+    geom,
+    group::Point,
+    notation::{self, NotationError},
+    presets,
+    puzzle::GripSignature,
+    tiling::{QuotientGroup, QuotientTables, Tiling},
+    todd_coxeter::TCError,
+};
 use eframe::{
     egui::{self, pos2, vec2, CollapsingHeader, Color32, Frame, Pos2, RichText, Shadow, Slider},
     epaint::PathShape,
 };
 use gfx::GfxData;
-use group::{Generator, Point, Word};
-mod conformal_puzzle;
-use puzzle::GripSignature;
-use regex::Regex;
-use tiling::{QuotientGroup, Tiling};
-
-mod config;
-mod geom;
+
+mod enumeration;
+mod export;
+#[cfg(not(target_arch = "wasm32"))]
+mod gallery;
 mod gfx;
-mod group;
-mod puzzle;
-mod tiling;
-mod todd_coxeter;
 
 /// Native main function
 #[cfg(not(target_arch = "wasm32"))]
@@ -82,10 +85,23 @@ fn main() {
     });
 }
 
+const UNDO_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+const REDO_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Y);
+
 enum Status {
     Invalid,
     Generated,
     Failed,
+    Unenumerated,
+    Jumbling,
+    Blocked,
+    NoSolution,
+    Exported,
+    Saved,
+    LimitReached,
+    Enumerating,
     Idle,
 }
 impl Status {
@@ -94,6 +110,20 @@ impl Status {
             Status::Invalid => "Invalid".to_string(),
             Status::Generated => "Generated".to_string(),
             Status::Failed => "Failed".to_string(),
+            Status::Unenumerated => {
+                "Move requires un-enumerated elements; raise tile limit".to_string()
+            }
+            Status::Jumbling => {
+                "Move doesn't map its grip to itself; jumbling isn't supported".to_string()
+            }
+            Status::Blocked => "Move is blocked by pieces outside the grip".to_string(),
+            Status::NoSolution => "No solution found within the move limit".to_string(),
+            Status::Exported => "Exported".to_string(),
+            Status::Saved => "Saved".to_string(),
+            Status::LimitReached => {
+                "Tile limit too low; previewing up to the iteration depth".to_string()
+            }
+            Status::Enumerating => "Enumerating...".to_string(),
             Status::Idle => "".to_string(),
         }
     }
@@ -112,10 +142,52 @@ impl Needs {
     }
 }
 
+/// Move count and timing for solving practice. Times are seconds from `egui::InputState::time`.
+#[derive(Debug, Default)]
+struct SolveStats {
+    move_count: u32,
+    start: Option<f64>,
+    end: Option<f64>,
+}
+impl SolveStats {
+    /// Count a move, starting the timer on the first one and stopping it once solved. Only
+    /// for moves made forwards, not undos or redos.
+    fn record_move(&mut self, time: f64, solved: bool) {
+        // Moving again after a solve starts a fresh attempt
+        if self.end.is_some() {
+            *self = Self::default();
+        }
+        self.move_count += 1;
+        self.start.get_or_insert(time);
+        if solved {
+            self.end = Some(time);
+        }
+    }
+
+    fn elapsed(&self, now: f64) -> f64 {
+        match self.start {
+            Some(start) => self.end.unwrap_or(now) - start,
+            None => 0.,
+        }
+    }
+
+    fn moves_per_second(&self, now: f64) -> f64 {
+        let elapsed = self.elapsed(now);
+        match elapsed > 0. {
+            true => self.move_count as f64 / elapsed,
+            false => 0.,
+        }
+    }
+}
+
 struct App {
     settings: Settings,
     tiling: Arc<Tiling>,
     quotient_group: Arc<QuotientGroup>,
+    /// Enumerator state behind `quotient_group`, extended when the tile limit is raised
+    quotient_tables: Option<QuotientTables>,
+    /// Groups being enumerated in the background, replacing the tiling once done
+    enumeration: Option<enumeration::Enumeration>,
     gfx_data: GfxData,
     camera_transform: cga2d::Rotoflector,
     // puzzle_info: PuzzleInfo,
@@ -124,42 +196,478 @@ struct App {
     puzzle: Option<ConformalPuzzle>,
     needs: Needs,
     status: Status,
+    animation_export: export::AnimationExport,
+    image_export: export::ImageExport,
+    svg_export: export::SvgExport,
+    /// Pan motion over one frame at `camera::COAST_FPS`, reapplied with friction once a
+    /// drag is released
+    pan_momentum: Option<cga2d::Rotoflector>,
+    /// Glide towards a recentred view, stepped each frame
+    camera_animation: Option<camera::CameraAnimation>,
+    solve_stats: SolveStats,
+    /// Base twist applied by clicking on a grip
+    active_twist: usize,
+    /// Name given to the next camera bookmark
+    bookmark_name: String,
+    /// Grip whose available twists are shown, and where on screen it was picked
+    selected_grip: Option<(Point, Pos2)>,
+    /// Tile under the pointer last frame, tinted when highlighting is on
+    hovered_tile: Option<Point>,
+    /// Points clicked to measure between, in tiling coordinates, while measuring
+    measure: Option<Vec<cga2d::Blade1>>,
+    /// Whether the groups shown are only the neighbourhood within the iteration depth,
+    /// because the whole groups didn't fit in the tile limit
+    previewing: bool,
+    /// Elements at each word length, for the tiling with this presentation key
+    growth: Option<(String, Vec<usize>)>,
+    /// Longest solution the solver searches for
+    solve_depth: usize,
+    /// Moves typed in to apply, and why they last failed to parse
+    notation: String,
+    notation_error: Option<NotationError>,
+    /// Tiling loaded at startup in place of the built-in default
+    home: Option<TilingSettings>,
+    /// Built-in presets followed by any loaded from file
+    presets: Vec<presets::Preset>,
+    /// Piece types of the preset being loaded, applied once its tiling is regenerated
+    pending_piece_types: Option<Vec<GripSignature>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    gallery: gallery::Gallery,
+    /// Path puzzle definitions and solves are saved to and loaded from, or the JSON itself
+    /// on the web
+    definition_file: String,
 }
 impl App {
     fn new(cc: &eframe::CreationContext<'_>) -> Self {
         let mut gfx_data = GfxData::new(cc);
 
-        let settings = Settings::new();
-        let camera_transform = cga2d::Rotoflector::ident();
+        let mut settings = Settings::load(cc.storage);
+        let camera_transform = cc
+            .storage
+            .filter(|_| settings.remember_camera)
+            .and_then(|s| eframe::get_value(s, config::CAMERA_KEY))
+            .map_or(cga2d::Rotoflector::ident(), |c| camera::from_components(&c));
 
-        let tiling = Arc::new(settings.tiling_settings.generate().unwrap());
-        let quotient_group = Arc::new(tiling.get_quotient_group(settings.tile_limit).unwrap());
+        let home = load_home(cc.storage);
+        let startup = Startup::new(home.as_ref(), &settings);
+        settings.tiling_settings = startup.tiling_settings;
+        let (tiling, quotient_group) = (startup.tiling, startup.quotient_group);
         // let puzzle_info = tiling.get_puzzle_info(settings.tile_limit).unwrap();
         // let puzzle = Puzzle::new_anticore_only(
         //     puzzle_info.element_group.clone(),
         //     puzzle_info.coset_group.clone(),
         // );
         let puzzle_def = PuzzleDefinition::new(tiling.clone(), quotient_group.clone());
-        let puzzle = puzzle_def.generate_puzzle().unwrap();
+        let puzzle = match startup.previewing {
+            true => None,
+            false => puzzle_def.generate_puzzle().ok(),
+        };
+        match &puzzle {
+            Some(puzzle) => gfx_data.regenerate_puzzle_buffers(camera_transform, puzzle),
+            None => gfx_data.regenerate_group_buffers(&quotient_group),
+        }
         let needs = Needs::new();
-        gfx_data.regenerate_puzzle_buffers(camera_transform, &puzzle);
 
         Self {
             settings,
             tiling,
             quotient_group,
+            quotient_tables: None,
+            enumeration: None,
             gfx_data,
             camera_transform,
             // puzzle_info,
-            puzzle_editor: Some(PuzzleEditor::new(puzzle_def)),
-            puzzle: Some(puzzle),
+            puzzle_editor: puzzle.is_some().then(|| PuzzleEditor::new(puzzle_def)),
+            puzzle,
             needs,
-            status: Status::Idle,
+            status: match startup.previewing {
+                true => Status::LimitReached,
+                false => Status::Idle,
+            },
+            animation_export: export::AnimationExport::new(),
+            image_export: export::ImageExport::new(),
+            svg_export: export::SvgExport::new(),
+            pan_momentum: None,
+            camera_animation: None,
+            solve_stats: SolveStats::default(),
+            active_twist: 0,
+            bookmark_name: String::new(),
+            selected_grip: None,
+            hovered_tile: None,
+            measure: None,
+            previewing: startup.previewing,
+            growth: None,
+            solve_depth: 6,
+            notation: String::new(),
+            notation_error: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            gallery: gallery::Gallery::new(),
+            home,
+            presets: presets::builtin(),
+            pending_piece_types: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            definition_file: "puzzle.json".to_string(),
+            #[cfg(target_arch = "wasm32")]
+            definition_file: String::new(),
+        }
+    }
+
+    /// Apply the moves typed into the notation box, stopping at the first that fails.
+    fn apply_notation(&mut self, time: f64) {
+        let Some(puzzle) = &mut self.puzzle else {
+            return;
+        };
+        let moves = match puzzle.parse_moves(&self.notation) {
+            Ok(moves) => moves,
+            Err(e) => {
+                self.notation_error = Some(e);
+                return;
+            }
+        };
+        self.notation_error = None;
+        self.status = Status::Idle;
+        for (attitude, twist, inverse) in moves {
+            match puzzle.apply_move(attitude, twist, inverse) {
+                Ok(()) => self.solve_stats.record_move(time, puzzle.is_solved()),
+                Err(MoveError::Unenumerated) => self.status = Status::Unenumerated,
+                Err(MoveError::Jumbling) => self.status = Status::Jumbling,
+                Err(MoveError::Blocked) => self.status = Status::Blocked,
+                Err(MoveError::Invalid) => self.status = Status::Invalid,
+            }
+            if !matches!(self.status, Status::Idle) {
+                break;
+            }
+        }
+        self.gfx_data.regenerate_sticker_buffer(puzzle);
+    }
+
+    /// Write out saved JSON: to the chosen file, or into the text box on the web.
+    fn write_definition_file(&mut self, json: String) -> Result<(), ()> {
+        #[cfg(not(target_arch = "wasm32"))]
+        return std::fs::write(&self.definition_file, json).map_err(|_| ());
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.definition_file = json;
+            Ok(())
+        }
+    }
+
+    fn read_definition_file(&self) -> Result<String, ()> {
+        #[cfg(not(target_arch = "wasm32"))]
+        return std::fs::read_to_string(&self.definition_file).map_err(|_| ());
+        #[cfg(target_arch = "wasm32")]
+        Ok(self.definition_file.clone())
+    }
+
+    /// Disk to redraw in the Klein model as seen through `camera_transform`, if that's the
+    /// chosen projection and the tiling is hyperbolic.
+    fn klein_disk(&self, camera_transform: cga2d::Rotoflector) -> Option<geom::Disk> {
+        if self.settings.view_settings.projection != geom::Projection::Klein {
+            return None;
+        }
+        let boundary = camera_transform.sandwich(self.tiling.hyperbolic_boundary()?);
+        geom::Disk::new(boundary, &self.settings.tolerances)
+    }
+
+    /// Hyperbolic distance between the two measured points, and how many tiles apart they
+    /// are. `None` until both points are picked.
+    fn measurement(&self) -> Option<(Option<f64>, Option<u32>)> {
+        let &[a, b] = self.measure.as_deref()? else {
+            return None;
+        };
+        let tile_group = &self.quotient_group.tile_group;
+        let tile = |p| self.tiling.tile_at(tile_group, p, self.settings.depth);
+        let tiles_apart = match (tile(a), tile(b)) {
+            (Some(x), Some(y)) => tile_group.distances_from(x)[y.0 as usize],
+            _ => None,
+        };
+        Some((self.tiling.hyperbolic_distance(a, b), tiles_apart))
+    }
+
+    /// Applied moves in notation, each clickable to undo back to just after it.
+    fn move_log_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(puzzle) = &self.puzzle else {
+            return;
+        };
+        let log = puzzle.move_log();
+        let mut undo_to = None;
+        ui.collapsing("Move Log", |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} moves", log.len()));
+                if ui.button("Copy").clicked() {
+                    let notation: Vec<String> = log.iter().map(|m| m.to_string()).collect();
+                    ui.output_mut(|o| o.copied_text = notation.join(" "));
+                }
+            });
+            egui::ScrollArea::vertical()
+                .max_height(200.)
+                .show(ui, |ui| {
+                    if ui.selectable_label(log.is_empty(), "Start").clicked() {
+                        undo_to = Some(0);
+                    }
+                    for (i, m) in log.iter().enumerate() {
+                        let current = i + 1 == log.len();
+                        if ui
+                            .selectable_label(current, format!("{}. {}", i + 1, m))
+                            .clicked()
+                        {
+                            undo_to = Some(i + 1);
+                        }
+                    }
+                });
+        });
+        if let Some(len) = undo_to {
+            self.undo_to(len);
+        }
+    }
+
+    /// Undo moves until only the first `len` remain.
+    fn undo_to(&mut self, len: usize) {
+        let Some(puzzle) = &mut self.puzzle else {
+            return;
+        };
+        self.status = Status::Idle;
+        while puzzle.history_len() > len {
+            match puzzle.undo() {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(MoveError::Unenumerated) => self.status = Status::Unenumerated,
+                Err(MoveError::Jumbling) => self.status = Status::Jumbling,
+                Err(MoveError::Blocked) => self.status = Status::Blocked,
+                Err(MoveError::Invalid) => self.status = Status::Invalid,
+            }
+            if !matches!(self.status, Status::Idle) {
+                break;
+            }
+        }
+        self.gfx_data.regenerate_sticker_buffer(puzzle);
+    }
+
+    /// Apply a shortest solution, if one is found within `solve_depth` moves.
+    fn solve_bfs(&mut self, time: f64) {
+        let Some(puzzle) = &mut self.puzzle else {
+            return;
+        };
+        let Some(moves) = puzzle.solve_bfs(self.solve_depth) else {
+            self.status = Status::NoSolution;
+            return;
+        };
+        self.status = Status::Idle;
+        for (attitude, twist, inverse) in moves {
+            if puzzle.apply_move(attitude, twist, inverse).is_err() {
+                self.status = Status::Invalid;
+                break;
+            }
+            self.solve_stats.record_move(time, puzzle.is_solved());
+        }
+        self.gfx_data.regenerate_sticker_buffer(puzzle);
+    }
+
+    /// Switch to a newly enumerated tiling, or a preview of it if it didn't fit in the tile
+    /// limit.
+    fn finish_enumeration(&mut self, enumerated: enumeration::Enumerated) {
+        self.tiling = enumerated.tiling;
+        self.quotient_tables = Some(enumerated.tables);
+        match enumerated.result {
+            Ok(q) => {
+                self.previewing = false;
+                self.quotient_group = q;
+                let mut def =
+                    PuzzleDefinition::new(self.tiling.clone(), self.quotient_group.clone());
+                if let Some(piece_types) = self.pending_piece_types.take() {
+                    def.piece_types = piece_types;
+                }
+                self.puzzle_editor = Some(PuzzleEditor::new(def));
+                self.needs.puzzle_regenerate = true;
+            }
+            Err(TCError::LimitReached) => {
+                // Show what's near the fundamental region rather than nothing
+                let depth = self.settings.depth as usize;
+                let limit = self.settings.tile_limit;
+                self.quotient_group =
+                    Arc::new(self.tiling.get_quotient_group_bounded(depth, limit));
+                self.gfx_data.regenerate_group_buffers(&self.quotient_group);
+                self.puzzle = None;
+                self.puzzle_editor = None;
+                self.previewing = true;
+                self.status = Status::LimitReached
+            }
+            Err(TCError::Cancelled) => self.status = Status::Failed,
+        }
+        self.pending_piece_types = None;
+    }
+
+    /// Switch to a saved solve, replaying its moves.
+    fn load_solve(&mut self, json: &str) -> Result<(), ()> {
+        let puzzle = ConformalPuzzle::import_log(json)?;
+        let def = puzzle.definition.clone();
+        self.settings.tiling_settings = def.tiling.settings.clone();
+        self.tiling = def.tiling.clone();
+        self.quotient_group = def.quotient_group.clone();
+        self.quotient_tables = None;
+        self.enumeration = None;
+        self.puzzle_editor = Some(PuzzleEditor::new(def));
+        self.gfx_data
+            .regenerate_puzzle_buffers(self.camera_transform, &puzzle);
+        self.puzzle = Some(puzzle);
+        self.solve_stats = SolveStats::default();
+        self.status = Status::Generated;
+        Ok(())
+    }
+
+    /// Switch to a saved puzzle definition, along with the tiling it was made on.
+    fn load_definition(&mut self, json: &str) -> Result<(), ()> {
+        let def = PuzzleDefinition::from_json(json)?;
+        self.settings.tiling_settings = def.tiling.settings.clone();
+        self.tiling = def.tiling.clone();
+        self.quotient_group = def.quotient_group.clone();
+        self.quotient_tables = None;
+        self.enumeration = None;
+        self.puzzle_editor = Some(PuzzleEditor::new(def));
+        self.needs.puzzle_regenerate = true;
+        Ok(())
+    }
+
+    /// Replace the tiling settings with those of a preset, regenerating on the next frame.
+    fn load_preset(&mut self, index: usize) {
+        let Some(preset) = self.presets.get(index) else {
+            return;
+        };
+        self.settings.tiling_settings = preset.tiling_settings.clone();
+        self.pending_piece_types =
+            Some(preset.piece_types.clone()).filter(|types| !types.is_empty());
+        self.needs.tiling_regenerate = true;
+    }
+
+    /// Render the view from `camera_transform` offscreen at the given size, leaving it in
+    /// the texture ready to be read back.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn render_offscreen(
+        &mut self,
+        camera_transform: cga2d::Rotoflector,
+        outlines: &Vec<cga2d::Blade3>,
+        width: u32,
+        height: u32,
+    ) {
+        let min = width.min(height) as f32;
+        let scale = [width as f32 / min, height as f32 / min];
+        if let Some(puzzle) = &self.puzzle {
+            self.gfx_data
+                .regenerate_cut_buffer(camera_transform, puzzle);
+        }
+        self.gfx_data
+            .regenerate_outline_buffer(camera_transform, outlines);
+        self.gfx_data.frame(
+            gfx::Params::new(
+                self.tiling
+                    .mirrors
+                    .iter()
+                    .map(|&m| camera_transform.sandwich(m))
+                    .collect(),
+                self.settings.view_settings.edges(&self.tiling),
+                cga2d::point(0., 1.),
+                scale,
+                self.puzzle.as_ref(),
+                outlines.len(),
+                self.settings.depth,
+                &self.settings.view_settings,
+            )
+            .with_klein_disk(self.klein_disk(camera_transform)),
+            width,
+            height,
+        );
+    }
+
+    /// Write the visible mirrors and cut circles to an SVG file. `half_size` is the extent
+    /// of the view in view units, and `unit` the pixels per view unit.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_svg(
+        &self,
+        boundary_circle: cga2d::Blade3,
+        half_size: egui::Vec2,
+        unit: f32,
+    ) -> Result<(), ()> {
+        let export = &self.svg_export;
+        let mut svg = export::SvgWriter::new(half_size.x as f64, half_size.y as f64, unit as f64);
+        let gen_cols = generator_cols(&self.settings.view_settings.generators);
+        let col = |col: Color32| match self.settings.view_settings.high_contrast {
+            true => Color32::BLACK,
+            false => col,
+        };
+        let mut add = |mirror: cga2d::Blade3, stroke: Color32| match MirrorCurve::new(
+            self.camera_transform.sandwich(mirror),
+            boundary_circle,
+            self.settings.tolerances.line,
+            export.sample_count,
+        ) {
+            Some(MirrorCurve::Arc(points)) => {
+                svg.polyline(points.iter().map(|p| (p.x, p.y)), col(stroke), 1.)
+            }
+            Some(MirrorCurve::Circle { cx, cy, r }) => svg.circle(cx, cy, r, col(stroke), 1.),
+            None => (),
+        };
+        if self.settings.view_settings.mirrors {
+            for (&mirror, &stroke) in self.tiling.mirrors.iter().zip(&gen_cols) {
+                add(mirror, stroke);
+            }
+        }
+        let cut_circles = match (&self.puzzle, &self.puzzle_editor) {
+            (Some(puzzle), _) => &puzzle.cut_circles,
+            (None, Some(editor)) => &editor.puzzle_def.cut_circles,
+            (None, None) => &vec![],
+        };
+        for &cut in cut_circles {
+            add(cut, CUT_COL);
+        }
+        std::fs::write(&export.path, svg.finish()).map_err(|_| ())
+    }
+
+    /// Render each frame of the camera path offscreen and write it out as a PNG.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_animation(&mut self, outlines: &Vec<cga2d::Blade3>) -> Result<(), ()> {
+        std::fs::create_dir_all(&self.animation_export.directory).map_err(|_| ())?;
+
+        let (width, height) = (self.animation_export.width, self.animation_export.height);
+        for i in 0..self.animation_export.frame_count() {
+            let camera_transform = self.animation_export.camera_at(i).ok_or(())?;
+            self.render_offscreen(camera_transform, outlines, width, height);
+            self.gfx_data
+                .export_png(&self.animation_export.frame_path(i))?;
         }
+        Ok(())
+    }
+
+    /// Render the current view offscreen at the export resolution and write it out as a PNG.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_image(&mut self, outlines: &Vec<cga2d::Blade3>) -> Result<(), ()> {
+        let export = &self.image_export;
+        let (width, height) = (export.width, export.height);
+        let path = std::path::PathBuf::from(&export.path);
+        self.render_offscreen(self.camera_transform, outlines, width, height);
+        self.gfx_data.export_png(&path)
     }
 }
 impl eframe::App for App {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, config::HOME_KEY, &self.home);
+        eframe::set_value(storage, config::SETTINGS_KEY, &self.settings);
+        if self.settings.remember_camera {
+            eframe::set_value(
+                storage,
+                config::CAMERA_KEY,
+                &camera::to_components(&self.camera_transform),
+            );
+        }
+    }
+
     fn update(&mut self, ctx: &eframe::egui::Context, _frame: &mut eframe::Frame) {
+        if let Some(error) = self.gfx_data.shader_error() {
+            egui::Window::new("Shader Error").show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.label(RichText::new(error).monospace());
+                });
+            });
+        }
         egui::CentralPanel::default()
             .frame(Frame::none())
             .show(ctx, |ui| {
@@ -188,25 +696,62 @@ impl eframe::App for App {
                             .show(ui, |ui| {
                                 CollapsingHeader::new("Settings").show(ui, |ui| {
                                     ui.collapsing("Tiling Settings", |ui| {
+                                        let mut selected = None;
+                                        egui::ComboBox::from_label("Preset")
+                                            .selected_text("Load...")
+                                            .show_ui(ui, |ui| {
+                                                for (i, preset) in self.presets.iter().enumerate() {
+                                                    if ui.selectable_label(false, &preset.name).clicked() {
+                                                        selected = Some(i);
+                                                    }
+                                                }
+                                            });
+                                        if let Some(i) = selected {
+                                            self.load_preset(i);
+                                        }
                                         ui.horizontal(|ui| {
                                             self.needs.tiling_regenerate |= ui
                                                 .text_edit_singleline(
                                                     &mut self.settings.tiling_settings.schlafli,
                                                 )
                                                 .changed();
-                                            ui.label(
-                                                RichText::new("■").color(
-                                                    match Regex::new(config::SCHLAFLI_PATTERN)
-                                                        .unwrap()
-                                                        .is_match(
-                                                            &self.settings.tiling_settings.schlafli,
-                                                        ) {
-                                                        true => egui::Color32::GREEN,
-                                                        false => egui::Color32::RED,
-                                                    },
-                                                ),
-                                            );
+                                            // Curvature of the symbol as typed, before it's generated
+                                            match config::parse_schlafli(
+                                                &self.settings.tiling_settings.schlafli,
+                                            )
+                                            .map(config::Schlafli)
+                                            {
+                                                Ok(schlafli) => {
+                                                    let kind = schlafli.geometry_kind();
+                                                    let (col, hover) =
+                                                        match schlafli.get_mirrors() {
+                                                            Ok(_) => (egui::Color32::GREEN, None),
+                                                            Err(e) => (
+                                                                egui::Color32::RED,
+                                                                Some(e.to_string()),
+                                                            ),
+                                                        };
+                                                    let r = ui.label(RichText::new("■").color(col));
+                                                    if let Some(hover) = hover {
+                                                        r.on_hover_text(hover);
+                                                    }
+                                                    ui.label(format!("{kind:?}"));
+                                                }
+                                                Err(e) => {
+                                                    ui.label(
+                                                        RichText::new("■")
+                                                            .color(egui::Color32::RED),
+                                                    )
+                                                    .on_hover_text(e.to_string());
+                                                }
+                                            };
                                         });
+                                        if let Some(area) = self.tiling.fundamental_area() {
+                                            ui.label(format!("Fundamental region area: {area:.4}"))
+                                                .on_hover_text(
+                                                    "Measured in a space of curvature ±1",
+                                                );
+                                        }
                                         ui.horizontal(|ui| {
                                             if ui.button("+").clicked() {
                                                 self.settings
@@ -220,15 +765,116 @@ impl eframe::App for App {
                                                 self.needs.tiling_regenerate = true;
                                             }
                                         });
+                                        // Check generators against the symbol as typed, or the
+                                        // current tiling if it doesn't parse
+                                        let rank = config::parse_schlafli(
+                                            &self.settings.tiling_settings.schlafli,
+                                        )
+                                        .map_or(self.tiling.rank, |s| s.len() as u8 + 1);
                                         for rel in &mut self.settings.tiling_settings.relations {
-                                            self.needs.tiling_regenerate |=
-                                                ui.text_edit_singleline(rel).changed();
+                                            ui.horizontal(|ui| {
+                                                self.needs.tiling_regenerate |=
+                                                    ui.text_edit_singleline(rel).changed();
+                                                if let Err(e) = config::parse_relation(rel, rank) {
+                                                    ui.label(
+                                                        RichText::new(e.to_string())
+                                                            .color(egui::Color32::RED),
+                                                    );
+                                                }
+                                            });
+                                        }
+                                        let redundant = &self.tiling.redundant_relations;
+                                        if !redundant.is_empty() {
+                                            let indices: Vec<String> =
+                                                redundant.iter().map(|i| i.to_string()).collect();
+                                            ui.label(
+                                                RichText::new(format!(
+                                                    "{} redundant relations",
+                                                    redundant.len()
+                                                ))
+                                                .color(egui::Color32::YELLOW),
+                                            )
+                                            .on_hover_text(format!(
+                                                "Relations {} repeat an earlier one or the Schläfli symbol, up to rotation and reversal",
+                                                indices.join(", ")
+                                            ));
                                         }
                                         self.needs.tiling_regenerate |= ui
                                             .text_edit_singleline(
                                                 &mut self.settings.tiling_settings.subgroup,
                                             )
                                             .changed();
+                                        ui.horizontal(|ui| {
+                                            if ui.button("Set as Home").clicked() {
+                                                self.home =
+                                                    Some(self.settings.tiling_settings.clone());
+                                            }
+                                            if ui
+                                                .add_enabled(
+                                                    self.home.is_some(),
+                                                    egui::Button::new("Clear Home"),
+                                                )
+                                                .clicked()
+                                            {
+                                                self.home = None;
+                                            }
+                                        });
+                                    });
+                                    #[cfg(not(target_arch = "wasm32"))]
+                                    ui.collapsing("Tiling Gallery", |ui| {
+                                        let gallery = &mut self.gallery;
+                                        ui.horizontal(|ui| {
+                                            ui.radio_value(&mut gallery.entry_count, 2, "{p,q}");
+                                            ui.radio_value(&mut gallery.entry_count, 3, "{p,q,r}");
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.add(Slider::new(&mut gallery.max_entry, 3..=12));
+                                            ui.label("Largest Entry");
+                                        });
+                                        ui.horizontal(|ui| {
+                                            for kind in [
+                                                config::GeometryKind::Spherical,
+                                                config::GeometryKind::Euclidean,
+                                                config::GeometryKind::Hyperbolic,
+                                            ] {
+                                                ui.selectable_value(
+                                                    &mut gallery.kind,
+                                                    kind,
+                                                    format!("{kind:?}"),
+                                                );
+                                            }
+                                        });
+                                        let running =
+                                            gallery.search.as_mut().is_some_and(|s| s.poll());
+                                        if running {
+                                            ui.horizontal(|ui| {
+                                                ui.spinner();
+                                                if ui.button("Cancel").clicked() {
+                                                    if let Some(search) = &gallery.search {
+                                                        search.cancel();
+                                                    }
+                                                }
+                                            });
+                                        } else if ui.button("Search").clicked() {
+                                            gallery.search = Some(gallery::GallerySearch::start(
+                                                self.settings.tiling_settings.clone(),
+                                                gallery.entry_count,
+                                                gallery.max_entry,
+                                                gallery.kind,
+                                                self.settings.tile_limit,
+                                            ));
+                                        }
+                                        if let Some(search) = &gallery.search {
+                                            ui.horizontal_wrapped(|ui| {
+                                                for schlafli in &search.results {
+                                                    if ui.button(schlafli.to_string()).clicked() {
+                                                        self.settings.tiling_settings.schlafli =
+                                                            schlafli.to_string();
+                                                        self.needs.tiling_regenerate = true;
+                                                    }
+                                                }
+                                            });
+                                        }
                                     });
                                     ui.collapsing("View Settings", |ui| {
                                         ui.horizontal(|ui| {
@@ -243,10 +889,35 @@ impl eframe::App for App {
                                         });
                                         ui.horizontal(|ui| {
                                             ui.add(Slider::new(
-                                                &mut self.settings.view_settings.outline_thickness,
+                                                &mut self.settings.view_settings.cell_outline_thickness,
                                                 0.0..=1.0,
                                             ));
-                                            ui.label("Outline Thickness")
+                                            ui.label("Cell Outline Thickness")
+                                        });
+                                        if self.tiling.rank > 3 {
+                                            ui.horizontal(|ui| {
+                                                ui.add(Slider::new(
+                                                    &mut self
+                                                        .settings
+                                                        .view_settings
+                                                        .vertex_outline_thickness,
+                                                    0.0..=1.0,
+                                                ));
+                                                ui.label("Vertex Outline Thickness")
+                                            });
+                                        }
+                                        ui.horizontal(|ui| {
+                                            ui.color_edit_button_rgba_unmultiplied(
+                                                &mut self.settings.view_settings.outline_col,
+                                            );
+                                            ui.label("Outline Colour")
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.add(Slider::new(
+                                                &mut self.settings.view_settings.inset,
+                                                0.0..=0.2,
+                                            ));
+                                            ui.label("Tile Inset")
                                         });
                                         ui.checkbox(
                                             &mut self.settings.view_settings.fundamental,
@@ -256,6 +927,29 @@ impl eframe::App for App {
                                             &mut self.settings.view_settings.mirrors,
                                             "Draw mirrors",
                                         );
+                                        ui.horizontal(|ui| {
+                                            let view = &mut self.settings.view_settings;
+                                            let mut custom = view.custom_edges.is_some();
+                                            if ui
+                                                .checkbox(&mut custom, "Custom edges")
+                                                .on_hover_text(
+                                                    "Choose which mirrors bound a tile, instead of those outside the subgroup",
+                                                )
+                                                .changed()
+                                            {
+                                                view.custom_edges = custom.then(|| {
+                                                    self.tiling.edges[..self.tiling.rank as usize]
+                                                        .to_vec()
+                                                });
+                                            }
+                                            if let Some(edges) = &mut view.custom_edges {
+                                                for (edge, style) in
+                                                    edges.iter_mut().zip(&view.generators)
+                                                {
+                                                    ui.checkbox(edge, &style.label);
+                                                }
+                                            }
+                                        });
                                         ui.checkbox(
                                             &mut self.settings.view_settings.path_debug,
                                             "Draw path",
@@ -268,6 +962,156 @@ impl eframe::App for App {
                                             &mut self.settings.view_settings.inverse_col,
                                             "Colour by neighbours",
                                         );
+                                        ui.checkbox(
+                                            &mut self.settings.view_settings.col_distance,
+                                            "Colour by distance",
+                                        );
+                                        ui.checkbox(
+                                            &mut self.settings.view_settings.cayley_graph,
+                                            "Draw Cayley graph",
+                                        );
+                                        ui.checkbox(
+                                            &mut self.settings.view_settings.legend,
+                                            "Show generator legend",
+                                        );
+                                        ui.collapsing("Generator colours", |ui| {
+                                            for (i, style) in (self.settings.view_settings.generators)
+                                                .iter_mut()
+                                                .enumerate()
+                                            {
+                                                ui.horizontal(|ui| {
+                                                    ui.color_edit_button_rgba_unmultiplied(
+                                                        &mut style.col,
+                                                    );
+                                                    ui.add(
+                                                        egui::TextEdit::singleline(&mut style.label)
+                                                            .desired_width(60.),
+                                                    );
+                                                    if ui.button("Reset").clicked() {
+                                                        *style = GeneratorStyle::new(i);
+                                                    }
+                                                });
+                                            }
+                                        });
+                                        ui.checkbox(
+                                            &mut self.settings.view_settings.highlight_tile,
+                                            "Highlight hovered tile",
+                                        );
+                                        ui.horizontal(|ui| {
+                                            let projection =
+                                                &mut self.settings.view_settings.projection;
+                                            ui.label("Projection").on_hover_text(
+                                                "Model hyperbolic rank 3 tilings are drawn in",
+                                            );
+                                            ui.radio_value(
+                                                projection,
+                                                geom::Projection::Poincare,
+                                                "Poincaré",
+                                            );
+                                            ui.radio_value(
+                                                projection,
+                                                geom::Projection::Klein,
+                                                "Klein",
+                                            );
+                                        });
+                                        let mut measuring = self.measure.is_some();
+                                        if ui
+                                            .checkbox(&mut measuring, "Measure distances")
+                                            .on_hover_text(
+                                                "Click two points to measure between them, in place of twisting",
+                                            )
+                                            .changed()
+                                        {
+                                            self.measure = measuring.then(Vec::new);
+                                        }
+                                        if let Some((distance, tiles_apart)) = self.measurement() {
+                                            ui.label(match distance {
+                                                Some(d) => format!("Distance: {d:.4}"),
+                                                None => "Distance: only measured on hyperbolic rank 3 tilings".to_string(),
+                                            });
+                                            ui.label(match tiles_apart {
+                                                Some(n) => format!("Tiles apart: {n}"),
+                                                None => "Tiles apart: beyond the enumerated tiles".to_string(),
+                                            });
+                                        }
+                                        ui.checkbox(
+                                            &mut self.settings.view_settings.schlafli_polygons,
+                                            "Draw Schläfli polygons",
+                                        );
+                                        ui.checkbox(
+                                            &mut self.settings.view_settings.dual_overlay,
+                                            "Draw tiling and dual",
+                                        );
+                                        ui.checkbox(
+                                            &mut self.settings.view_settings.high_contrast,
+                                            "High contrast",
+                                        );
+                                        ui.horizontal(|ui| {
+                                            ui.add(Slider::new(
+                                                &mut self.settings.view_settings.supersampling,
+                                                1..=4,
+                                            ));
+                                            ui.label("Supersampling");
+                                        })
+                                        .response
+                                        .on_hover_text(
+                                            "Render at several times the resolution and average down for smoother curves",
+                                        );
+                                        ui.horizontal(|ui| {
+                                            let view = &mut self.settings.view_settings;
+                                            ui.add(
+                                                egui::DragValue::new(&mut view.min_zoom)
+                                                    .speed(0.01)
+                                                    .range(1e-6..=view.max_zoom),
+                                            );
+                                            ui.add(
+                                                egui::DragValue::new(&mut view.max_zoom)
+                                                    .speed(10.)
+                                                    .range(view.min_zoom..=1e12),
+                                            );
+                                            ui.label("Zoom limits");
+                                        });
+                                        ui.checkbox(
+                                            &mut self.settings.view_settings.linear_output,
+                                            "Linear colour output",
+                                        )
+                                        .on_hover_text(
+                                            "Store linear colour values instead of sRGB encoded ones",
+                                        );
+                                        ui.horizontal(|ui| {
+                                            ui.checkbox(
+                                                &mut self.settings.view_settings.parity_coloring,
+                                                "Colour by parity",
+                                            );
+                                            for col in &mut self.settings.view_settings.parity_cols
+                                            {
+                                                ui.color_edit_button_rgba_unmultiplied(col);
+                                            }
+                                        });
+                                        ui.horizontal_wrapped(|ui| {
+                                            ui.label("Sticker palette").on_hover_text(
+                                                "Colours for stickers in turn. Empty uses the colour ramp",
+                                            );
+                                            let palette = &mut self.settings.view_settings.palette;
+                                            let mut removed = None;
+                                            for (i, col) in palette.iter_mut().enumerate() {
+                                                ui.color_edit_button_rgba_unmultiplied(col)
+                                                    .context_menu(|ui| {
+                                                        if ui.button("Remove").clicked() {
+                                                            removed = Some(i);
+                                                            ui.close_menu();
+                                                        }
+                                                    });
+                                            }
+                                            if let Some(i) = removed {
+                                                palette.remove(i);
+                                            }
+                                            if ui.button("+").clicked() {
+                                                palette.push(
+                                                    palette.last().copied().unwrap_or([1.; 4]),
+                                                );
+                                            }
+                                        });
                                     });
                                     if let Some(puzzle_editor) = &mut self.puzzle_editor {
                                         ui.collapsing("Puzzle Definition Editor", |ui| {
@@ -280,13 +1124,90 @@ impl eframe::App for App {
                                             if let Some(piece_type) =
                                                 puzzle_editor.active_piece_type
                                             {
-                                                ui.label(format!("Editing type {}", piece_type));
+                                                let region_count = puzzle_editor
+                                                    .puzzle_def
+                                                    .piece_type_masks(piece_type)
+                                                    .len();
+                                                ui.label(format!(
+                                                    "Editing type {} ({} regions)",
+                                                    piece_type, region_count
+                                                ));
+                                            }
+                                            if let Err(e) = &puzzle_editor.validation {
+                                                ui.colored_label(
+                                                    egui::Color32::YELLOW,
+                                                    e.to_string(),
+                                                );
                                             }
                                             if ui.button("+").clicked() {
-                                                puzzle_editor
-                                                    .puzzle_def
-                                                    .piece_types
-                                                    .push(GripSignature::CORE);
+                                                puzzle_editor.add_piece_type();
+                                            }
+                                            ui.label("Base Twists");
+                                            let def = &mut puzzle_editor.puzzle_def;
+                                            let mut removed = None;
+                                            for (i, twist) in def.base_twists.iter().enumerate() {
+                                                let order = (def.quotient_group.element_group)
+                                                    .word_order(twist)
+                                                    .map_or("?".to_string(), |n| n.to_string());
+                                                // Odd twists reverse orientation, mirroring pieces
+                                                let kind = match twist.is_odd() {
+                                                    true => ", reflection",
+                                                    false => "",
+                                                };
+                                                ui.horizontal(|ui| {
+                                                    ui.label(format!(
+                                                        "{i}: {twist}(order {order}{kind})"
+                                                    ));
+                                                    if ui.button("-").clicked() {
+                                                        removed = Some(i);
+                                                    }
+                                                });
+                                            }
+                                            if let Some(i) = removed {
+                                                def.base_twists.remove(i);
+                                            }
+                                            ui.horizontal(|ui| {
+                                                ui.text_edit_singleline(
+                                                    &mut puzzle_editor.new_twist,
+                                                );
+                                                if ui.button("Add Twist").clicked() {
+                                                    match parse_word(&puzzle_editor.new_twist) {
+                                                        Ok(word) => {
+                                                            (puzzle_editor.puzzle_def.base_twists)
+                                                                .push(word);
+                                                            puzzle_editor.new_twist.clear();
+                                                        }
+                                                        Err(()) => self.status = Status::Invalid,
+                                                    }
+                                                }
+                                            });
+                                            ui.label("Cuts");
+                                            let def = &mut puzzle_editor.puzzle_def;
+                                            let mut cuts_changed = false;
+                                            for (i, cut) in def.cuts.iter_mut().enumerate() {
+                                                ui.horizontal(|ui| {
+                                                    cuts_changed |= ui
+                                                        .add(Slider::new(
+                                                            &mut cut.angle,
+                                                            0.0..=std::f64::consts::FRAC_PI_2,
+                                                        ))
+                                                        .changed();
+                                                    cuts_changed |= ui
+                                                        .add(
+                                                            egui::DragValue::new(&mut cut.turns)
+                                                                .range(0..=64),
+                                                        )
+                                                        .changed();
+                                                    ui.label(format!("Cut {i}"));
+                                                });
+                                            }
+                                            // Only the circles move, so any puzzle in progress
+                                            // is kept
+                                            if cuts_changed {
+                                                def.update_cut_circles();
+                                                if let Some(puzzle) = &mut self.puzzle {
+                                                    puzzle.set_cuts(def.cuts.clone());
+                                                }
                                             }
                                             if ui.button("Generate Puzzle").clicked() {
                                                 puzzle_editor.active_piece_type = None;
@@ -299,6 +1220,148 @@ impl eframe::App for App {
                                             }
                                         });
                                     }
+                                    ui.collapsing("Save and Load", |ui| {
+                                        #[cfg(not(target_arch = "wasm32"))]
+                                        ui.horizontal(|ui| {
+                                            ui.label("File");
+                                            ui.text_edit_singleline(&mut self.definition_file);
+                                        });
+                                        #[cfg(target_arch = "wasm32")]
+                                        ui.text_edit_multiline(&mut self.definition_file);
+                                        ui.horizontal(|ui| {
+                                            if ui.button("Save Definition").clicked() {
+                                                let saved = (self.puzzle_editor.as_ref())
+                                                    .ok_or(())
+                                                    .and_then(|e| e.puzzle_def.to_json())
+                                                    .and_then(|json| self.write_definition_file(json));
+                                                self.status = match saved {
+                                                    Ok(()) => Status::Saved,
+                                                    Err(()) => Status::Failed,
+                                                };
+                                            }
+                                            if ui.button("Load Definition").clicked()
+                                                && (self.read_definition_file())
+                                                    .and_then(|json| self.load_definition(&json))
+                                                    .is_err()
+                                            {
+                                                self.status = Status::Failed;
+                                            }
+                                        });
+                                        ui.horizontal(|ui| {
+                                            if ui.button("Save Solve").clicked() {
+                                                let saved = (self.puzzle.as_ref())
+                                                    .ok_or(())
+                                                    .and_then(|p| p.export_log(self.settings.tile_limit))
+                                                    .and_then(|json| self.write_definition_file(json));
+                                                self.status = match saved {
+                                                    Ok(()) => Status::Saved,
+                                                    Err(()) => Status::Failed,
+                                                };
+                                            }
+                                            if ui.button("Load Solve").clicked()
+                                                && (self.read_definition_file())
+                                                    .and_then(|json| self.load_solve(&json))
+                                                    .is_err()
+                                            {
+                                                self.status = Status::Failed;
+                                            }
+                                        });
+                                        if ui
+                                            .button("Load Presets")
+                                            .on_hover_text("Add a JSON list of presets to the preset menu")
+                                            .clicked()
+                                        {
+                                            match (self.read_definition_file())
+                                                .and_then(|json| presets::Preset::from_json(&json))
+                                            {
+                                                Ok(loaded) => self.presets.extend(loaded),
+                                                Err(()) => self.status = Status::Failed,
+                                            }
+                                        }
+                                    });
+                                    #[cfg(not(target_arch = "wasm32"))]
+                                    ui.collapsing("Export Image", |ui| {
+                                        let export = &mut self.image_export;
+                                        ui.horizontal(|ui| {
+                                            ui.add(Slider::new(&mut export.width, 64..=8192));
+                                            ui.label("Width");
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.add(Slider::new(&mut export.height, 64..=8192));
+                                            ui.label("Height");
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.text_edit_singleline(&mut export.path);
+                                            ui.label("File");
+                                        });
+                                        if ui.button("Save Image").clicked() {
+                                            export.requested = true;
+                                        }
+                                    });
+                                    #[cfg(not(target_arch = "wasm32"))]
+                                    ui.collapsing("Export SVG", |ui| {
+                                        let export = &mut self.svg_export;
+                                        ui.horizontal(|ui| {
+                                            ui.add(Slider::new(
+                                                &mut export.sample_count,
+                                                8..=2000,
+                                            ));
+                                            ui.label("Samples per Arc");
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.text_edit_singleline(&mut export.path);
+                                            ui.label("File");
+                                        });
+                                        if ui.button("Save SVG").clicked() {
+                                            export.requested = true;
+                                        }
+                                    });
+                                    #[cfg(not(target_arch = "wasm32"))]
+                                    ui.collapsing("Export Animation", |ui| {
+                                        let export = &mut self.animation_export;
+                                        ui.horizontal(|ui| {
+                                            if ui.button("Set Start").clicked() {
+                                                export.start = Some(self.camera_transform);
+                                            }
+                                            if ui.button("Set End").clicked() {
+                                                export.end = Some(self.camera_transform);
+                                            }
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.add(Slider::new(&mut export.duration, 0.1..=30.0));
+                                            ui.label("Duration (s)");
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.add(Slider::new(&mut export.fps, 1..=120));
+                                            ui.label("FPS");
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.add(Slider::new(&mut export.width, 64..=4096));
+                                            ui.label("Width");
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.add(Slider::new(&mut export.height, 64..=4096));
+                                            ui.label("Height");
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.text_edit_singleline(&mut export.directory);
+                                            ui.label("Directory");
+                                        });
+                                        ui.add_enabled_ui(
+                                            export.start.is_some() && export.end.is_some(),
+                                            |ui| {
+                                                if ui
+                                                    .button(format!(
+                                                        "Export {} Frames",
+                                                        export.frame_count()
+                                                    ))
+                                                    .clicked()
+                                                {
+                                                    export.requested = true;
+                                                }
+                                            },
+                                        );
+                                    });
                                     // if let Some(puzzle) = &mut self.puzzle {
                                     //     ui.collapsing("Puzzle Settings", |ui| {
                                     //         if puzzle.editor.is_none() {
@@ -328,10 +1391,12 @@ impl eframe::App for App {
                                     // }
 
                                     ui.horizontal(|ui| {
-                                        ui.add(
+                                        let r = ui.add(
                                             Slider::new(&mut self.settings.depth, 1..=100)
                                                 .logarithmic(true),
                                         );
+                                        // The preview only reaches as far as the depth
+                                        self.needs.tiling_regenerate |= r.changed() && self.previewing;
                                         ui.label("Iteration Depth");
                                     });
                                     ui.horizontal(|ui| {
@@ -349,20 +1414,223 @@ impl eframe::App for App {
                                         };
                                         ui.label("Tile Limit");
                                     });
-
                                     ui.horizontal(|ui| {
-                                        if ui.button("Reset Camera").clicked() {
-                                            self.camera_transform = cga2d::Rotoflector::ident();
+                                        let key = self.tiling.presentation_key();
+                                        if ui
+                                            .button("Estimate size")
+                                            .on_hover_text(
+                                                "Count the elements within the iteration depth, to see whether the group ends",
+                                            )
+                                            .clicked()
+                                        {
+                                            let growth = self.tiling.growth(
+                                                self.settings.depth as usize,
+                                                GROWTH_LIMIT,
+                                            );
+                                            self.growth = Some((key.clone(), growth));
+                                        }
+                                        if let Some((_, growth)) =
+                                            self.growth.as_ref().filter(|(k, _)| *k == key)
+                                        {
+                                            let order: usize = growth.iter().sum();
+                                            if growth.last().is_some_and(|&n| n > 0) {
+                                                ui.label(
+                                                    RichText::new(format!(
+                                                        "Still growing after {order} elements; likely infinite"
+                                                    ))
+                                                    .color(egui::Color32::YELLOW),
+                                                );
+                                            } else if order > self.settings.tile_limit as usize {
+                                                ui.label(format!(
+                                                    "Order {order}; needs a tile limit of at least {order}"
+                                                ));
+                                            } else {
+                                                ui.label(format!("Order {order}"));
+                                            }
+                                        }
+                                    });
+                                    ui.horizontal(|ui| {
+                                        ui.add(Slider::new(&mut self.settings.target_fps, 1..=240));
+                                        ui.label("Frame Rate Limit");
+                                    });
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut self.settings.pan_inertia, "Pan Inertia");
+                                        ui.add_enabled(
+                                            self.settings.pan_inertia,
+                                            Slider::new(&mut self.settings.pan_friction, 0.01..=1.0)
+                                                .logarithmic(true)
+                                                .text("Friction"),
+                                        );
+                                    });
+
+                                    ui.horizontal(|ui| {
+                                        if ui.button("Reset Camera").clicked() {
+                                            self.camera_transform = cga2d::Rotoflector::ident();
+                                            self.camera_animation = None;
                                         }
+                                        if ui
+                                            .button("Center View")
+                                            .on_hover_text("Move to the least distorted nearby view")
+                                            .clicked()
+                                        {
+                                            let target = camera::optimize_camera(
+                                                &self.tiling.mirrors,
+                                                self.camera_transform,
+                                            );
+                                            self.camera_animation = Some(
+                                                camera::CameraAnimation::new(
+                                                    self.camera_transform,
+                                                    target,
+                                                ),
+                                            );
+                                            self.pan_momentum = None;
+                                        }
+                                        ui.checkbox(
+                                            &mut self.settings.remember_camera,
+                                            "Remember Camera",
+                                        );
                                         self.needs.tiling_regenerate |=
                                             ui.button("Regenerate").clicked();
                                     });
-                                    ui.label(self.status.message());
+                                    ui.collapsing("Camera Bookmarks", |ui| {
+                                        ui.horizontal(|ui| {
+                                            ui.text_edit_singleline(&mut self.bookmark_name);
+                                            if ui.button("Save View").clicked() {
+                                                let bookmarks = &mut self.settings.camera_bookmarks;
+                                                let name = match self.bookmark_name.trim() {
+                                                    "" => format!("View {}", bookmarks.len() + 1),
+                                                    name => name.to_string(),
+                                                };
+                                                bookmarks.push(camera::Bookmark {
+                                                    name,
+                                                    transform: self.camera_transform,
+                                                });
+                                                self.bookmark_name.clear();
+                                            }
+                                        });
+                                        let mut removed = None;
+                                        for (i, bookmark) in
+                                            self.settings.camera_bookmarks.iter().enumerate()
+                                        {
+                                            ui.horizontal(|ui| {
+                                                if ui.button(&bookmark.name).clicked() {
+                                                    self.camera_animation =
+                                                        Some(camera::CameraAnimation::new(
+                                                            self.camera_transform,
+                                                            bookmark.transform,
+                                                        ));
+                                                    self.pan_momentum = None;
+                                                }
+                                                if ui.button("×").clicked() {
+                                                    removed = Some(i);
+                                                }
+                                            });
+                                        }
+                                        if let Some(i) = removed {
+                                            self.settings.camera_bookmarks.remove(i);
+                                        }
+                                    });
                                     if let Some(puzzle) = &self.puzzle {
-                                        ui.label(
-                                            puzzle.puzzle.grip_group.point_count().to_string(),
-                                        );
+                                        let twist_count = puzzle.base_twists.len();
+                                        self.active_twist =
+                                            self.active_twist.min(twist_count.saturating_sub(1));
+                                        if twist_count > 1 {
+                                            ui.horizontal(|ui| {
+                                                ui.label("Twist");
+                                                for i in 0..twist_count {
+                                                    ui.radio_value(
+                                                        &mut self.active_twist,
+                                                        i,
+                                                        i.to_string(),
+                                                    );
+                                                }
+                                            });
+                                        }
+                                    }
+                                    if self.puzzle.is_some() {
+                                        ui.horizontal(|ui| {
+                                            ui.text_edit_singleline(&mut self.notation)
+                                                .on_hover_text(
+                                                    "Grip names with an optional .twist, ' to invert and repeat count, eg. A B' C.1 D2",
+                                                );
+                                            if ui.button("Apply Moves").clicked() {
+                                                self.apply_notation(ctx.input(|i| i.time));
+                                            }
+                                        });
+                                        if let Some(error) = &self.notation_error {
+                                            ui.colored_label(Color32::RED, error.to_string());
+                                        }
+                                        self.move_log_ui(ui);
+                                        if let Some(puzzle) = &self.puzzle {
+                                            let mirrored = puzzle.mirrored_piece_count();
+                                            if mirrored > 0 {
+                                                ui.label(format!("{mirrored} pieces mirrored"));
+                                            }
+                                        }
+                                        ui.horizontal(|ui| {
+                                            if ui
+                                                .button("Solve")
+                                                .on_hover_text("Search for a shortest solution")
+                                                .clicked()
+                                            {
+                                                self.solve_bfs(ctx.input(|i| i.time));
+                                            }
+                                            ui.add(
+                                                Slider::new(&mut self.solve_depth, 1..=12)
+                                                    .text("Max moves"),
+                                            );
+                                        });
                                     }
+                                    ui.horizontal(|ui| {
+                                        if matches!(self.status, Status::Enumerating) {
+                                            ui.spinner();
+                                        }
+                                        ui.label(self.status.message());
+                                        if self.puzzle.as_ref().is_some_and(|p| p.is_solved()) {
+                                            ui.label(
+                                                RichText::new("Solved!").color(Color32::GREEN),
+                                            );
+                                        }
+                                    });
+                                    if let Some(puzzle) = &self.puzzle {
+                                        ui.collapsing("Puzzle Statistics", |ui| {
+                                            let stats = puzzle.stats();
+                                            egui::Grid::new("puzzle_stats").show(ui, |ui| {
+                                                let rows = [
+                                                    ("Pieces", stats.pieces),
+                                                    ("Stickers", stats.stickers),
+                                                    ("Piece types", stats.piece_types),
+                                                    ("Piece orbits", stats.orbits),
+                                                    ("Grips", stats.grips as usize),
+                                                    ("Elements", stats.elements as usize),
+                                                ];
+                                                for (name, count) in rows {
+                                                    ui.label(name);
+                                                    ui.label(count.to_string());
+                                                    ui.end_row();
+                                                }
+                                            });
+                                        });
+                                    }
+                                    ui.collapsing("Coset Tables", |ui| {
+                                        let groups = [
+                                            ("Tile Group", &self.quotient_group.tile_group),
+                                            ("Element Group", &self.quotient_group.element_group),
+                                        ];
+                                        for (name, group) in groups {
+                                            ui.collapsing(name, |ui| {
+                                                egui::ScrollArea::both()
+                                                    .id_source(name)
+                                                    .max_height(300.)
+                                                    .show(ui, |ui| {
+                                                        ui.label(
+                                                            RichText::new(group.to_string())
+                                                                .monospace(),
+                                                        );
+                                                    });
+                                            });
+                                        }
+                                    });
                                     // if ui.button("Move").clicked() {
                                     //     if self.puzzle.apply_move(Word(vec![]), 0, false).is_err() {
                                     //         self.status = Status::Invalid
@@ -371,105 +1639,6 @@ impl eframe::App for App {
                                     //         self.status = Status::Idle
                                     //     };
                                     // }
-                                    // for mirror in self
-                                    //     .tiling
-                                    //     .mirrors
-                                    //     .iter()
-                                    //     .map(|&m| self.camera_transform.sandwich(m))
-                                    // {
-                                    //     let new_b = mirror.sandwich(cga2d::circle(NO, 1.));
-                                    //     let badness = match new_b.unpack(0.0) {
-                                    //         cga2d::LineOrCircle::Line { .. } => f32::INFINITY,
-                                    //         cga2d::LineOrCircle::Circle { cx, cy, r } => {
-                                    //             ((cx * cx + cy * cy) + r.ln() * r.ln()) as f32
-                                    //         }
-                                    //     };
-                                    //     ui.label(badness.to_string());
-                                    // }
-                                    // let clicked = ui.button("Go Forth and Boop").clicked();
-                                    // let mut trans = cga2d::Rotoflector::ident();
-                                    // let mut mirrored = false;
-                                    // let mut mirrors: Vec<Blade3> = self
-                                    //     .tiling
-                                    //     .mirrors
-                                    //     .iter()
-                                    //     .map(|&m| self.camera_transform.sandwich(m))
-                                    //     .collect();
-                                    // for _ in 0..10 {
-                                    //     let mut badness: f32 = mirrors
-                                    //         .iter()
-                                    //         .map(|&m| {
-                                    //             let new_b = m.sandwich(cga2d::circle(NO, 1.));
-                                    //             match new_b.unpack(0.0) {
-                                    //                 cga2d::LineOrCircle::Line { .. } => {
-                                    //                     f32::INFINITY
-                                    //                 }
-                                    //                 cga2d::LineOrCircle::Circle { cx, cy, r } => {
-                                    //                     ((cx * cx + cy * cy) + r.ln() * r.ln())
-                                    //                         as f32
-                                    //                 }
-                                    //             }
-                                    //         })
-                                    //         .sum();
-                                    //     let mut m_index = None;
-                                    //     for (i, mirror) in mirrors.iter().enumerate() {
-                                    //         let new_badness = mirrors
-                                    //             .iter()
-                                    //             .map(|&m| {
-                                    //                 let m = mirror.sandwich(m);
-                                    //                 let new_b = m.sandwich(cga2d::circle(NO, 1.));
-                                    //                 match new_b.unpack(0.0) {
-                                    //                     cga2d::LineOrCircle::Line { .. } => {
-                                    //                         f32::INFINITY
-                                    //                     }
-                                    //                     cga2d::LineOrCircle::Circle {
-                                    //                         cx,
-                                    //                         cy,
-                                    //                         r,
-                                    //                     } => {
-                                    //                         ((cx * cx + cy * cy) + r.ln() * r.ln())
-                                    //                             as f32
-                                    //                     }
-                                    //                 }
-                                    //             })
-                                    //             .sum();
-                                    //         if new_badness < badness {
-                                    //             badness = new_badness;
-                                    //             m_index = Some(i);
-                                    //         }
-                                    //     }
-                                    //     if let Some(m_index) = m_index {
-                                    //         ui.label(m_index.to_string());
-                                    //         let mirror = mirrors[m_index];
-                                    //         mirrors
-                                    //             .iter_mut()
-                                    //             .for_each(|m| *m = mirror.sandwich(*m));
-                                    //         mirrored = !mirrored;
-                                    //         trans = trans * mirror;
-                                    //     }
-                                    // }
-                                    // if mirrored {
-                                    //     trans = self.tiling.mirrors[0] * trans;
-                                    // }
-                                    // if true | clicked {
-                                    //     self.camera_transform =
-                                    //         (trans * self.camera_transform).normalize();
-                                    // }
-                                    // // let goodness = self
-                                    // //     .tiling
-                                    // //     .mirrors
-                                    // //     .iter()
-                                    // //     .map(|&m| {
-                                    // //         let m = self.camera_transform.sandwich(m);
-                                    // //         let new_b = m.sandwich(boundary_circle);
-                                    // //         match new_b.unpack(0.01) {
-                                    // //             cga2d::LineOrCircle::Line { .. } => f32::INFINITY,
-                                    // //             cga2d::LineOrCircle::Circle { cx, cy, r } => {
-                                    // //                 ((cx * cx + cy * cy) + r.ln() * r.ln()) as f32
-                                    // //             }
-                                    // //         }
-                                    // //     })
-                                    // //     .sum();
                                 })
                             });
                     },
@@ -494,24 +1663,35 @@ impl eframe::App for App {
                     }
                 };
 
+                // Any new interaction stops the camera coasting
+                if r.is_pointer_button_down_on() {
+                    self.pan_momentum = None;
+                    self.camera_animation = None;
+                }
+
                 // Scroll zooming
                 if r.hovered() {
                     let scroll_delta = ctx.input(|i| i.smooth_scroll_delta.y / unit);
-                    if scroll_delta.abs() > 0.001 {
-                        let scale = (NO ^ NI)
-                            .connect(cga2d::point(1. + scroll_delta as f64 / 2., 0.))
-                            * (NO ^ NI).connect(cga2d::point(1., 0.));
-                        self.camera_transform = scale * self.camera_transform;
+                    if scroll_delta.abs() > self.settings.tolerances.scroll {
+                        self.pan_momentum = None;
+                        self.camera_animation = None;
+                        let view = &self.settings.view_settings;
+                        self.camera_transform = camera::scroll_zoom(
+                            self.camera_transform,
+                            scroll_delta as f64,
+                            view.min_zoom..=view.max_zoom,
+                        );
                         // self.scale = (self.scale - scroll_delta).max(0.1);
                         // unit = size.min_elem() / (2. * self.scale);
                     }
                 }
                 // Camera movement
                 if r.dragged_by(egui::PointerButton::Secondary) {
-                    if r.drag_delta().length() > 0.1 {
+                    if r.drag_delta().length() > self.settings.tolerances.drag {
                         if let Some(mpos) = r.interact_pointer_pos() {
+                            let klein_disk = self.klein_disk(self.camera_transform);
                             let egui_to_geom = |pos: Pos2| {
-                                let Pos { x, y } = egui_to_screen(pos);
+                                let Pos { x, y } = egui_to_screen(pos).unproject(klein_disk);
                                 cga2d::point(x, y)
                             };
                             let root_pos = egui_to_geom(mpos - r.drag_delta());
@@ -526,6 +1706,9 @@ impl eframe::App for App {
                                 .map(|&m| self.camera_transform.sandwich(m))
                                 .collect();
                             let boundary = match (modifiers.command, modifiers.alt) {
+                                _ if ms.len() < 3 => self
+                                    .camera_transform
+                                    .sandwich(self.tiling.orthogonal_circle()),
                                 (true, false) => {
                                     let third = if self.tiling.rank == 4 {
                                         !ms[3]
@@ -546,76 +1729,145 @@ impl eframe::App for App {
                                 _ => !ms[0] ^ !ms[1] ^ !ms[2],
                             }; // the boundary to fix when transforming space
 
-                            let init_refl = !(root_pos ^ end_pos) ^ !boundary; // get root_pos to end_pos
-                            let f = end_pos ^ !boundary;
-                            let final_refl = !(!init_refl ^ f) ^ f; // restore orientation fixing the "straight line" from root_pos to end_pos
-
-                            self.camera_transform =
-                                (final_refl * init_refl * self.camera_transform).normalize();
+                            if let Some(motion) = geom::drag_motion(
+                                root_pos,
+                                end_pos,
+                                boundary,
+                                &self.settings.tolerances,
+                            ) {
+                                self.camera_transform =
+                                    (motion * self.camera_transform).normalize();
+                                // Kept as the motion over one frame at the
+                                // reference rate, whatever this frame took
+                                let frames = ctx.input(|i| i.stable_dt) as f64 * camera::COAST_FPS;
+                                self.pan_momentum = Some(geom::interpolate_rotoflector(
+                                    cga2d::Rotoflector::ident(),
+                                    motion.into(),
+                                    1. / frames.max(f64::EPSILON),
+                                ));
+                            }
                         }
+                    } else {
+                        // Held still, so there's nothing to coast on when released
+                        self.pan_momentum = None;
+                    }
+                } else if let Some(momentum) = self.pan_momentum {
+                    let dt = ctx.input(|i| i.stable_dt) as f64;
+                    let momentum = geom::interpolate_rotoflector(
+                        cga2d::Rotoflector::ident(),
+                        momentum,
+                        camera::coast_decay(self.settings.pan_friction as f64, dt),
+                    );
+                    if self.settings.pan_inertia && !camera::is_near_identity(&momentum, self.settings.tolerances.identity) {
+                        let step = geom::interpolate_rotoflector(
+                            cga2d::Rotoflector::ident(),
+                            momentum,
+                            dt * camera::COAST_FPS,
+                        );
+                        self.camera_transform = (step * self.camera_transform).normalize();
+                        self.pan_momentum = Some(momentum);
+                        ctx.request_repaint_after(self.settings.frame_interval());
+                    } else {
+                        self.pan_momentum = None;
                     }
                 }
 
                 let camera_transform = self.camera_transform;
+                let klein_disk = self.klein_disk(camera_transform);
                 let egui_to_geom = |pos: Pos2| {
-                    let Pos { x, y } = egui_to_screen(pos);
+                    let Pos { x, y } = egui_to_screen(pos).unproject(klein_disk);
                     camera_transform.rev().sandwich(cga2d::point(x, y))
                 };
                 let geom_to_egui = |pos: cga2d::Blade1| {
                     let (x, y) = camera_transform.sandwich(pos).unpack_point();
-                    screen_to_egui(Pos { x, y })
+                    screen_to_egui(Pos { x, y }.project(klein_disk))
                 };
                 // Move fundamental region to avoid noise
                 if r.middle_clicked() {
                     if let Some(mpos) = ctx.pointer_latest_pos() {
-                        let mut seed = egui_to_geom(mpos);
-                        let mut word = Word(vec![]);
-                        let mut trans = cga2d::Rotoflector::ident();
-                        let mut mirrored = false;
-                        for _ in 0..self.settings.depth {
-                            let mut done = true;
-                            for (i, &mirror) in self.tiling.mirrors.iter().enumerate() {
-                                if !(mirror ^ seed) < 0. {
-                                    let new_seed = mirror.sandwich(seed);
-                                    seed = new_seed;
-                                    done = false;
-                                    word = word * Generator(i as u8);
-                                    trans = trans * mirror;
-                                    mirrored = !mirrored;
-                                }
-                            }
-                            if done {
-                                break;
-                            }
-                        }
+                        let (word, mirrored) =
+                            self.tiling.locate(egui_to_geom(mpos), self.settings.depth);
                         if !mirrored {
-                            self.camera_transform = (self.camera_transform * trans).normalize();
+                            let trans = (word.0.iter())
+                                .fold(cga2d::Rotoflector::ident(), |trans, g| {
+                                    trans * self.tiling.mirrors[g.0 as usize]
+                                });
+                            let target = (self.camera_transform * trans).normalize();
+                            // Shift-click jumps straight there
+                            if ctx.input(|i| i.modifiers.shift) {
+                                self.camera_transform = target;
+                            } else {
+                                self.camera_animation = Some(camera::CameraAnimation::new(
+                                    self.camera_transform,
+                                    target,
+                                ));
+                            }
                         }
                     }
                 }
+                if let Some(animation) = &mut self.camera_animation {
+                    self.camera_transform = animation.step(ctx.input(|i| i.stable_dt) as f64);
+                    if animation.is_done() {
+                        self.camera_animation = None;
+                    } else {
+                        ctx.request_repaint_after(self.settings.frame_interval());
+                    }
+                }
+                // Keep rounding from building up, and start over if it has already broken
+                self.camera_transform = self.camera_transform.normalize();
+                if !camera::is_finite(&self.camera_transform) {
+                    self.camera_transform = cga2d::Rotoflector::ident();
+                    self.camera_animation = None;
+                    self.pan_momentum = None;
+                }
 
                 if self.needs.tiling_regenerate {
                     if let Ok(x) = self.settings.tiling_settings.generate() {
-                        self.tiling = Arc::new(x);
-                        if let Ok(q) = self.tiling.get_quotient_group(self.settings.tile_limit) {
-                            self.quotient_group = Arc::new(q);
-                            self.puzzle_editor = Some(PuzzleEditor::new(PuzzleDefinition::new(
-                                self.tiling.clone(),
-                                self.quotient_group.clone(),
-                            )));
-                            self.needs.puzzle_regenerate = true;
+                        // Keep the old tiling, and the groups it has cached, if nothing
+                        // about it changed
+                        let tiling = if x.schlafli != self.tiling.schlafli
+                            || x.presentation_key() != self.tiling.presentation_key()
+                        {
+                            Arc::new(x)
                         } else {
-                            self.status = Status::Failed;
-                        }
+                            if x.redundant_relations != self.tiling.redundant_relations {
+                                Arc::make_mut(&mut self.tiling).redundant_relations =
+                                    x.redundant_relations;
+                            }
+                            self.tiling.clone()
+                        };
+                        let limit = self.settings.tile_limit;
+                        let tables = (self.quotient_tables.take())
+                            .filter(|t| t.can_extend(&tiling, limit))
+                            .unwrap_or_else(|| QuotientTables::new(&tiling));
+                        // The current tiling stays on screen until its replacement is ready
+                        self.enumeration =
+                            Some(enumeration::Enumeration::start(tiling, limit, tables));
+                        self.status = Status::Enumerating;
                     } else {
                         self.status = Status::Invalid;
+                        self.pending_piece_types = None;
                     }
                     self.needs.tiling_regenerate = false;
                 }
+                if let Some(enumeration) = &mut self.enumeration {
+                    match enumeration.poll() {
+                        Some(Ok(enumerated)) => {
+                            self.enumeration = None;
+                            self.finish_enumeration(enumerated);
+                        }
+                        Some(Err(())) => {
+                            self.enumeration = None;
+                            self.status = Status::Failed;
+                        }
+                        None => ctx.request_repaint_after(self.settings.frame_interval()),
+                    }
+                }
                 if self.needs.puzzle_regenerate {
-                    if let Some(puzzle_editor) = &self.puzzle_editor {
+                    if let Some(puzzle_editor) = &mut self.puzzle_editor {
                         if let Ok(puzzle) = puzzle_editor.puzzle_def.generate_puzzle() {
                             self.puzzle = Some(puzzle);
+                            self.solve_stats = SolveStats::default();
                             self.status = Status::Generated;
                             self.gfx_data.regenerate_puzzle_buffers(
                                 self.camera_transform,
@@ -631,34 +1883,44 @@ impl eframe::App for App {
                     self.gfx_data
                         .regenerate_cut_buffer(self.camera_transform, puzzle);
                 }
-                let mut outlines = vec![];
-                let mirrors = &self.tiling.mirrors;
-                let b_cell = !mirrors[0] ^ !mirrors[1] ^ !mirrors[2];
-                if b_cell.mag2() > 0. {
-                    let bp = b_cell & mirrors[2];
-                    outlines.push(cga2d::slerp(
-                        -mirrors[2],
-                        bp ^ (b_cell.mag2().signum() * mirrors[0] & mirrors[1])
-                            .unpack_point_pair()
-                            .unwrap()[0],
-                        std::f64::consts::PI / 2.
-                            * self.settings.view_settings.outline_thickness as f64,
-                    ));
+                let view_settings = &self.settings.view_settings;
+                let (cell_angle, vertex_angle) = view_settings.outline_angles();
+                let outlines = self.tiling.outlines(
+                    &view_settings.edges(&self.tiling),
+                    cell_angle,
+                    vertex_angle,
+                );
+                #[cfg(not(target_arch = "wasm32"))]
+                if std::mem::take(&mut self.animation_export.requested) {
+                    self.status = match self.export_animation(&outlines) {
+                        Ok(()) => Status::Exported,
+                        Err(()) => Status::Failed,
+                    };
+                    // Put the buffers back for the on-screen view
+                    if let Some(puzzle) = &self.puzzle {
+                        self.gfx_data
+                            .regenerate_cut_buffer(self.camera_transform, puzzle);
+                    }
                 }
-                let b_vert = !mirrors[1] ^ !mirrors[2] ^ !mirrors[3];
-                if b_vert.mag2() > 0. {
-                    let bp = b_vert & mirrors[3];
-                    outlines.push(-cga2d::slerp(
-                        mirrors[3],
-                        bp ^ (b_vert.mag2().signum() * mirrors[1] & mirrors[2])
-                            .unpack_point_pair()
-                            .unwrap()[1],
-                        std::f64::consts::PI / 2.
-                            * self.settings.view_settings.outline_thickness as f64,
-                    ));
+                #[cfg(not(target_arch = "wasm32"))]
+                if std::mem::take(&mut self.image_export.requested) {
+                    self.status = match self.export_image(&outlines) {
+                        Ok(()) => Status::Exported,
+                        Err(()) => Status::Failed,
+                    };
+                    if let Some(puzzle) = &self.puzzle {
+                        self.gfx_data
+                            .regenerate_cut_buffer(self.camera_transform, puzzle);
+                    }
                 }
                 self.gfx_data
                     .regenerate_outline_buffer(camera_transform, &outlines);
+                self.gfx_data
+                    .set_linear_output(self.settings.view_settings.linear_output);
+                self.gfx_data
+                    .set_palette(&self.settings.view_settings.palette);
+                self.gfx_data
+                    .set_supersampling(self.settings.view_settings.supersampling);
                 self.gfx_data.frame(
                     gfx::Params::new(
                         self.tiling
@@ -666,22 +1928,23 @@ impl eframe::App for App {
                             .iter()
                             .map(|&m| self.camera_transform.sandwich(m))
                             .collect(),
-                        self.tiling.edges.clone(),
+                        self.settings.view_settings.edges(&self.tiling),
                         if let Some(mpos) = ctx.pointer_latest_pos() {
                             egui_to_geom(mpos)
                         } else {
                             cga2d::point(0., 1.)
                         },
                         scale,
-                        if let Some(puzzle) = &self.puzzle {
-                            puzzle.cut_circles.len()
-                        } else {
-                            0
-                        },
+                        self.puzzle.as_ref(),
                         outlines.len(),
                         self.settings.depth,
                         &self.settings.view_settings,
-                    ),
+                    )
+                    .with_highlight(
+                        self.hovered_tile
+                            .filter(|_| self.settings.view_settings.highlight_tile),
+                    )
+                    .with_klein_disk(klein_disk),
                     target_size[0],
                     target_size[1],
                 );
@@ -702,77 +1965,162 @@ impl eframe::App for App {
                 //     egui::Color32::GOLD,
                 // );
 
-                let cols = [
-                    egui::Color32::RED,
-                    egui::Color32::GREEN,
-                    egui::Color32::BLUE,
-                    egui::Color32::YELLOW,
-                    egui::Color32::KHAKI,
-                    egui::Color32::BLACK,
-                ];
+                // The tiling may have just been regenerated with a different rank
+                (self.settings.view_settings).resize_generators(self.tiling.rank as usize);
+                let cols = generator_cols(&self.settings.view_settings.generators);
                 let stroke_width = 1.;
+                let high_contrast = self.settings.view_settings.high_contrast;
+                let stroke_col = |col: Color32| match high_contrast {
+                    true => egui::Color32::BLACK,
+                    false => col,
+                };
 
-                let draw_circle = |mirror: cga2d::Blade3, col_index, stroke_width: f32| {
-                    // Find the point pair where the mirror intersects the visible region.
-                    let pp = mirror & boundary_circle;
-                    if let Some(_) = pp.unpack_point_pair() {
-                        let mid = pp.sandwich(cga2d::NI);
-                        let perpendicular_pp = pp.connect(mid) & mirror;
-
-                        // Sample points uniformly along the mirror.
-                        const CURVE_SAMPLE_COUNT: usize = 200;
-                        let points = (0..=CURVE_SAMPLE_COUNT)
-                            .filter_map(|i| {
-                                // Interpolate along a straight line.
-                                let t = i as f64 / CURVE_SAMPLE_COUNT as f64;
-                                let [sample_point, _] =
-                                    cga2d::slerp(pp, perpendicular_pp, t * std::f64::consts::PI)
-                                        .unpack_point_pair()?;
-                                Some(sample_point.unpack_point())
-                            })
-                            .map(|(x, y)| screen_to_egui(Pos { x, y }))
-                            .collect();
+                // Circles stop being circles in the Klein model, so those are traced instead
+                let draw_curve = |curve: &MirrorCurve, col: Color32, stroke_width: f32| match (
+                    curve,
+                    klein_disk,
+                ) {
+                    (&MirrorCurve::Circle { cx, cy, r }, None) => {
+                        ui.painter().circle_stroke(
+                            screen_to_egui(Pos::new(cx, cy)),
+                            (r * unit as f64) as _,
+                            (stroke_width, stroke_col(col)),
+                        );
+                    }
+                    _ => {
                         ui.painter().add(PathShape {
-                            points,
+                            points: (curve.points())
+                                .map(|p| screen_to_egui(p.project(klein_disk)))
+                                .collect(),
                             closed: false,
                             fill: Color32::TRANSPARENT,
-                            stroke: (stroke_width, cols[col_index]).into(),
+                            stroke: (stroke_width, stroke_col(col)).into(),
                         });
-                    } else {
-                        match mirror.unpack(0.001) {
-                            cga2d::LineOrCircle::Line { .. } => (), // does not intersect view
-                            cga2d::LineOrCircle::Circle { cx, cy, r } => {
-                                ui.painter().circle_stroke(
-                                    screen_to_egui(Pos::new(cx, cy)),
-                                    (r * unit as f64) as _,
-                                    (stroke_width, cols[col_index]),
-                                );
-                            }
-                        }
+                    }
+                };
+                let line_tolerance = self.settings.tolerances.line;
+                #[cfg(not(target_arch = "wasm32"))]
+                if std::mem::take(&mut self.svg_export.requested) {
+                    self.status = match self.export_svg(boundary_circle, size / 2. / unit, unit) {
+                        Ok(()) => Status::Exported,
+                        Err(()) => Status::Failed,
+                    };
+                }
+                let draw_circle = |mirror: cga2d::Blade3, col: Color32, stroke_width: f32| {
+                    if let Some(curve) = MirrorCurve::new(
+                        mirror,
+                        boundary_circle,
+                        line_tolerance,
+                        MirrorCurve::SAMPLE_COUNT,
+                    ) {
+                        draw_curve(&curve, col, stroke_width);
                     }
                 };
                 if self.settings.view_settings.mirrors {
-                    for (i, mirror) in self
+                    let curves: Vec<Option<MirrorCurve>> = self
                         .tiling
                         .mirrors
                         .iter()
-                        .map(|&m| self.camera_transform.sandwich(m))
-                        .enumerate()
-                    {
-                        draw_circle(mirror, i, stroke_width);
+                        .map(|&m| {
+                            MirrorCurve::new(
+                                self.camera_transform.sandwich(m),
+                                boundary_circle,
+                                line_tolerance,
+                                MirrorCurve::SAMPLE_COUNT,
+                            )
+                        })
+                        .collect();
+                    let polylines: Vec<Vec<Pos2>> = curves
+                        .iter()
+                        .map(|curve| match curve {
+                            Some(curve) => (curve.points())
+                                .map(|p| screen_to_egui(p.project(klein_disk)))
+                                .collect(),
+                            None => vec![],
+                        })
+                        .collect();
+                    let hovered_mirror = ctx
+                        .pointer_latest_pos()
+                        .filter(|_| r.hovered())
+                        .and_then(|mpos| nearest_curve(mpos, &polylines, 5.));
+                    for (i, curve) in curves.iter().enumerate() {
+                        if let Some(curve) = curve {
+                            let stroke_width = match hovered_mirror == Some(i) {
+                                true => 3.,
+                                false => stroke_width,
+                            };
+                            draw_curve(curve, cols[i], stroke_width);
+                        }
+                    }
+                }
+                if self.settings.view_settings.cayley_graph {
+                    // Each edge is drawn once, from a region's center to its image in one of the
+                    // region's mirrors, so quotients whose words wrap around still draw local arcs
+                    let element_group = &self.quotient_group.element_group;
+                    let center = self.tiling.fundamental_center();
+                    let ends: Vec<(cga2d::Blade1, cga2d::Blade1)> = (0..self.tiling.rank as usize)
+                        .map(|g| {
+                            (
+                                self.tiling.edge_midpoint(center, g),
+                                self.tiling.mirrors[g].sandwich(center),
+                            )
+                        })
+                        .collect();
+                    for (p, g, _) in element_group.cayley_edges() {
+                        let word = &element_group.word_table[p.0 as usize];
+                        let (mid, end) = ends[g.0 as usize];
+                        let [a, m, b] = [center, mid, end]
+                            .map(|v| geom_to_egui(self.tiling.chamber_point(v, word)));
+                        if !egui_rect.contains(a) && !egui_rect.contains(b) {
+                            continue;
+                        }
+                        ui.painter().add(PathShape::line(
+                            arc_through(a, m, b, 16),
+                            (stroke_width, stroke_col(cols[g.0 as usize])),
+                        ));
+                    }
+                }
+                if self.settings.view_settings.schlafli_polygons {
+                    let entries = self.tiling.schlafli.0.len();
+                    for (entry, &col) in cols.iter().enumerate().take(entries) {
+                        if let Some(vertices) = self.tiling.schlafli_polygon(entry) {
+                            ui.painter().add(PathShape {
+                                points: vertices.iter().map(|&v| geom_to_egui(v)).collect(),
+                                closed: true,
+                                fill: Color32::TRANSPARENT,
+                                stroke: (2., stroke_col(col)).into(),
+                            });
+                        }
+                    }
+                }
+                if self.settings.view_settings.dual_overlay {
+                    let (primal_col, dual_col) = match high_contrast {
+                        true => (egui::Color32::BLACK, egui::Color32::DARK_GRAY),
+                        false => (egui::Color32::WHITE, egui::Color32::GOLD),
+                    };
+                    for (dual, col) in [(false, primal_col), (true, dual_col)] {
+                        let Some(path) = self.tiling.edge_path(dual) else {
+                            continue;
+                        };
+                        for word in &self.quotient_group.element_group.word_table {
+                            let points: Vec<Pos2> = (path.iter())
+                                .map(|&p| geom_to_egui(self.tiling.chamber_point(p, word)))
+                                .collect();
+                            if points.iter().any(|&p| egui_rect.contains(p)) {
+                                ui.painter().add(PathShape::line(points, (2., col)));
+                            }
+                        }
                     }
                 }
                 if let Some(puzzle_editor) = &self.puzzle_editor {
                     if let Some(active_piece_type) = puzzle_editor.active_piece_type {
                         let stroke_width = 3.;
-                        let circ = if self.tiling.rank == 3 {
-                            !self.tiling.mirrors[0]
-                                ^ !self.tiling.mirrors[1]
-                                ^ cga2d::point(0.3, 0.)
+                        let circ = if self.tiling.rank == 4 {
+                            self.tiling.orthogonal_circle()
                         } else {
                             !self.tiling.mirrors[0]
                                 ^ !self.tiling.mirrors[1]
-                                ^ !self.tiling.mirrors[2]
+                                ^ cga2d::point(0.3, 0.)
                         };
                         for grip in &puzzle_editor.puzzle_def.piece_types[active_piece_type].0 {
                             let word = &self.quotient_group.tile_group.word_table[grip.0 as usize];
@@ -781,24 +2129,28 @@ impl eframe::App for App {
                                     .sandwich(word.0.iter().fold(circ, |c, g| {
                                         self.tiling.mirrors[g.0 as usize].sandwich(c)
                                     })),
-                                5,
+                                GRIP_COL,
                                 stroke_width,
                             );
                         }
                         for cut in &puzzle_editor.puzzle_def.cut_circles {
-                            draw_circle(self.camera_transform.sandwich(*cut), 4, stroke_width);
+                            draw_circle(self.camera_transform.sandwich(*cut), CUT_COL, stroke_width);
                         }
                     }
                 };
 
-                if r.is_pointer_button_down_on() {
+                let previous_tile = self.hovered_tile.take();
+                // The grip under the pointer is outlined before it's clicked
+                if r.hovered() || r.is_pointer_button_down_on() {
                     if let Some(mpos) = ctx.pointer_latest_pos() {
                         let mut seed = egui_to_geom(mpos);
 
                         // Fill regions
-                        if ui.input(|i| i.pointer.primary_down()) {
-                            ui.painter()
-                                .circle_filled(geom_to_egui(seed), 5., egui::Color32::GRAY);
+                        if r.hovered() || ui.input(|i| i.pointer.primary_down()) {
+                            if ui.input(|i| i.pointer.primary_down()) {
+                                ui.painter()
+                                    .circle_filled(geom_to_egui(seed), 5., egui::Color32::GRAY);
+                            }
                             // for (i, &mirror) in self.tiling.mirrors.iter().enumerate() {
                             //     if !(mirror ^ seed) < 0. {
                             //         ui.painter().circle_filled(
@@ -809,90 +2161,129 @@ impl eframe::App for App {
                             //     }
                             // }
 
-                            let mut word = Word(vec![]);
-                            let circ = !self.tiling.mirrors[0]
-                                ^ !self.tiling.mirrors[1]
-                                ^ !self.tiling.mirrors[2];
-                            let mut mirrored = false;
-                            for _ in 0..self.settings.depth {
-                                let mut done = true;
-                                for (i, &mirror) in self.tiling.mirrors.iter().enumerate() {
-                                    if !(mirror ^ seed) < 0. {
-                                        let new_seed = mirror.sandwich(seed);
-                                        if self.settings.view_settings.path_debug {
-                                            ui.painter().line_segment(
-                                                [geom_to_egui(seed), geom_to_egui(new_seed)],
-                                                (3., cols[i]),
-                                            );
-                                            ui.painter().circle_filled(
-                                                geom_to_egui(new_seed),
-                                                5.,
-                                                egui::Color32::LIGHT_GRAY,
-                                            );
-                                        }
-                                        seed = new_seed;
-                                        done = false;
-                                        word = word * Generator(i as u8);
-                                        mirrored = !mirrored;
-                                    }
-                                }
-                                if done {
-                                    break;
+                            let circ = self.tiling.orthogonal_circle();
+                            let (word, _) = self.tiling.locate(seed, self.settings.depth);
+                            // Replay the folding to draw its path and find where the point lands
+                            for g in &word.0 {
+                                let new_seed = self.tiling.mirrors[g.0 as usize].sandwich(seed);
+                                if self.settings.view_settings.path_debug {
+                                    ui.painter().line_segment(
+                                        [geom_to_egui(seed), geom_to_egui(new_seed)],
+                                        (3., cols[g.0 as usize]),
+                                    );
+                                    ui.painter().circle_filled(
+                                        geom_to_egui(new_seed),
+                                        5.,
+                                        egui::Color32::LIGHT_GRAY,
+                                    );
                                 }
+                                seed = new_seed;
                             }
                             draw_circle(
                                 self.camera_transform.sandwich(
-                                    word.inverse().0.iter().fold(circ, |c, g| {
+                                    (word.inverse(&self.quotient_group.element_group).0.iter())
+                                        .fold(circ, |c, g| {
                                         self.tiling.mirrors[g.0 as usize].sandwich(c)
                                     }),
                                 ),
-                                4,
+                                CUT_COL,
                                 stroke_width,
                             );
+                            let editing = (self.puzzle_editor.as_ref())
+                                .is_some_and(|e| e.active_piece_type.is_some());
+                            let grip = self.quotient_group.tile_group.mul_word(
+                                &Point::INIT,
+                                &word.inverse(&self.quotient_group.tile_group),
+                            );
+                            // Name the grip as it's written in move notation, grayed out if
+                            // clicking wouldn't twist it
+                            if let (Some(puzzle), Some(grip), false) = (&self.puzzle, grip, editing)
+                            {
+                                let inverse = ctx.input(|i| i.modifiers.shift);
+                                let col =
+                                    match puzzle.can_apply_move(&word, self.active_twist, inverse) {
+                                        true => egui::Color32::WHITE,
+                                        false => egui::Color32::GRAY,
+                                    };
+                                ui.painter().text(
+                                    mpos + egui::vec2(12., -12.),
+                                    egui::Align2::LEFT_BOTTOM,
+                                    notation::grip_name(grip),
+                                    egui::FontId::proportional(16.),
+                                    col,
+                                );
+                            }
+                            self.hovered_tile = grip;
+                            if let (Some(tile), true) =
+                                (grip, self.settings.view_settings.highlight_tile)
+                            {
+                                egui::show_tooltip_at_pointer(
+                                    ctx,
+                                    ui.layer_id(),
+                                    egui::Id::new("Hovered Tile"),
+                                    |ui| {
+                                        ui.label(format!("Tile {}", tile.0));
+                                        ui.label(format!("Word: {word}"));
+                                    },
+                                );
+                            }
+                            // Right-click offers every twist available at a grip
+                            if r.secondary_clicked() && !editing {
+                                self.selected_grip = grip.map(|g| (g, mpos));
+                            }
                             if ctx.input(|i| i.pointer.primary_pressed()) {
-                                if let Some(puzzle_editor) = &mut self.puzzle_editor {
+                                if let Some(points) = &mut self.measure {
+                                    // A third click starts a new measurement
+                                    if points.len() == 2 {
+                                        points.clear();
+                                    }
+                                    points.push(egui_to_geom(mpos));
+                                } else if let Some(puzzle_editor) = &mut self.puzzle_editor {
                                     if let Some(active_piece_type) = puzzle_editor.active_piece_type
                                     {
                                         if word.0.len() == 0 {
                                             let mask = puzzle_editor.puzzle_def.get_cut_mask(seed);
-                                            if puzzle_editor.puzzle_def.cut_map[mask]
-                                                == Some(active_piece_type)
-                                            {
-                                                puzzle_editor.puzzle_def.cut_map[mask] = None;
-                                            } else {
-                                                puzzle_editor.puzzle_def.cut_map[mask] =
-                                                    Some(active_piece_type);
-                                            }
+                                            puzzle_editor.toggle_region(active_piece_type, mask);
                                         } else {
                                             if let Some(grip) = self
                                                 .quotient_group
                                                 .tile_group
-                                                .mul_word(&Point::INIT, &word.inverse())
+                                                .mul_word(
+                                                    &Point::INIT,
+                                                    &word.inverse(&self.quotient_group.tile_group),
+                                                )
                                             {
-                                                // TODO: hide this
-                                                if puzzle_editor.puzzle_def.piece_types
-                                                    [active_piece_type]
-                                                    .contains(&grip)
-                                                {
-                                                    puzzle_editor.puzzle_def.piece_types
-                                                        [active_piece_type]
-                                                        .0
-                                                        .retain(|g| g.0 != grip.0);
-                                                } else {
-                                                    puzzle_editor.puzzle_def.piece_types
-                                                        [active_piece_type]
-                                                        .0
-                                                        .push(grip);
-                                                }
+                                                puzzle_editor
+                                                    .toggle_grip(active_piece_type, grip);
                                             }
                                         }
                                     } else {
                                         if let Some(puzzle) = &mut self.puzzle {
-                                            if puzzle.apply_move(word, 0, false).is_err() {
-                                                self.status = Status::Invalid
-                                            } else {
-                                                self.gfx_data.regenerate_sticker_buffer(&puzzle);
-                                                self.status = Status::Idle
+                                            // Shift-click twists the other way
+                                            let inverse = ctx.input(|i| i.modifiers.shift);
+                                            match puzzle.apply_move(word, self.active_twist, inverse)
+                                            {
+                                                Ok(()) => {
+                                                    self.gfx_data
+                                                        .regenerate_sticker_buffer(&puzzle);
+                                                    self.solve_stats.record_move(
+                                                        ctx.input(|i| i.time),
+                                                        puzzle.is_solved(),
+                                                    );
+                                                    self.status = Status::Idle
+                                                }
+                                                Err(MoveError::Unenumerated) => {
+                                                    self.status = Status::Unenumerated
+                                                }
+                                                Err(MoveError::Jumbling) => {
+                                                    self.status = Status::Jumbling
+                                                }
+                                                Err(MoveError::Blocked) => {
+                                                    self.status = Status::Blocked
+                                                }
+                                                Err(MoveError::Invalid) => {
+                                                    self.status = Status::Invalid
+                                                }
                                             };
                                         }
                                     }
@@ -901,7 +2292,181 @@ impl eframe::App for App {
                         }
                     }
                 }
+                if let Some(points) = &self.measure {
+                    let points: Vec<Pos2> = points.iter().map(|&p| geom_to_egui(p)).collect();
+                    if let &[a, b] = &points[..] {
+                        ui.painter().line_segment([a, b], (2., Color32::WHITE));
+                    }
+                    for p in points {
+                        ui.painter().circle_filled(p, 4., Color32::WHITE);
+                    }
+                }
+                // The tint is drawn a frame behind the pointer, so catch up once it settles
+                if self.settings.view_settings.highlight_tile && self.hovered_tile != previous_tile
+                {
+                    ctx.request_repaint_after(self.settings.frame_interval());
+                }
             });
+        if let (Some((grip, pos)), Some(puzzle)) = (self.selected_grip, &mut self.puzzle) {
+            let mut chosen = None;
+            let mut close = ctx.input(|i| i.key_pressed(egui::Key::Escape));
+            egui::Area::new(egui::Id::new("Twist Handles"))
+                .fixed_pos(pos + vec2(12., 4.))
+                .show(ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label(notation::grip_name(grip));
+                            for (twist, inverse) in puzzle.available_twists(&grip) {
+                                let name = format!("{}{}", twist, if inverse { "'" } else { "" });
+                                let mut r = ui.button(name);
+                                if puzzle.is_reflection(twist) {
+                                    r = r.on_hover_text("Reflection");
+                                }
+                                if r.clicked() {
+                                    chosen = Some((twist, inverse));
+                                }
+                            }
+                            close |= ui.button("×").clicked();
+                        });
+                    });
+                });
+            if let (Some((twist, inverse)), Some(attitude)) = (chosen, puzzle.grip_attitude(grip)) {
+                match puzzle.apply_move(attitude, twist, inverse) {
+                    Ok(()) => {
+                        self.gfx_data.regenerate_sticker_buffer(puzzle);
+                        self.solve_stats
+                            .record_move(ctx.input(|i| i.time), puzzle.is_solved());
+                        self.status = Status::Idle
+                    }
+                    Err(MoveError::Unenumerated) => self.status = Status::Unenumerated,
+                    Err(MoveError::Jumbling) => self.status = Status::Jumbling,
+                    Err(MoveError::Blocked) => self.status = Status::Blocked,
+                    Err(MoveError::Invalid) => self.status = Status::Invalid,
+                }
+            }
+            if close {
+                self.selected_grip = None;
+            }
+        }
+        if !ctx.wants_keyboard_input() {
+            let undo = ctx.input_mut(|i| i.consume_shortcut(&UNDO_SHORTCUT));
+            let redo = ctx.input_mut(|i| i.consume_shortcut(&REDO_SHORTCUT));
+            // While a piece type is being edited, undo applies to the edits instead of moves
+            let editor = (self.puzzle_editor.as_mut()).filter(|e| e.active_piece_type.is_some());
+            if let (Some(editor), true) = (editor, undo || redo) {
+                match undo {
+                    true => editor.undo(),
+                    false => editor.redo(),
+                };
+            } else if let (Some(puzzle), true) = (&mut self.puzzle, undo || redo) {
+                let result = match undo {
+                    true => puzzle.undo(),
+                    false => puzzle.redo(),
+                };
+                match result {
+                    // Taking moves back doesn't count towards a solve
+                    Ok(true) => {
+                        self.gfx_data.regenerate_sticker_buffer(puzzle);
+                        self.status = Status::Idle
+                    }
+                    Ok(false) => (),
+                    Err(MoveError::Unenumerated) => self.status = Status::Unenumerated,
+                    Err(MoveError::Jumbling) => self.status = Status::Jumbling,
+                    Err(MoveError::Blocked) => self.status = Status::Blocked,
+                    Err(MoveError::Invalid) => self.status = Status::Invalid,
+                }
+            }
+        }
+        if self.solve_stats.move_count > 0 {
+            let now = ctx.input(|i| i.time);
+            let stats = &self.solve_stats;
+            egui::Area::new(egui::Id::new("Solve Stats"))
+                .anchor(egui::Align2::RIGHT_TOP, vec2(-10., 10.))
+                .show(ctx, |ui| {
+                    Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.label(format!("Moves: {}", stats.move_count));
+                        ui.label(format!("Time: {:.1}s", stats.elapsed(now)));
+                        ui.label(format!("Moves/s: {:.2}", stats.moves_per_second(now)));
+                    });
+                });
+        }
+        if self.settings.view_settings.legend {
+            egui::Area::new(egui::Id::new("Generator Legend"))
+                .anchor(egui::Align2::RIGHT_BOTTOM, vec2(-10., -10.))
+                .show(ctx, |ui| {
+                    Frame::popup(ui.style()).show(ui, |ui| {
+                        generator_legend(ui, &self.settings.view_settings.generators);
+                    });
+                });
+        }
+    }
+}
+
+/// Generate a tiling and enumerate its groups.
+fn generate_tiling(
+    tiling_settings: &TilingSettings,
+    tile_limit: u32,
+) -> Result<(Arc<Tiling>, Arc<QuotientGroup>), ()> {
+    let tiling = tiling_settings.generate().map_err(|_| ())?;
+    let quotient_group = tiling
+        .get_quotient_group_cached(tile_limit, None, None)
+        .map_err(|_| ())?;
+    Ok((Arc::new(tiling), quotient_group))
+}
+
+/// Home tiling saved by a previous session, if one was chosen.
+fn load_home(storage: Option<&dyn eframe::Storage>) -> Option<TilingSettings> {
+    storage
+        .and_then(|s| eframe::get_value::<Option<TilingSettings>>(s, config::HOME_KEY))
+        .flatten()
+}
+
+/// Tiling and groups shown when the app opens.
+struct Startup {
+    tiling_settings: TilingSettings,
+    tiling: Arc<Tiling>,
+    quotient_group: Arc<QuotientGroup>,
+    /// Whether the groups are only a preview, as none of the tilings fit in the tile limit
+    previewing: bool,
+}
+impl Startup {
+    /// Start on the home tiling, then the one last used, falling back to the built-in one
+    /// if neither generates. If even that doesn't fit in the tile limit, preview what's
+    /// near its fundamental region instead.
+    fn new(home: Option<&TilingSettings>, settings: &Settings) -> Self {
+        let generated = (home.into_iter())
+            .chain([&settings.tiling_settings])
+            .find_map(|t| Some((t, generate_tiling(t, settings.tile_limit).ok()?)));
+        if let Some((t, (tiling, quotient_group))) = generated {
+            return Self {
+                tiling_settings: t.clone(),
+                tiling,
+                quotient_group,
+                previewing: false,
+            };
+        }
+
+        let tiling_settings = TilingSettings::default();
+        let tiling = Arc::new(
+            tiling_settings
+                .generate()
+                .expect("Built-in tiling is valid"),
+        );
+        let (quotient_group, previewing) =
+            match tiling.get_quotient_group_cached(settings.tile_limit, None, None) {
+                Ok(quotient_group) => (quotient_group, false),
+                Err(_) => {
+                    let depth = settings.depth as usize;
+                    let preview = tiling.get_quotient_group_bounded(depth, settings.tile_limit);
+                    (Arc::new(preview), true)
+                }
+            };
+        Self {
+            tiling_settings,
+            tiling,
+            quotient_group,
+            previewing,
+        }
     }
 }
 
@@ -914,6 +2479,29 @@ impl Pos {
     fn new(x: f64, y: f64) -> Self {
         Self { x, y }
     }
+
+    /// Where a point of the Poincaré disk is drawn, moved into the Klein model when there's
+    /// a disk to redraw.
+    fn project(self, klein_disk: Option<geom::Disk>) -> Self {
+        match klein_disk {
+            Some(disk) => {
+                let (x, y) = disk.poincare_to_klein(self.x, self.y);
+                Self { x, y }
+            }
+            None => self,
+        }
+    }
+
+    /// Undo `project`.
+    fn unproject(self, klein_disk: Option<geom::Disk>) -> Self {
+        match klein_disk {
+            Some(disk) => {
+                let (x, y) = disk.klein_to_poincare(self.x, self.y);
+                Self { x, y }
+            }
+            None => self,
+        }
+    }
 }
 impl From<Pos> for Pos2 {
     fn from(value: Pos) -> Self {
@@ -924,6 +2512,151 @@ impl From<Pos> for Pos2 {
     }
 }
 
+/// Most elements defined when estimating the size of a group.
+const GROWTH_LIMIT: u32 = 50_000;
+
+/// Stroke colour of cut circles and the outline of the hovered grip.
+const CUT_COL: Color32 = Color32::KHAKI;
+/// Stroke colour of the grips of the piece type being edited.
+const GRIP_COL: Color32 = Color32::BLACK;
+
+/// Stroke colour of each generator's mirror and Cayley graph edges.
+fn generator_cols(styles: &[GeneratorStyle]) -> Vec<Color32> {
+    (styles.iter())
+        .map(|s| egui::Rgba::from_rgba_unmultiplied(s.col[0], s.col[1], s.col[2], s.col[3]).into())
+        .collect()
+}
+
+/// Swatches naming the colour of each generator, for reading the debug overlays.
+fn generator_legend(ui: &mut egui::Ui, styles: &[GeneratorStyle]) {
+    for (style, col) in styles.iter().zip(generator_cols(styles)) {
+        ui.horizontal(|ui| {
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(12., 12.), egui::Sense::hover());
+            ui.painter().rect_filled(rect, 2., col);
+            ui.label(&style.label);
+        });
+    }
+}
+
+/// Curve traced by a mirror in screen space.
+enum MirrorCurve {
+    /// Arc crossing the visible region, sampled uniformly along the mirror
+    Arc(Vec<Pos>),
+    /// Circle lying entirely within the visible region
+    Circle { cx: f64, cy: f64, r: f64 },
+}
+impl MirrorCurve {
+    const SAMPLE_COUNT: usize = 200;
+
+    /// Find the visible part of a mirror, if any, sampling arcs at `sample_count` points.
+    fn new(
+        mirror: cga2d::Blade3,
+        boundary_circle: cga2d::Blade3,
+        line_tolerance: f64,
+        sample_count: usize,
+    ) -> Option<Self> {
+        // Find the point pair where the mirror intersects the visible region.
+        let pp = mirror & boundary_circle;
+        if let Some(_) = pp.unpack_point_pair() {
+            let mid = pp.sandwich(cga2d::NI);
+            let perpendicular_pp = pp.connect(mid) & mirror;
+
+            // Sample points uniformly along the mirror.
+            let points = (0..=sample_count)
+                .filter_map(|i| {
+                    // Interpolate along a straight line.
+                    let t = i as f64 / sample_count as f64;
+                    let [sample_point, _] =
+                        cga2d::slerp(pp, perpendicular_pp, t * std::f64::consts::PI)
+                            .unpack_point_pair()?;
+                    let (x, y) = sample_point.unpack_point();
+                    Some(Pos { x, y })
+                })
+                .collect();
+            Some(Self::Arc(points))
+        } else {
+            match mirror.unpack(line_tolerance) {
+                cga2d::LineOrCircle::Line { .. } => None, // does not intersect view
+                cga2d::LineOrCircle::Circle { cx, cy, r } => Some(Self::Circle { cx, cy, r }),
+            }
+        }
+    }
+
+    /// Polyline approximating the curve, closed for full circles.
+    fn points(&self) -> Box<dyn Iterator<Item = Pos> + '_> {
+        match self {
+            MirrorCurve::Arc(points) => Box::new(points.iter().copied()),
+            &MirrorCurve::Circle { cx, cy, r } => {
+                Box::new((0..=Self::SAMPLE_COUNT).map(move |i| {
+                    let t = i as f64 / Self::SAMPLE_COUNT as f64 * std::f64::consts::TAU;
+                    Pos::new(cx + r * t.cos(), cy + r * t.sin())
+                }))
+            }
+        }
+    }
+}
+
+/// Index of the polyline passing closest to the cursor, if any is within `threshold`.
+fn nearest_curve(cursor: Pos2, polylines: &[Vec<Pos2>], threshold: f32) -> Option<usize> {
+    polylines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, points)| {
+            let dist = points
+                .windows(2)
+                .map(|w| distance_to_segment(cursor, w[0], w[1]))
+                .fold(f32::INFINITY, f32::min);
+            (dist <= threshold).then_some((i, dist))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i)
+}
+
+/// Points along the circular arc from `a` through `m` to `b`, or the straight segment if
+/// the three are collinear.
+fn arc_through(a: Pos2, m: Pos2, b: Pos2, sample_count: usize) -> Vec<Pos2> {
+    let d = 2. * (a.x * (m.y - b.y) + m.x * (b.y - a.y) + b.x * (a.y - m.y));
+    if d.abs() <= 1e-4 * (b - a).length_sq() {
+        return vec![a, b];
+    }
+    let (a2, m2, b2) = (
+        a.to_vec2().length_sq(),
+        m.to_vec2().length_sq(),
+        b.to_vec2().length_sq(),
+    );
+    let c = Pos2::new(
+        (a2 * (m.y - b.y) + m2 * (b.y - a.y) + b2 * (a.y - m.y)) / d,
+        (a2 * (b.x - m.x) + m2 * (a.x - b.x) + b2 * (m.x - a.x)) / d,
+    );
+    let r = (a - c).length();
+    let angle = |p: Pos2| (p.y - c.y).atan2(p.x - c.x);
+    let start = angle(a);
+    let to_mid = (angle(m) - start).rem_euclid(std::f32::consts::TAU);
+    let mut sweep = (angle(b) - start).rem_euclid(std::f32::consts::TAU);
+    // Go around whichever way passes through the middle point
+    if to_mid > sweep {
+        sweep -= std::f32::consts::TAU;
+    }
+    (0..=sample_count)
+        .map(|i| {
+            let t = start + sweep * i as f32 / sample_count as f32;
+            c + r * egui::vec2(t.cos(), t.sin())
+        })
+        .collect()
+}
+
+/// Distance from a point to the closest point of a line segment.
+fn distance_to_segment(p: Pos2, a: Pos2, b: Pos2) -> f32 {
+    let ab = b - a;
+    let len_sq = ab.length_sq();
+    let t = if len_sq > 0. {
+        ((p - a).dot(ab) / len_sq).clamp(0., 1.)
+    } else {
+        0.
+    };
+    p.distance(a + t * ab)
+}
+
 /// Rounds an egui rectangle to the nearest pixel boundary and returns the
 /// rounded egui rectangle, along with its width & height in pixels.
 pub fn rounded_pixel_rect(
@@ -953,3 +2686,184 @@ pub fn rounded_pixel_rect(
     ];
     (egui_rect, pixel_size)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether every tag is closed in order and every attribute value is quoted, which is
+    /// as much of XML as the SVG writer produces.
+    fn well_formed(xml: &str) -> bool {
+        let mut open = vec![];
+        let mut rest = xml;
+        while let Some(start) = rest.find('<') {
+            if !rest[..start].trim().is_empty() {
+                return false;
+            }
+            let Some(len) = rest[start..].find('>') else {
+                return false;
+            };
+            let tag = &rest[start + 1..start + len];
+            rest = &rest[start + len + 1..];
+            if tag.matches('"').count() & 1 == 1 {
+                return false;
+            }
+            let name = |tag: &str| tag.split_whitespace().next().unwrap_or("").to_string();
+            if let Some(closing) = tag.strip_prefix('/') {
+                if open.pop() != Some(name(closing)) {
+                    return false;
+                }
+            } else if !tag.ends_with('/') {
+                open.push(name(tag));
+            }
+        }
+        rest.trim().is_empty() && open.is_empty()
+    }
+
+    /// App storage kept in memory rather than on disk.
+    #[derive(Default)]
+    struct MemoryStorage(std::collections::HashMap<String, String>);
+    impl eframe::Storage for MemoryStorage {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+        fn set_string(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_string(), value);
+        }
+        fn flush(&mut self) {}
+    }
+
+    #[test]
+    fn cursor_picks_nearest_mirror_arc() {
+        // A horizontal line and a quarter circle, as drawn for two mirrors
+        let line = vec![Pos2::new(0., 0.), Pos2::new(100., 0.)];
+        let arc = arc_through(
+            Pos2::new(100., 0.),
+            Pos2::new(50. * (1. + 0.5f32.sqrt()), 50. * 0.5f32.sqrt()),
+            Pos2::new(50., 50.),
+            32,
+        );
+        let polylines = [line, arc];
+        assert_eq!(nearest_curve(Pos2::new(40., 2.), &polylines, 5.), Some(0));
+        assert_eq!(nearest_curve(Pos2::new(87., 36.), &polylines, 5.), Some(1));
+        // Near both, the closer one wins
+        assert_eq!(nearest_curve(Pos2::new(98., 3.), &polylines, 5.), Some(1));
+        assert_eq!(nearest_curve(Pos2::new(20., 40.), &polylines, 5.), None);
+    }
+
+    #[test]
+    fn svg_writer_output_is_well_formed() {
+        let mut svg = export::SvgWriter::new(2., 1., 100.);
+        svg.polyline([(0., 0.), (1., 0.5), (-1., 0.25)], Color32::RED, 1.);
+        svg.circle(0.5, 0., 0.25, Color32::BLUE, 2.);
+        let svg = svg.finish();
+        assert!(well_formed(&svg));
+        assert_eq!(svg.matches("<polyline").count(), 1);
+        assert_eq!(svg.matches("<circle").count(), 1);
+        assert!(!well_formed(&svg.replace("</g>", "")));
+    }
+
+    #[test]
+    fn svg_of_hyperbolic_tiling_is_well_formed() {
+        let preset = (presets::builtin().into_iter())
+            .find(|p| p.tiling_settings.schlafli == "{7,3}")
+            .unwrap();
+        let tiling = preset.tiling_settings.generate().unwrap();
+        let boundary_circle = cga2d::circle(cga2d::NO, 2.);
+        let line_tolerance = Settings::new().tolerances.line;
+
+        let mut svg = export::SvgWriter::new(2., 2., 100.);
+        for &mirror in &tiling.mirrors {
+            match MirrorCurve::new(mirror, boundary_circle, line_tolerance, 50) {
+                Some(MirrorCurve::Arc(points)) => {
+                    svg.polyline(points.iter().map(|p| (p.x, p.y)), Color32::BLACK, 1.)
+                }
+                Some(MirrorCurve::Circle { cx, cy, r }) => {
+                    svg.circle(cx, cy, r, Color32::BLACK, 1.)
+                }
+                None => (),
+            }
+        }
+        let svg = svg.finish();
+        assert!(well_formed(&svg));
+        // Every mirror of {7,3} passes through the view around the origin
+        let curves = svg.matches("<polyline").count() + svg.matches("<circle").count();
+        assert_eq!(curves, tiling.mirrors.len());
+    }
+
+    #[test]
+    fn low_tile_limit_starts_on_preview() {
+        // The built-in {6,5,3} tiling has 660 elements
+        let settings = Settings {
+            tile_limit: 100,
+            ..Settings::new()
+        };
+        let startup = Startup::new(None, &settings);
+        assert!(startup.previewing);
+        assert!(!startup.quotient_group.element_group.is_complete());
+
+        let startup = Startup::new(None, &Settings::new());
+        assert!(!startup.previewing);
+        assert_eq!(startup.quotient_group.element_group.order(), Some(660));
+    }
+
+    #[test]
+    fn solve_stats_timer() {
+        let mut stats = SolveStats::default();
+        assert_eq!(stats.elapsed(5.), 0.);
+        stats.record_move(1., false);
+        stats.record_move(2., false);
+        assert_eq!((stats.move_count, stats.elapsed(4.)), (2, 3.));
+        stats.record_move(3., true);
+        assert_eq!((stats.move_count, stats.elapsed(10.)), (3, 2.));
+        assert_eq!(stats.moves_per_second(10.), 1.5);
+
+        // The next move starts a new attempt
+        stats.record_move(20., false);
+        assert_eq!((stats.move_count, stats.elapsed(21.)), (1, 1.));
+    }
+
+    #[test]
+    fn saved_home_loads_on_restart() {
+        let home = TilingSettings {
+            schlafli: "{4,3}".to_string(),
+            relations: vec![],
+            subgroup: "0;1".to_string(),
+        };
+        let mut storage = MemoryStorage::default();
+        assert!(load_home(Some(&storage)).is_none());
+        eframe::set_value(&mut storage, config::HOME_KEY, &Some(home.clone()));
+        let loaded = load_home(Some(&storage)).unwrap();
+        assert_eq!(loaded.schlafli, home.schlafli);
+        assert_eq!(loaded.relations, home.relations);
+        assert_eq!(loaded.subgroup, home.subgroup);
+        // Clearing the home saves `None` over it
+        eframe::set_value(&mut storage, config::HOME_KEY, &None::<TilingSettings>);
+        assert!(load_home(Some(&storage)).is_none());
+        assert!(load_home(None).is_none());
+    }
+
+    #[test]
+    fn startup_prefers_home_tiling() {
+        let home = TilingSettings {
+            schlafli: "{4,3}".to_string(),
+            relations: vec![],
+            subgroup: "0;1".to_string(),
+        };
+        let settings = Settings::new();
+        let startup = Startup::new(Some(&home), &settings);
+        assert_eq!(startup.tiling_settings.schlafli, "{4,3}");
+        assert_eq!(startup.tiling.settings.subgroup, "0;1");
+        assert!(!startup.previewing);
+        // A home that doesn't generate falls back to the last used tiling
+        let broken = TilingSettings {
+            schlafli: "{4,".to_string(),
+            ..home
+        };
+        let startup = Startup::new(Some(&broken), &settings);
+        assert_eq!(
+            startup.tiling_settings.schlafli,
+            settings.tiling_settings.schlafli
+        );
+    }
+}
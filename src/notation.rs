@@ -0,0 +1,170 @@
+use std::fmt;
+
+use crate::group::Point;
+
+/// Name of a grip in move notation: A to Z, then AA, AB and so on.
+pub fn grip_name(grip: Point) -> String {
+    let mut n = grip.0 as usize + 1;
+    let mut name = vec![];
+    while n > 0 {
+        n -= 1;
+        name.push(b'A' + (n % 26) as u8);
+        n /= 26;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap()
+}
+
+/// Inverse of `grip_name`.
+pub fn parse_grip_name(name: &str) -> Option<Point> {
+    if name.is_empty() || !name.bytes().all(|b| b.is_ascii_uppercase()) {
+        return None;
+    }
+    let n = (name.bytes()).try_fold(0u32, |n, b| {
+        n.checked_mul(26)?.checked_add((b - b'A') as u32 + 1)
+    })?;
+    Some(Point(n - 1))
+}
+
+/// Largest repeat count accepted, far beyond the order of any twist worth writing out.
+/// Keeps a typo like `A999999999` from queueing a billion moves.
+pub const MAX_REPEAT: usize = 100;
+
+/// One token of move notation, eg. `B`, `AC'`, `D.1`, `C'2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotationMove {
+    pub grip: Point,
+    /// Index into the puzzle's base twists, written after a `.`
+    pub twist: usize,
+    /// Written as a trailing `'`
+    pub inverse: bool,
+    pub repeat: usize,
+}
+
+impl fmt::Display for NotationMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", grip_name(self.grip))?;
+        if self.twist != 0 {
+            write!(f, ".{}", self.twist)?;
+        }
+        if self.inverse {
+            write!(f, "'")?;
+        }
+        if self.repeat != 1 {
+            write!(f, "{}", self.repeat)?;
+        }
+        Ok(())
+    }
+}
+
+/// Which token of a move sequence failed to parse, and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotationError {
+    /// Index of the offending token, counted from 0
+    pub token: usize,
+    pub reason: &'static str,
+}
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} in move {}", self.reason, self.token + 1)
+    }
+}
+
+/// Parse whitespace-separated moves. Each is a grip name, an optional `.` and twist index,
+/// an optional `'` for the inverse and an optional repeat count.
+pub fn parse_notation(string: &str) -> Result<Vec<NotationMove>, NotationError> {
+    (string.split_whitespace().enumerate())
+        .map(|(token, s)| {
+            let err = |reason| NotationError { token, reason };
+            let name_end = s.find(|c: char| !c.is_ascii_uppercase()).unwrap_or(s.len());
+            let grip = parse_grip_name(&s[..name_end]).ok_or(err("expected a grip name"))?;
+            let mut rest = &s[name_end..];
+
+            let mut twist = 0;
+            if let Some(r) = rest.strip_prefix('.') {
+                let digits_end = r.find(|c: char| !c.is_ascii_digit()).unwrap_or(r.len());
+                twist = r[..digits_end]
+                    .parse()
+                    .map_err(|_| err("expected a twist index"))?;
+                rest = &r[digits_end..];
+            }
+            let inverse = match rest.strip_prefix('\'') {
+                Some(r) => {
+                    rest = r;
+                    true
+                }
+                None => false,
+            };
+            let repeat = match rest {
+                "" => 1,
+                _ => rest.parse().map_err(|_| err("expected a repeat count"))?,
+            };
+            if repeat == 0 {
+                return Err(err("repeat count must be positive"));
+            }
+            if repeat > MAX_REPEAT {
+                return Err(err("repeat count too large"));
+            }
+            Ok(NotationMove {
+                grip,
+                twist,
+                inverse,
+                repeat,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grip_names() {
+        for (n, name) in [(0, "A"), (25, "Z"), (26, "AA"), (27, "AB"), (702, "AAA")] {
+            assert_eq!(grip_name(Point(n)), name);
+            assert_eq!(parse_grip_name(name), Some(Point(n)));
+        }
+        assert_eq!(parse_grip_name(""), None);
+        assert_eq!(parse_grip_name("a"), None);
+    }
+
+    #[test]
+    fn parse_moves() {
+        let moves = parse_notation(" B  AC' D.1 C'2 AA.2'10 ").unwrap();
+        let expected = [
+            (1, 0, false, 1),
+            (28, 0, true, 1),
+            (3, 1, false, 1),
+            (2, 0, true, 2),
+            (26, 2, true, 10),
+        ];
+        assert_eq!(moves.len(), expected.len());
+        for (m, (grip, twist, inverse, repeat)) in moves.iter().zip(expected) {
+            assert_eq!(
+                (m.grip, m.twist, m.inverse, m.repeat),
+                (Point(grip), twist, inverse, repeat)
+            );
+        }
+        // Writing the moves back out gives the same tokens
+        let written: Vec<String> = moves.iter().map(|m| m.to_string()).collect();
+        assert_eq!(written.join(" "), "B AC' D.1 C'2 AA.2'10");
+        assert_eq!(parse_notation(&written.join(" ")).unwrap(), moves);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |s| parse_notation(s).unwrap_err();
+        assert_eq!(error("A b").token, 1);
+        assert_eq!(error("A B0").reason, "repeat count must be positive");
+        assert_eq!(error("A999999999").reason, "repeat count too large");
+        assert_eq!(
+            error("A99999999999999999999999").reason,
+            "expected a repeat count"
+        );
+        assert_eq!(error("A.x").reason, "expected a twist index");
+        assert_eq!(error("A''").reason, "expected a repeat count");
+        assert!(parse_notation(&format!("A{MAX_REPEAT}")).is_ok());
+        assert!(parse_notation("").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{config::TilingSettings, puzzle::GripSignature};
+
+/// A named tiling, along with the piece types to cut it into.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub tiling_settings: TilingSettings,
+    /// Left empty to keep the default single piece type
+    #[serde(default)]
+    pub piece_types: Vec<GripSignature>,
+}
+impl Preset {
+    fn new(name: &str, schlafli: &str, relations: &[&str], subgroup: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            tiling_settings: TilingSettings {
+                schlafli: schlafli.to_string(),
+                relations: relations.iter().map(|r| r.to_string()).collect(),
+                subgroup: subgroup.to_string(),
+            },
+            piece_types: vec![],
+        }
+    }
+
+    /// Read a list of presets saved as JSON.
+    pub fn from_json(json: &str) -> Result<Vec<Self>, ()> {
+        serde_json::from_str(json).map_err(|_| ())
+    }
+}
+
+/// The presets shipped with the app, starting with the default tiling.
+pub fn builtin() -> Vec<Preset> {
+    let default = TilingSettings::default();
+    vec![
+        Preset {
+            name: "Default {6,5,3}".to_string(),
+            tiling_settings: default,
+            piece_types: vec![],
+        },
+        // 6 faces, 48 elements
+        Preset::new("Cube", "{4,3}", &[], "0;1"),
+        // 12 faces, 120 elements
+        Preset::new("Dodecahedron", "{5,3}", &[], "0;1"),
+        // 24 heptagons, 336 elements
+        Preset::new("Klein Quartic", "{7,3}", &["0,2,1;8"], "0;1"),
+        // 12 octagons, 192 elements
+        Preset::new("Dyck's Map", "{8,3}", &["0,2,1;6"], "0;1"),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_presets_generate() {
+        // Faces and elements of each preset after the default
+        let sizes = [(6, 48), (12, 120), (24, 336), (12, 192)];
+        let presets = builtin();
+        assert_eq!(presets.len(), sizes.len() + 1);
+        for (i, preset) in presets.iter().enumerate() {
+            let tiling = (preset.tiling_settings.generate())
+                .unwrap_or_else(|e| panic!("{}: {e}", preset.name));
+            let quotient_group = (tiling.get_quotient_group(10_000, None, None))
+                .unwrap_or_else(|_| panic!("{}: quotient doesn't close", preset.name));
+            let tiles = quotient_group.tile_group.point_count();
+            let elements = quotient_group.element_group.point_count();
+            assert!(tiles > 0, "{}", preset.name);
+            if let Some(&expected) = i.checked_sub(1).and_then(|i| sizes.get(i)) {
+                assert_eq!((tiles, elements), expected, "{}", preset.name);
+            }
+        }
+    }
+}
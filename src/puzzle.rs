@@ -1,6 +1,11 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
 use crate::group::{Group, Point, Word};
 
-pub(crate) struct Puzzle {
+#[derive(Clone)]
+pub struct Puzzle {
     pub elem_group: Group,
     pub grip_group: Group,
     // subgroup: Group,
@@ -10,10 +15,9 @@ pub(crate) struct Puzzle {
 }
 impl Puzzle {
     pub fn new_anticore_only(elem_group: Group, grip_group: Group) -> Self {
-        let pieces = vec![Piece {
-            attitude: Point::INIT,
-            grips: GripSignature((0..grip_group.point_count()).map(|q| Point(q)).collect()),
-        }];
+        let pieces = vec![Piece::new(GripSignature(
+            (0..grip_group.point_count()).map(|q| Point(q)).collect(),
+        ))];
         Self {
             elem_group,
             grip_group,
@@ -27,52 +31,119 @@ impl Puzzle {
         grip_group: Group,
         piece_types: Vec<GripSignature>,
     ) -> Result<Self, ()> {
+        let sigs = Self::expand_signatures(&elem_group, &grip_group, &piece_types)?;
+        let pieces = sigs.into_iter().map(Piece::new).collect();
+        Ok(Self {
+            elem_group,
+            grip_group,
+            piece_types,
+            pieces,
+        })
+    }
+
+    /// Every distinct image of the piece types under the element group, sorted by grips so
+    /// that pieces come out in the same order however the groups were enumerated.
+    pub fn expand_signatures(
+        elem_group: &Group,
+        grip_group: &Group,
+        piece_types: &[GripSignature],
+    ) -> Result<Vec<GripSignature>, ()> {
+        let mut seen = HashSet::new();
         let mut sigs = vec![];
-        for sig in &piece_types {
+        for sig in piece_types {
             for word in (0..elem_group.point_count()).map(|i| &elem_group.word_table[i as usize]) {
                 let new_sig = Self::free_transform_signature(&sig, &grip_group, word)?;
-                if !sigs.contains(&new_sig) {
+                if seen.insert(new_sig.clone()) {
                     sigs.push(new_sig);
                 }
             }
         }
-        let pieces = sigs
+        sigs.sort_unstable();
+        Ok(sigs)
+    }
+
+    /// Whether every piece moved by a twist can be followed within the enumerated groups.
+    pub fn move_is_enumerated(&self, grip: &Point, word: &Word) -> bool {
+        self.pieces
             .iter()
-            .map(move |sig| Piece {
-                attitude: Point::INIT,
-                grips: sig.clone(),
+            .filter(|piece| piece.grips.contains(grip))
+            .all(|piece| {
+                self.elem_group.mul_word(&piece.attitude, word).is_some()
+                    && (piece.grips.0.iter()).all(|g| self.grip_group.mul_word(g, word).is_some())
             })
+    }
+
+    /// Whether a twist maps its grip to itself. Otherwise the turned pieces would land on
+    /// stationary ones, which the grip group can't describe.
+    pub fn move_is_aligned(&self, grip: &Point, word: &Word) -> bool {
+        self.grip_group.mul_word(grip, word) == Some(*grip)
+    }
+
+    /// Whether a twist moves every piece on its grip into a place vacated by another, rather
+    /// than tearing a bandaged piece or landing on one that stays put.
+    pub fn can_apply(&self, grip: &Point, word: &Word) -> bool {
+        if !self.move_is_enumerated(grip, word) || !self.move_is_aligned(grip, word) {
+            return false;
+        }
+        let moved: HashSet<&GripSignature> = (self.pieces.iter())
+            .filter(|piece| piece.grips.contains(grip))
+            .map(|piece| &piece.grips)
             .collect();
-        Ok(Self {
-            elem_group,
-            grip_group,
-            piece_types,
-            pieces,
+        // Twists permute signatures, so it's enough that each lands somewhere in the set
+        moved.iter().all(|sig| {
+            self.transform_signature(sig, word)
+                .is_ok_and(|new_sig| moved.contains(&new_sig))
         })
     }
 
     pub fn apply_move(&mut self, grip: &Point, word: &Word) -> Result<(), ()> {
-        for piece in &mut self.pieces {
-            if piece.grips.contains(grip) {
-                piece.attitude = self.elem_group.mul_word(&piece.attitude, &word).ok_or(())?;
-                for g in &mut piece.grips.0 {
-                    *g = self.grip_group.mul_word(g, &word).ok_or(())?
-                }
+        self.apply_move_to_grips(std::slice::from_ref(grip), word)
+    }
+
+    /// Twist every piece on any of several grips at once, as when all the tiles of one colour
+    /// turn together. Each piece moves once, however many of the grips it's on. The twist
+    /// must keep the grips among themselves, or pieces would land on ones that stay put.
+    pub fn apply_move_to_grips(&mut self, grips: &[Point], word: &Word) -> Result<(), ()> {
+        for grip in grips {
+            if !grips.contains(&self.grip_group.mul_word(grip, word).ok_or(())?) {
+                return Err(());
+            }
+        }
+        // Work out every piece's new position before moving any, so that a piece which can't
+        // be followed leaves the puzzle as it was
+        let mut moved = vec![];
+        for (i, piece) in self.pieces.iter().enumerate() {
+            if grips.iter().any(|grip| piece.grips.contains(grip)) {
+                let attitude = self.elem_group.mul_word(&piece.attitude, word).ok_or(())?;
+                let grips = self.transform_signature(&piece.grips, word)?;
+                moved.push((i, attitude, grips));
             }
         }
+        for (i, attitude, grips) in moved {
+            self.pieces[i].attitude = attitude;
+            self.pieces[i].grips = grips;
+        }
         Ok(())
     }
 
+    /// Whether every piece is back where it started and turned the right way, so with each
+    /// sticker on its own grip. A piece reflected in place has its stickers home but an odd
+    /// attitude, so doesn't count either.
+    pub fn is_solved(&self) -> bool {
+        self.pieces
+            .iter()
+            .all(|piece| piece.attitude == Point::INIT)
+    }
+
     pub fn free_transform_signature(
         sig: &GripSignature,
         grip_group: &Group,
         word: &Word,
     ) -> Result<GripSignature, ()> {
-        let mut out = sig.clone();
-        for g in &mut out.0 {
-            *g = grip_group.mul_word(&g, word).ok_or(())?
-        }
-        Ok(out)
+        let grips = (sig.0.iter())
+            .map(|g| grip_group.mul_word(g, word).ok_or(()))
+            .collect::<Result<_, ()>>()?;
+        Ok(GripSignature::new(grips))
     }
 
     pub fn transform_signature(
@@ -89,24 +160,286 @@ impl Puzzle {
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct Piece {
+pub struct Piece {
     /// Group element
     pub attitude: Point,
     /// Set of cosets
     pub grips: GripSignature,
+    /// Grips of the piece when solved, one sticker on each
+    pub home: GripSignature,
 }
+impl Piece {
+    pub fn new(grips: GripSignature) -> Self {
+        Self {
+            attitude: Point::INIT,
+            home: grips.clone(),
+            grips,
+        }
+    }
 
-#[derive(Debug, Clone)]
-pub(crate) struct GripSignature(pub Vec<Point>);
+    /// Grip each sticker is on, in the order of the grips in `home`, found by moving them
+    /// by the piece's attitude. `None` if the groups don't reach that far.
+    pub fn stickers(&self, elem_group: &Group, grip_group: &Group) -> Option<Vec<Point>> {
+        let word = elem_group.word_table.get(self.attitude.0 as usize)?;
+        (self.home.0.iter())
+            .map(|grip| grip_group.mul_word(grip, word))
+            .collect()
+    }
+
+    /// Whether the piece has been reflected an odd number of times, leaving it mirrored.
+    pub fn is_mirrored(&self, elem_group: &Group) -> bool {
+        (elem_group.word_table.get(self.attitude.0 as usize)).is_some_and(|word| word.is_odd())
+    }
+}
+
+/// Grips of a piece, kept sorted so that signatures compare and hash by their contents.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(from = "Vec<Point>")]
+pub struct GripSignature(pub Vec<Point>);
 impl GripSignature {
     pub const CORE: Self = Self(vec![]);
 
+    pub fn new(mut grips: Vec<Point>) -> Self {
+        grips.sort_unstable();
+        grips.dedup();
+        Self(grips)
+    }
+
     pub fn contains(&self, grip: &Point) -> bool {
-        self.0.contains(grip)
+        self.0.binary_search(grip).is_ok()
+    }
+
+    pub fn insert(&mut self, grip: Point) {
+        if let Err(i) = self.0.binary_search(&grip) {
+            self.0.insert(i, grip);
+        }
+    }
+
+    pub fn remove(&mut self, grip: &Point) {
+        if let Ok(i) = self.0.binary_search(grip) {
+            self.0.remove(i);
+        }
     }
 }
-impl PartialEq for GripSignature {
-    fn eq(&self, other: &Self) -> bool {
-        self.0.len() == other.0.len() && self.0.iter().all(|g| other.0.contains(g))
+impl From<Vec<Point>> for GripSignature {
+    fn from(grips: Vec<Point>) -> Self {
+        Self::new(grips)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        group::Generator,
+        todd_coxeter::{get_element_table, get_element_table_bounded, Strategy},
+    };
+
+    fn word(generators: &[u8]) -> Word {
+        Word(generators.iter().map(|&g| Generator(g)).collect())
+    }
+
+    fn cube_rels() -> Vec<Vec<u8>> {
+        vec![[0, 1].repeat(4), [0, 2].repeat(2), [1, 2].repeat(3)]
+    }
+
+    /// The cube, {4,3}, with centres and edges.
+    fn cube() -> Puzzle {
+        let elem_group = get_element_table(3, &cube_rels(), 1000, Strategy::Hlt, None).unwrap();
+        let quotient = elem_group.quotient_by(&[word(&[0]), word(&[1])]);
+        let front = Point::INIT;
+        let neighbor = quotient.tile_group.mul_gen(&front, &Generator(2)).unwrap();
+        let piece_types = vec![
+            GripSignature::new(vec![front]),
+            GripSignature::new(vec![front, neighbor]),
+        ];
+        Puzzle::new(quotient.element_group, quotient.tile_group, piece_types).unwrap()
+    }
+
+    fn positions(puzzle: &Puzzle) -> Vec<(Point, GripSignature)> {
+        (puzzle.pieces.iter())
+            .map(|p| (p.attitude, p.grips.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn batch_move_matches_single_moves() {
+        let mut single = cube();
+        assert_eq!(single.pieces.len(), 6 + 12);
+        let front = Point::INIT;
+        let quarter_turn = word(&[0, 1]);
+        // The face opposite the front, which the same turn fixes
+        let back = (single.grip_group.points())
+            .find(|&g| g != front && single.move_is_aligned(&g, &quarter_turn))
+            .unwrap();
+        let mut batch = single.clone();
+
+        single.apply_move(&front, &quarter_turn).unwrap();
+        single.apply_move(&back, &quarter_turn).unwrap();
+        batch
+            .apply_move_to_grips(&[front, back], &quarter_turn)
+            .unwrap();
+        assert_eq!(positions(&single), positions(&batch));
+        assert!(!batch.is_solved());
+
+        // A turn that doesn't keep the grips together changes nothing
+        let before = positions(&batch);
+        assert!(batch.apply_move_to_grips(&[front], &word(&[2])).is_err());
+        assert_eq!(positions(&batch), before);
+    }
+
+    #[test]
+    fn failed_batch_move_changes_nothing() {
+        let mut puzzle = cube();
+        // Only elements a couple of generators from the identity are known, so the turn can
+        // be followed from the identity but not from a piece already moved away from it
+        puzzle.elem_group = get_element_table_bounded(3, &cube_rels(), 2, 1000);
+        let far = (puzzle.elem_group.mul_word(&Point::INIT, &word(&[2, 1]))).unwrap();
+        let front = Point::INIT;
+        let quarter_turn = word(&[0, 1]);
+        // The last piece to move, so that the ones before it have succeeded
+        let last = (puzzle.pieces.iter())
+            .rposition(|p| p.grips.contains(&front))
+            .unwrap();
+        puzzle.pieces[last].attitude = far;
+
+        let before = positions(&puzzle);
+        assert!(puzzle.apply_move(&front, &quarter_turn).is_err());
+        assert_eq!(positions(&puzzle), before);
+    }
+
+    #[test]
+    fn corners_home_but_rotated_unsolved() {
+        // The tetrahedron, {3,3}, with a piece on each corner
+        let rels = vec![[0, 1].repeat(3), [0, 2].repeat(2), [1, 2].repeat(3)];
+        let elem_group = get_element_table(3, &rels, 1000, Strategy::Hlt, None).unwrap();
+        let quotient = elem_group.quotient_by(&[word(&[0]), word(&[1])]);
+        let faces: Vec<Point> = quotient.tile_group.points().collect();
+        let corner = GripSignature::new(faces[..3].to_vec());
+        let mut puzzle =
+            Puzzle::new(quotient.element_group, quotient.tile_group, vec![corner]).unwrap();
+        assert_eq!(puzzle.pieces.len(), 4);
+        // A third of a turn of each face
+        let turns = [
+            word(&[0, 1]),
+            word(&[0, 1, 2, 1]),
+            word(&[0, 1, 0, 2]),
+            word(&[1, 2]),
+        ];
+        for (face, turn) in faces.iter().zip(&turns) {
+            assert!(puzzle.move_is_aligned(face, turn));
+        }
+        let home = |puzzle: &Puzzle| puzzle.pieces.iter().all(|p| p.grips == p.home);
+
+        // Turning a face cycles its three corners, and three turns bring them back
+        puzzle.apply_move(&faces[0], &turns[0]).unwrap();
+        assert!(!home(&puzzle));
+        puzzle.apply_move(&faces[0], &turns[0]).unwrap();
+        puzzle.apply_move(&faces[0], &turns[0]).unwrap();
+        assert!(puzzle.is_solved());
+
+        // These put every corner back in its place, but three of them turned. The total
+        // turn of the corners is fixed, so one can't be turned alone.
+        for face in [0, 0, 1, 3] {
+            puzzle.apply_move(&faces[face], &turns[face]).unwrap();
+        }
+        assert!(home(&puzzle));
+        let rotated = (puzzle.pieces.iter())
+            .filter(|p| p.stickers(&puzzle.elem_group, &puzzle.grip_group).unwrap() != p.home.0)
+            .count();
+        assert_eq!(rotated, 3);
+        assert!(!puzzle.is_solved());
+    }
+
+    #[test]
+    fn solved_until_scrambled() {
+        let mut puzzle = cube();
+        assert!(puzzle.is_solved());
+        let front = Point::INIT;
+        let quarter_turn = word(&[0, 1]);
+        let side = (puzzle.grip_group.mul_gen(&front, &Generator(2))).unwrap();
+        let side_turn = word(&[2, 0, 1, 2]);
+        assert!(puzzle.move_is_aligned(&side, &side_turn));
+
+        puzzle.apply_move(&front, &quarter_turn).unwrap();
+        puzzle.apply_move(&side, &side_turn).unwrap();
+        assert!(!puzzle.is_solved());
+
+        // Undo each turn with three more of it
+        for _ in 0..3 {
+            puzzle.apply_move(&side, &side_turn).unwrap();
+        }
+        assert!(!puzzle.is_solved());
+        for _ in 0..3 {
+            puzzle.apply_move(&front, &quarter_turn).unwrap();
+        }
+        assert!(puzzle.is_solved());
+    }
+
+    #[test]
+    fn bandaged_piece_blocks_its_turns() {
+        let mut puzzle = cube();
+        let front = Point::INIT;
+        let quarter_turn = word(&[0, 1]);
+        let up = (puzzle.grip_group.mul_gen(&front, &Generator(2))).unwrap();
+        let right = (puzzle.grip_group.mul_word(&up, &quarter_turn)).unwrap();
+        let back = (puzzle.grip_group.points())
+            .find(|&g| g != front && puzzle.move_is_aligned(&g, &quarter_turn))
+            .unwrap();
+        assert!(puzzle.can_apply(&front, &quarter_turn));
+
+        // Fuse the three edges between the front, up and right faces into one block
+        let fused = GripSignature::new(vec![front, up, right]);
+        (puzzle.pieces)
+            .retain(|p| p.grips.0.len() < 2 || !p.grips.0.iter().all(|g| fused.contains(g)));
+        assert_eq!(puzzle.pieces.len(), 6 + 12 - 3);
+        puzzle.pieces.push(Piece::new(fused));
+
+        // Turning the front would swing the block onto pieces that stay put
+        assert!(!puzzle.can_apply(&front, &quarter_turn));
+        // The back doesn't touch it
+        assert!(puzzle.can_apply(&back, &quarter_turn));
+        puzzle.apply_move(&back, &quarter_turn).unwrap();
+        assert!(!puzzle.can_apply(&front, &quarter_turn));
+    }
+
+    #[test]
+    fn signature_dedup_matches_pairwise() {
+        // The Klein quartic, {7,3}, with 24 faces, 84 edges and 56 corners
+        let rels = vec![
+            [0, 1].repeat(7),
+            [0, 2].repeat(2),
+            [1, 2].repeat(3),
+            [0, 2, 1].repeat(8),
+        ];
+        let elem_group = get_element_table(3, &rels, 10_000, Strategy::Hlt, None).unwrap();
+        let quotient = elem_group.quotient_by(&[word(&[0]), word(&[1])]);
+        let (elem_group, grip_group) = (quotient.element_group, quotient.tile_group);
+        let front = Point::INIT;
+        let side = grip_group.mul_gen(&front, &Generator(2)).unwrap();
+        let next_side = grip_group.mul_word(&side, &word(&[0, 1])).unwrap();
+        let piece_types = vec![
+            GripSignature::new(vec![front]),
+            GripSignature::new(vec![front, side]),
+            GripSignature::new(vec![front, side, next_side]),
+        ];
+
+        // Comparing every new signature against all those found so far
+        let mut pairwise: Vec<GripSignature> = vec![];
+        for sig in &piece_types {
+            for w in &elem_group.word_table {
+                let new_sig = Puzzle::free_transform_signature(sig, &grip_group, w).unwrap();
+                if !pairwise.contains(&new_sig) {
+                    pairwise.push(new_sig);
+                }
+            }
+        }
+        pairwise.sort();
+
+        let sigs = Puzzle::expand_signatures(&elem_group, &grip_group, &piece_types).unwrap();
+        assert_eq!(sigs.len(), 24 + 84 + 56);
+        assert_eq!(sigs, pairwise);
+        let puzzle = Puzzle::new(elem_group, grip_group, piece_types).unwrap();
+        assert_eq!(puzzle.pieces.len(), sigs.len());
     }
 }
@@ -3,6 +3,8 @@
 @group(0) @binding(2) var<storage,read> sticker: array<i32>;
 @group(0) @binding(3) var<storage,read> cut_circles: array<vec4<f32>>;
 @group(0) @binding(4) var<storage,read> outlines: array<vec4<f32>>;
+@group(0) @binding(5) var<storage,read> palette: array<vec4<f32>>;
+@group(0) @binding(6) var<storage,read> distance: array<u32>;
 
 
 struct Params {
@@ -16,8 +18,18 @@ struct Params {
     depth: u32,
     flags: u32,
     mirror_count: u32,
+    parity_cols: array<vec4<f32>, 2>,
+    inset: f32,
+    palette_len: u32,
+    highlight: u32,
+    piece_type_count: u32,
+    outline_col: vec4<f32>,
+    klein_disk: vec4<f32>,
 }
 
+/// Whether the region being shaded belongs to the highlighted tile
+var<private> highlighted: bool;
+
 fn reflect(c: vec4<f32>, p: vec4<f32>) -> vec4<f32> {
     let s = dot(c * p, vec4(-1.0, 1.0, 1.0, 1.0));
     let xy = c.x * p.y - c.y * p.x;
@@ -71,7 +83,25 @@ fn vertex(in: VertexInput) -> VertexOutput {
 
 @fragment
 fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
-    var p = up(in.pos.xy);
+    let col = shade(in);
+    if highlighted {
+        return vec4(mix(col.rgb, vec3(1.,1.,1.), 0.4), col.a);
+    }
+    return col;
+}
+
+fn shade(in: VertexOutput) -> vec4<f32> {
+    var xy = in.pos.xy;
+    // Pixels lie in the Klein model, so take them back to the Poincaré disk the mirrors live in
+    if params.klein_disk.z > 0. {
+        let k = (xy - params.klein_disk.xy) / params.klein_disk.z;
+        let k2 = dot(k,k);
+        if k2 >= 1. {
+            return vec4(0.,0.,0.,1.);
+        }
+        xy = params.klein_disk.xy + params.klein_disk.z * k / (1. + sqrt(1. - k2));
+    }
+    var p = up(xy);
     var q = params.point;
 
     var elem = 0;
@@ -90,17 +120,42 @@ fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
             break;
         }
     }
+    if elem != -1 {
+        let tile = get_col(elem);
+        highlighted = tile >= 0 && u32(tile) == params.highlight;
+    }
     for (var j: u32 = 0u; j < params.mirror_count; j++) {
-        if !in_circle(params.mirrors[j],p) {
+        // Tile edges are pulled in by the inset, leaving a gap showing the background
+        var margin = 0.;
+        if params.edges[j] > 0u {
+            margin = params.inset;
+        }
+        if how_in_circle(params.mirrors[j],p) < margin {
+            if (params.flags & 8) > 0 {
+                return vec4(1.,1.,1.,1.);
+            }
             return vec4(0.1,0.1,0.1,1);
         }
     }
 
+    if (params.flags & 8) > 0 {
+        return high_contrast(p, k);
+    }
 
     if (params.flags & 1) > 0 && k == 0 {
         return vec4(0.5,0.5,0.5,1.);
     }
 
+    // Two-colour by the number of reflections taken to get home
+    if (params.flags & 16) > 0 {
+        return params.parity_cols[k % 2];
+    }
+
+    // Colour by word length, to show how the group grows
+    if (params.flags & 32) > 0 && elem != -1 {
+        return turbo(f32(distance[elem]) / 20.,0.,params.col_scale);
+    }
+
     if (params.flags & 2) == 0 || elem == -1 {
         var dist = params.col_scale;
         for (var i = 0u; i < params.mirror_count; i++) {
@@ -113,7 +168,7 @@ fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
 
     for (var o: u32 = 0; o < params.outline_count; o++) {
         if in_circle(outlines[o], p) {
-            return vec4(0.,0.,0.,1.);
+            return params.outline_col;
         }
     }
 
@@ -124,15 +179,40 @@ fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
         }
     }
     elem = get_sticker(elem, mask);
+    // The piece seen here lies beyond the enumerated groups
+    if elem == -1 {
+        return vec4(0.35,0.35,0.35,1.);
+    }
 
     if (params.flags & 4) > 0 {
         elem = mul_elem_gen(elem,params.mirror_count-1);
     }
 
-    return turbo(f32(get_col(elem)) / 50.,0.,params.col_scale);
+    let col = get_col(elem);
+    if params.palette_len > 0u && col >= 0 {
+        return palette[u32(col) % params.palette_len];
+    }
+    return turbo(f32(col) / 50.,0.,params.col_scale);
     // return turbo(f32(elem) / 20.,0.,params.col_scale);
 }
 
+/// Uniform light gray tiles with black outlines, ignoring any colouring
+fn high_contrast(p: vec4<f32>, k: i32) -> vec4<f32> {
+    for (var o: u32 = 0; o < params.outline_count; o++) {
+        if in_circle(outlines[o], p) {
+            return vec4(0.,0.,0.,1.);
+        }
+    }
+    if (params.flags & 1) > 0 && k == 0 {
+        for (var j: u32 = 0u; j < params.mirror_count; j++) {
+            if how_in_circle(params.mirrors[j],p) < 0.01 {
+                return vec4(0.,0.,0.,1.);
+            }
+        }
+    }
+    return vec4(0.85,0.85,0.85,1.);
+}
+
 /// Get the colour of where we started by inverting the element that gets us home
 fn get_col(elem: i32) -> i32 {
     return group[(params.mirror_count + 1) * u32(elem)];
@@ -146,11 +226,17 @@ fn mul_elem_gen(elem: i32, gen: u32) -> i32 {
     return group[(params.mirror_count + 1) * u32(elem) + gen + 1];
 }
 
+/// Look up the piece type owning a cut region, then that piece type's sticker in this element
 fn get_sticker(elem: i32, cut_inclusion: u32) -> i32 {
     if elem == -1 {
         return elem;
     }
-    return sticker[u32(elem) * (1u<<params.cut_circle_count) + cut_inclusion];
+    let piece_type = sticker[cut_inclusion];
+    if piece_type == -1 {
+        return elem;
+    }
+    let offset = 1u<<params.cut_circle_count;
+    return sticker[offset + u32(elem) * params.piece_type_count + u32(piece_type)];
 }
 
 fn turbo(value: f32, min: f32, max: f32) -> vec4<f32> {
@@ -1,70 +1,749 @@
-use std::str::FromStr;
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use cga2d::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    config::{parse_relation, parse_subgroup, Schlafli, TilingSettings},
-    group::{Group, Point},
-    todd_coxeter::{get_coset_table, get_element_table},
+    config::{
+        parse_relation, parse_subgroup, GeometryKind, RelationError, Schlafli, SchlafliEntry,
+        TilingSettings,
+    },
+    geom::{self, MirrorError, Tolerances},
+    group::{Generator, Group, InvalidGroup, Point, Word},
+    todd_coxeter::{get_element_table_bounded, Strategy, TCError, Tables},
 };
 
 #[derive(Debug, Clone)]
-pub(crate) struct Tiling {
+pub struct Tiling {
     pub rank: u8,
     pub schlafli: Schlafli,
+    /// Curvature of the tiled space, deciding how the mirrors are projected
+    pub geometry: GeometryKind,
     pub mirrors: Vec<cga2d::Blade3>,
     pub edges: Vec<bool>,
 
     pub relations: Vec<Vec<u8>>,
-    pub subgroup: Vec<u8>,
+    /// Indices of relations in the settings that repeat an earlier one or one implied by the
+    /// Schläfli symbol, up to rotation and reversal
+    pub redundant_relations: Vec<usize>,
+    /// Words generating the subgroup which fixes a tile
+    pub subgroup: Vec<Vec<u8>>,
+    pub tolerances: Tolerances,
+    /// Settings this tiling was generated from
+    pub settings: TilingSettings,
+    /// Quotient groups already enumerated, by tile limit. Shared between clones, which
+    /// have the same groups.
+    quotient_groups: Arc<Mutex<HashMap<u32, Arc<QuotientGroup>>>>,
 }
 impl Tiling {
-    pub fn from_settings(tiling_settings: &TilingSettings) -> Result<Self, ()> {
-        let schlafli = Schlafli::from_str(&tiling_settings.schlafli)?;
+    pub fn from_settings(tiling_settings: &TilingSettings) -> Result<Self, TilingError> {
+        let schlafli =
+            Schlafli::from_str(&tiling_settings.schlafli).map_err(|_| TilingError::Schlafli)?;
+        let rank = schlafli.rank();
+        let parsed: Vec<Vec<u8>> = (tiling_settings.relations.iter().enumerate())
+            .map(|(index, r)| {
+                parse_relation(r, rank).map_err(|error| TilingError::Relation { index, error })
+            })
+            .collect::<Result<_, _>>()?;
+        let subgroup: Vec<Vec<u8>> =
+            parse_subgroup(&tiling_settings.subgroup).map_err(|_| TilingError::Subgroup)?;
+        Self::build(schlafli, parsed, subgroup, tiling_settings.clone())
+    }
+
+    /// Tiling from a Schläfli symbol, the relations given on top of it in the same order as
+    /// the `settings`, and subgroup words.
+    fn build(
+        schlafli: Schlafli,
+        parsed: Vec<Vec<u8>>,
+        subgroup: Vec<Vec<u8>>,
+        settings: TilingSettings,
+    ) -> Result<Self, TilingError> {
         let rank = schlafli.rank();
         let mut relations = schlafli.get_rels();
-        let mut x: Vec<Vec<u8>> = tiling_settings
-            .relations
-            .iter()
-            .map(|r| parse_relation(r))
-            .collect::<Result<_, ()>>()?;
-        if !x.iter().all(|r| r.iter().all(|&g| g < rank)) {
-            return Err(());
-        };
-        relations.append(&mut x);
-        let subgroup = parse_subgroup(&tiling_settings.subgroup)?
-            .iter()
-            .map(|&x| if x <= schlafli.rank() { Ok(x) } else { Err(()) })
-            .collect::<Result<_, ()>>()?;
+        // Repeats are only dropped when identical, but any that add nothing are flagged
+        let mut redundant_relations = vec![];
+        for (index, relation) in parsed.into_iter().enumerate() {
+            if relations.iter().any(|r| same_relation(r, &relation)) {
+                redundant_relations.push(index);
+            }
+            if !relations.contains(&relation) {
+                relations.push(relation);
+            }
+        }
 
+        // Mirrors in the subgroup don't bound a tile
         let mut edges = vec![true; 4];
-        for &i in &subgroup {
-            edges[i as usize] = false;
+        for word in &subgroup {
+            if let [i] = word[..] {
+                if let Some(edge) = edges.get_mut(i as usize) {
+                    *edge = false;
+                }
+            }
         }
 
-        let mirrors = schlafli.get_mirrors()?;
+        let (geometry, mirrors) = schlafli.get_mirrors().map_err(TilingError::Mirrors)?;
 
-        Ok(Self {
+        let tiling = Self {
             rank,
             schlafli,
+            geometry,
             mirrors,
             edges,
             relations,
+            redundant_relations,
             subgroup,
-        })
+            tolerances: Tolerances::default(),
+            settings,
+            quotient_groups: Arc::new(Mutex::new(HashMap::new())),
+        };
+        tiling
+            .validate_generators()
+            .map_err(TilingError::Generators)?;
+        Ok(tiling)
+    }
+
+    /// The dual tiling, with the Schläfli symbol and so the generators reversed.
+    pub fn dual(&self) -> Result<Self, TilingError> {
+        let permutation: Vec<u8> = (0..self.rank).rev().collect();
+        self.permute_generators(&permutation)
+    }
+
+    /// The same tiling with generator `g` renamed `permutation[g]`. Generators past the end
+    /// of `permutation` are dropped, leaving a tiling of lower rank, and any relation or
+    /// subgroup word still using one is reported. Generators that don't commute must stay
+    /// next to each other, so that the result still has a Schläfli symbol.
+    pub fn permute_generators(&self, permutation: &[u8]) -> Result<Self, TilingError> {
+        let n = permutation.len();
+        let mut sorted = permutation.to_vec();
+        sorted.sort();
+        if n < 2 || n > self.rank as usize || !sorted.iter().copied().eq(0..n as u8) {
+            return Err(TilingError::Permutation);
+        }
+        // Entry between two generators, 2 if they aren't neighbours
+        let entry = |a: usize, b: usize| match a.abs_diff(b) {
+            1 => self.schlafli.0[a.min(b)],
+            _ => Some(SchlafliEntry::new(2)),
+        };
+        let commutes = |e: Option<SchlafliEntry>| e == Some(SchlafliEntry::new(2));
+        for i in 0..n - 1 {
+            let adjacent = permutation[i].abs_diff(permutation[i + 1]) == 1;
+            if !adjacent && !commutes(self.schlafli.0[i]) {
+                return Err(TilingError::Permutation);
+            }
+        }
+        let mut inverse = vec![0; n];
+        for (g, &image) in permutation.iter().enumerate() {
+            inverse[image as usize] = g;
+        }
+        let schlafli = Schlafli(
+            (0..n - 1)
+                .map(|i| entry(inverse[i], inverse[i + 1]))
+                .collect(),
+        );
+
+        // Dropped generators land past the new rank, for `validate_generators` to report
+        let rename = |word: &Vec<u8>| -> Vec<u8> {
+            (word.iter())
+                .map(|&g| permutation.get(g as usize).copied().unwrap_or(u8::MAX))
+                .collect()
+        };
+        let parsed: Vec<Vec<u8>> = (self.settings.relations.iter())
+            .map(|r| parse_relation(r, self.rank).map(|r| rename(&r)))
+            .collect::<Result<_, _>>()
+            .expect("The tiling was built from its settings");
+        let subgroup: Vec<Vec<u8>> = self.subgroup.iter().map(rename).collect();
+
+        let write = |word: &[u8]| -> String {
+            let generators: Vec<String> = word.iter().map(|g| g.to_string()).collect();
+            generators.join(",")
+        };
+        let settings = TilingSettings {
+            schlafli: schlafli.to_string(),
+            relations: (parsed.iter()).map(|r| format!("{};1", write(r))).collect(),
+            subgroup: (subgroup.iter())
+                .map(|w| write(w))
+                .collect::<Vec<_>>()
+                .join(";"),
+        };
+        Self::build(schlafli, parsed, subgroup, settings)
+    }
+
+    /// Check that every relation and subgroup generator refers to a generator of this tiling.
+    /// Should be rerun after anything that remaps generators.
+    pub fn validate_generators(&self) -> Result<(), InvalidGenerators> {
+        let invalid = InvalidGenerators {
+            relations: (self.relations.iter().enumerate())
+                .filter(|(_, r)| r.iter().any(|&g| g >= self.rank))
+                .map(|(i, _)| i)
+                .collect(),
+            subgroup: (self.subgroup.iter().enumerate())
+                .filter(|(_, w)| w.iter().any(|&g| g >= self.rank))
+                .map(|(i, _)| i)
+                .collect(),
+        };
+        if invalid.relations.is_empty() && invalid.subgroup.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid)
+        }
+    }
+
+    /// Circle crossing the first three mirrors at right angles, which every motion of the
+    /// tiling preserves. Rank 2 tilings only have two mirrors, so the circle through (1, 0)
+    /// is taken instead.
+    pub fn orthogonal_circle(&self) -> cga2d::Blade3 {
+        let ms = &self.mirrors;
+        match ms.get(2) {
+            Some(&m) => !ms[0] ^ !ms[1] ^ !m,
+            None => !ms[0] ^ !ms[1] ^ cga2d::point(1., 0.),
+        }
+    }
+
+    /// Boundary of the Poincaré disk a hyperbolic tiling is drawn in. `None` for other
+    /// geometries, and for rank 4, whose picture is a slice of 3D space.
+    pub fn hyperbolic_boundary(&self) -> Option<cga2d::Blade3> {
+        (self.geometry == GeometryKind::Hyperbolic && self.rank == 3)
+            .then(|| self.orthogonal_circle())
+    }
+
+    /// Distance between two points of a hyperbolic tiling, in the plane of curvature -1.
+    /// `None` where `hyperbolic_boundary` is.
+    pub fn hyperbolic_distance(&self, a: cga2d::Blade1, b: cga2d::Blade1) -> Option<f64> {
+        geom::hyperbolic_distance(a, b, self.hyperbolic_boundary()?, &self.tolerances)
+    }
+
+    /// Area of the fundamental region in a space of curvature ±1, from its angle excess or
+    /// defect by Gauss–Bonnet. `None` for Euclidean tilings, which have no natural scale, and
+    /// for rank 4, whose region is a tetrahedron rather than a polygon.
+    pub fn fundamental_area(&self) -> Option<f64> {
+        use std::f64::consts::{FRAC_PI_2, PI};
+
+        let angles: Vec<f64> = (self.schlafli.0.iter())
+            .map(|e| e.map_or(0., |e| e.angle()))
+            .collect();
+        let excess = match self.rank {
+            // A lune between two great circles
+            2 => 2. * angles[0],
+            3 => angles[0] + angles[1] + FRAC_PI_2 - PI,
+            _ => return None,
+        };
+        match self.geometry {
+            GeometryKind::Spherical => Some(excess),
+            GeometryKind::Euclidean => None,
+            GeometryKind::Hyperbolic => Some(-excess),
+        }
     }
 
-    pub fn get_quotient_group(&self, tile_limit: u32) -> Result<QuotientGroup, ()> {
-        let rels = &self.relations;
-        let element_group = get_element_table(self.rank as usize, &rels, tile_limit);
-        let tile_group = get_coset_table(self.rank as usize, &rels, &self.subgroup, tile_limit);
+    /// A point inside the fundamental region, roughly central to it.
+    pub fn fundamental_center(&self) -> cga2d::Blade1 {
+        let ms = &self.mirrors;
+        let inside = |p: cga2d::Blade1| ms.iter().all(|&m| self.tolerances.is_inside(m, p));
+        let vertices: Vec<(f64, f64)> = (0..ms.len())
+            .flat_map(|i| (i + 1..ms.len()).map(move |j| (i, j)))
+            .filter_map(|(i, j)| (ms[i] & ms[j]).unpack_point_pair())
+            .flatten()
+            .filter(|&p| inside(p))
+            .map(|p| p.unpack_point())
+            .filter(|(x, y)| x.is_finite() && y.is_finite())
+            .collect();
+        if vertices.is_empty() {
+            return cga2d::NO;
+        }
+        let n = vertices.len() as f64;
+        let (x, y) = vertices
+            .iter()
+            .fold((0., 0.), |(x, y), (vx, vy)| (x + vx / n, y + vy / n));
+
+        // The average of the vertices may fall outside a curved region, so fold it back in
+        let mut center = cga2d::point(x, y);
+        for _ in 0..100 {
+            let Some(&mirror) = ms.iter().find(|&&m| !(m ^ center) < 0.) else {
+                break;
+            };
+            center = mirror.sandwich(center);
+        }
+        center
+    }
+
+    /// Vertices of the polygon described by a Schläfli entry, found by rotating a vertex of
+    /// the fundamental region about the meeting point of the entry's two mirrors. Star
+    /// entries give their vertices in the order they're joined.
+    /// `None` for infinite entries.
+    pub fn schlafli_polygon(&self, entry: usize) -> Option<Vec<cga2d::Blade1>> {
+        let p = (*self.schlafli.0.get(entry)?)?.p;
+        let ms = &self.mirrors;
+        // Start at a corner on the second mirror, away from the first
+        let other = if entry + 2 < ms.len() {
+            entry + 2
+        } else {
+            entry.checked_sub(1)?
+        };
+        let start = (ms[entry + 1] & ms[other])
+            .unpack_point_pair()?
+            .into_iter()
+            .find(|&v| ms.iter().all(|&m| self.tolerances.is_inside(m, v)))?;
+
+        let rotation = ms[entry + 1] * ms[entry];
+        Some(
+            std::iter::successors(Some(start), |&v| Some(rotation.sandwich(v)))
+                .take(p)
+                .collect(),
+        )
+    }
 
+    /// Points along the part of an edge inside the fundamental region, from a vertex to the
+    /// middle of the edge. With `dual`, the mirrors are taken in reverse order, giving an
+    /// edge of the dual tiling running from a cell center instead. Only rank 3 tilings have
+    /// their edges in the plane.
+    pub fn edge_path(&self, dual: bool) -> Option<Vec<cga2d::Blade1>> {
+        const SAMPLE_COUNT: usize = 16;
+
+        if self.rank != 3 {
+            return None;
+        }
+        let mut ms = self.mirrors.clone();
+        if dual {
+            ms.reverse();
+        }
+        let inside = |p: cga2d::Blade1| ms.iter().all(|&m| self.tolerances.is_inside(m, p));
+        let corner = |other: cga2d::Blade3| {
+            let v = (ms[2] & other)
+                .unpack_point_pair()?
+                .into_iter()
+                .find(|&v| inside(v))?;
+            let (x, y) = v.unpack_point();
+            (x.is_finite() && y.is_finite()).then_some((x, y))
+        };
+        let (x0, y0) = corner(ms[1])?;
+        let (x1, y1) = corner(ms[0])?;
+
+        let samples = (0..=SAMPLE_COUNT).map(|i| i as f64 / SAMPLE_COUNT as f64);
+        match ms[2].unpack(self.tolerances.line) {
+            cga2d::LineOrCircle::Line { .. } => Some(
+                samples
+                    .map(|t| cga2d::point(x0 + t * (x1 - x0), y0 + t * (y1 - y0)))
+                    .collect(),
+            ),
+            cga2d::LineOrCircle::Circle { cx, cy, r } => {
+                let on_circle = |a: f64| cga2d::point(cx + r * a.cos(), cy + r * a.sin());
+                let start = (y0 - cy).atan2(x0 - cx);
+                // Go around whichever way stays inside the region
+                let mut sweep =
+                    ((y1 - cy).atan2(x1 - cx) - start).rem_euclid(std::f64::consts::TAU);
+                if !inside(on_circle(start + sweep / 2.)) {
+                    sweep -= std::f64::consts::TAU;
+                }
+                Some(samples.map(|t| on_circle(start + t * sweep)).collect())
+            }
+        }
+    }
+
+    /// Crescents hugging each of the given edges of the fundamental region, which the shader
+    /// fills in to outline the tiles. The angles are how far each is turned away from its
+    /// mirror, `vertex_angle` for the fourth mirror of a rank 4 tiling, which bounds vertex
+    /// figures rather than cells, and `cell_angle` for the rest.
+    pub fn outlines(
+        &self,
+        edges: &[bool],
+        cell_angle: f64,
+        vertex_angle: f64,
+    ) -> Vec<cga2d::Blade3> {
+        let ms = &self.mirrors;
+        if ms.len() < 3 {
+            return vec![];
+        }
+        let center = self.fundamental_center();
+        let (cx, cy) = center.unpack_point();
+        let distance = |p: &cga2d::Blade1| {
+            let (x, y) = p.unpack_point();
+            (x - cx).hypot(y - cy)
+        };
+        (0..ms.len())
+            .filter(|&i| edges.get(i) == Some(&true))
+            .filter_map(|i| {
+                // The circle orthogonal to this mirror and two neighbours meets it at the
+                // ends of the edge, and the neighbours meet at the opposite corner
+                let start = i.saturating_sub(2).min(ms.len() - 3);
+                let [a, b] = match (start..start + 3).filter(|&j| j != i).collect::<Vec<_>>()[..] {
+                    [j, k] => [ms[j], ms[k]],
+                    _ => return None,
+                };
+                let orthogonal = !a ^ !b ^ !ms[i];
+                if orthogonal.mag2() <= 0. {
+                    return None;
+                }
+                let corner = ((a & b).unpack_point_pair()?.into_iter())
+                    .filter(|p| distance(p).is_finite())
+                    .min_by(|p, q| distance(p).total_cmp(&distance(q)))?;
+                let bulge = (orthogonal & ms[i]) ^ corner;
+                // Turn toward whichever side puts the crescent along the edge in the region
+                let midpoint = self.edge_midpoint(center, i);
+                let angle = if i == 3 { vertex_angle } else { cell_angle };
+                [bulge, -bulge]
+                    .into_iter()
+                    .map(|b| cga2d::slerp(-ms[i], b, angle))
+                    .find(|&outline| !(outline ^ midpoint) > 0.)
+            })
+            .collect()
+    }
+
+    /// Point where the geodesic from `center` to its reflection in a mirror crosses that
+    /// mirror, for drawing the edge between adjacent regions.
+    pub fn edge_midpoint(&self, center: cga2d::Blade1, generator: usize) -> cga2d::Blade1 {
+        let mirror = self.mirrors[generator];
+        let image = mirror.sandwich(center);
+        let (x0, y0) = center.unpack_point();
+        let (x1, y1) = image.unpack_point();
+        let (mx, my) = ((x0 + x1) / 2., (y0 + y1) / 2.);
+        if self.geometry == GeometryKind::Euclidean {
+            return cga2d::point(mx, my);
+        }
+        // The geodesic is the circle through both points orthogonal to the invariant circle
+        let geodesic = center ^ image ^ self.orthogonal_circle().sandwich(center);
+        (geodesic & mirror)
+            .unpack_point_pair()
+            .and_then(|pair| {
+                pair.into_iter()
+                    .map(|p| (p, p.unpack_point()))
+                    .filter(|(_, (x, y))| x.is_finite() && y.is_finite())
+                    .min_by(|(_, (xa, ya)), (_, (xb, yb))| {
+                        let da = (xa - mx).powi(2) + (ya - my).powi(2);
+                        let db = (xb - mx).powi(2) + (yb - my).powi(2);
+                        da.total_cmp(&db)
+                    })
+                    .map(|(p, _)| p)
+            })
+            .unwrap_or(cga2d::point(mx, my))
+    }
+
+    /// Word of reflections folding `point` back into the fundamental region, and whether it
+    /// leaves the point mirrored. Gives up after `depth` passes over the mirrors, leaving the
+    /// word short.
+    pub fn locate(&self, mut point: cga2d::Blade1, depth: u32) -> (Word, bool) {
+        let mut word = Word(vec![]);
+        for _ in 0..depth {
+            let mut done = true;
+            for (i, &mirror) in self.mirrors.iter().enumerate() {
+                if !(mirror ^ point) < 0. {
+                    point = mirror.sandwich(point);
+                    done = false;
+                    word = word * Generator(i as u8);
+                }
+            }
+            if done {
+                break;
+            }
+        }
+        let mirrored = word.is_odd();
+        (word, mirrored)
+    }
+
+    /// Tile of `tile_group` containing `point`, found by folding it into the fundamental region.
+    pub fn tile_at(&self, tile_group: &Group, point: cga2d::Blade1, depth: u32) -> Option<Point> {
+        let (word, _) = self.locate(point, depth);
+        tile_group.mul_word(&Point::INIT, &word.inverse(tile_group))
+    }
+
+    /// Image of the fundamental center in the region reached by the word from the origin,
+    /// such that multiplying the word by a generator gives an adjacent region.
+    pub fn chamber_point(&self, center: cga2d::Blade1, word: &Word) -> cga2d::Blade1 {
+        word.0
+            .iter()
+            .rev()
+            .fold(center, |c, g| self.mirrors[g.0 as usize].sandwich(c))
+    }
+
+    /// Identifies the presentation of this tiling's groups.
+    pub fn presentation_key(&self) -> String {
+        format!("{};{:?};{:?}", self.rank, self.relations, self.subgroup)
+    }
+
+    /// Identifies the quotient group enumerated for this tiling with a given tile limit.
+    /// The version prefix changes whenever the stored group layout does.
+    pub fn cache_key(&self, tile_limit: u32) -> String {
+        format!("v2;{};{}", self.presentation_key(), tile_limit)
+    }
+
+    /// Like `get_quotient_group`, but reuses a copy saved to disk by a previous run if
+    /// there is one for the same parameters.
+    pub fn get_quotient_group_cached(
+        &self,
+        tile_limit: u32,
+        cancel: Option<&AtomicBool>,
+        tables: Option<&mut QuotientTables>,
+    ) -> Result<Arc<QuotientGroup>, TCError> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if let Some(quotient_group) = self.quotient_groups.lock().unwrap().get(&tile_limit) {
+                return Ok(quotient_group.clone());
+            }
+
+            use std::hash::{DefaultHasher, Hash, Hasher};
+
+            let key = self.cache_key(tile_limit);
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            let dir = std::env::temp_dir().join("discrete");
+            let path = dir.join(format!("{:016x}.bin", hasher.finish()));
+
+            // The key is stored first, so a hash collision or stale file is never mistaken
+            // for the right group
+            if let Ok(bytes) = std::fs::read(&path) {
+                let mut reader = bytes.as_slice();
+                if bincode::deserialize_from::<_, String>(&mut reader).ok() == Some(key.clone()) {
+                    // Groups that don't validate are enumerated again, replacing the file
+                    if let Ok(quotient_group) = QuotientGroup::from_bincode(reader) {
+                        let quotient_group = Arc::new(quotient_group);
+                        (self.quotient_groups.lock().unwrap())
+                            .insert(tile_limit, quotient_group.clone());
+                        return Ok(quotient_group);
+                    }
+                }
+            }
+
+            let quotient_group = self.get_quotient_group(tile_limit, cancel, tables)?;
+            if let (Ok(mut bytes), Ok(group_bytes)) =
+                (bincode::serialize(&key), quotient_group.to_bincode())
+            {
+                bytes.extend(group_bytes);
+                let _ = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&path, bytes));
+            }
+            Ok(quotient_group)
+        }
+        #[cfg(target_arch = "wasm32")]
+        self.get_quotient_group(tile_limit, cancel, tables)
+    }
+
+    /// Enumerate the element and tile groups, giving up early once `cancel` is set.
+    /// Extends `tables` from a previous call if given, rather than starting again.
+    /// Groups are kept, so asking again with the same limit is free.
+    pub fn get_quotient_group(
+        &self,
+        tile_limit: u32,
+        cancel: Option<&AtomicBool>,
+        tables: Option<&mut QuotientTables>,
+    ) -> Result<Arc<QuotientGroup>, TCError> {
+        if let Some(quotient_group) = self.quotient_groups.lock().unwrap().get(&tile_limit) {
+            return Ok(quotient_group.clone());
+        }
+        let mut fresh_tables;
+        let tables = match tables {
+            Some(tables) => tables,
+            None => {
+                fresh_tables = QuotientTables::new(self);
+                &mut fresh_tables
+            }
+        };
+        let mut keep_going = |_| !cancel.is_some_and(|c| c.load(Ordering::Relaxed));
+        tables
+            .element
+            .continue_to(tile_limit, Some(&mut keep_going))?;
+        tables.tile.continue_to(tile_limit, Some(&mut keep_going))?;
+        let quotient_group = Arc::new(QuotientGroup::new(
+            tables.element.coset_group(),
+            tables.tile.coset_group(),
+        ));
+        (self.quotient_groups.lock().unwrap()).insert(tile_limit, quotient_group.clone());
+        Ok(quotient_group)
+    }
+
+    /// Number of group elements at each word length up to `depth`, from a partial
+    /// enumeration that stops once `limit` elements are defined. Words near `depth` may not
+    /// have been merged yet, so the last few counts can run high.
+    pub fn growth(&self, depth: usize, limit: u32) -> Vec<usize> {
+        let group = get_element_table_bounded(self.rank as usize, &self.relations, depth, limit);
+        let mut counts = vec![0; depth + 1];
+        for d in group.distances().into_iter().flatten() {
+            if let Some(count) = counts.get_mut(d as usize) {
+                *count += 1;
+            }
+        }
+        counts
+    }
+
+    /// Rough order of the group: the number of elements within `depth` reflections of the
+    /// identity. Exact for a finite group once `depth` is well past its diameter, where the
+    /// growth drops to zero.
+    pub fn estimate_order(&self, depth: usize, limit: u32) -> usize {
+        self.growth(depth, limit).iter().sum()
+    }
+
+    /// Partial groups of everything within `max_word_len` reflections of the fundamental
+    /// region, for previewing a tiling whose groups don't fit in the tile limit.
+    pub fn get_quotient_group_bounded(
+        &self,
+        max_word_len: usize,
+        tile_limit: u32,
+    ) -> QuotientGroup {
+        let mut tables = QuotientTables::new(self);
+        tables.element.continue_within(max_word_len, tile_limit);
+        tables.tile.continue_within(max_word_len, tile_limit);
+        QuotientGroup::new(tables.element.coset_group(), tables.tile.coset_group())
+    }
+}
+
+/// Enumerator state for the groups of a tiling, kept so a higher tile limit can carry on
+/// from where the last enumeration stopped.
+pub struct QuotientTables {
+    presentation_key: String,
+    pub element: Tables,
+    pub tile: Tables,
+}
+impl QuotientTables {
+    pub fn new(tiling: &Tiling) -> Self {
+        let rank = tiling.rank as usize;
+        let strategy = Strategy::default();
+        Self {
+            presentation_key: tiling.presentation_key(),
+            element: Tables::new(rank, &tiling.relations, &vec![], strategy),
+            tile: Tables::new(rank, &tiling.relations, &tiling.subgroup, strategy),
+        }
+    }
+
+    /// Whether extending these tables to `tile_limit` gives the same groups as enumerating
+    /// `tiling` from scratch.
+    pub fn can_extend(&self, tiling: &Tiling, tile_limit: u32) -> bool {
+        self.presentation_key == tiling.presentation_key()
+            && self.element.iterations() <= tile_limit
+            && self.tile.iterations() <= tile_limit
+    }
+}
+
+/// Whether two relations are the same up to rotation and reversal. Those give conjugates
+/// and inverses, since every generator is its own inverse, so impose nothing new.
+fn same_relation(a: &[u8], b: &[u8]) -> bool {
+    let reversed: Vec<u8> = b.iter().rev().copied().collect();
+    a.len() == b.len()
+        && (0..a.len()).any(|k| {
+            let rotated = a[k..].iter().chain(&a[..k]);
+            rotated.clone().eq(b) || rotated.eq(&reversed)
+        })
+}
+
+/// Entries of a tiling which reference generators beyond its rank.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InvalidGenerators {
+    /// Indices into `Tiling::relations`
+    pub relations: Vec<usize>,
+    /// Indices into `Tiling::subgroup`
+    pub subgroup: Vec<usize>,
+}
+
+/// Why tiling settings failed to generate a tiling.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TilingError {
+    /// The Schläfli symbol doesn't parse
+    Schlafli,
+    /// The Schläfli symbol has no mirror arrangement
+    Mirrors(MirrorError),
+    /// The relation at `index` in the settings is invalid
+    Relation { index: usize, error: RelationError },
+    /// The subgroup doesn't parse
+    Subgroup,
+    /// Relations or subgroup words use a generator the tiling doesn't have
+    Generators(InvalidGenerators),
+    /// Not a permutation of the generators, or one which separates generators that don't
+    /// commute
+    Permutation,
+}
+impl fmt::Display for TilingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Schlafli => write!(f, "invalid Schläfli symbol"),
+            Self::Mirrors(error) => write!(f, "{error}"),
+            Self::Relation { index, error } => write!(f, "relation {index}: {error}"),
+            Self::Subgroup => write!(f, "invalid subgroup"),
+            Self::Generators(InvalidGenerators {
+                relations,
+                subgroup,
+            }) => write!(
+                f,
+                "relations {relations:?} and subgroup words {subgroup:?} use generators the \
+                 tiling doesn't have"
+            ),
+            Self::Permutation => write!(f, "generators can't be permuted that way"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "QuotientGroupData")]
+pub struct QuotientGroup {
+    pub element_group: Group,
+    pub tile_group: Group,
+    /// Map from a group element E to C0 * E' in the coset group
+    pub inverse_map: Vec<Option<Point>>,
+}
+impl QuotientGroup {
+    pub fn new(element_group: Group, tile_group: Group) -> Self {
         // Inverse Element -> Coset
         let inverse_map: Vec<Option<Point>> = element_group
             .word_table
             .iter()
-            .map(|word| tile_group.mul_word(&Point::INIT, &word.inverse()))
+            .map(|word| tile_group.mul_word(&Point::INIT, &word.inverse(&element_group)))
             .collect();
 
-        Ok(QuotientGroup {
+        Self {
+            element_group,
+            tile_group,
+            inverse_map,
+        }
+    }
+
+    /// Tiles across each mirror of a tile, in generator order.
+    pub fn neighbors(&self, tile: Point) -> impl Iterator<Item = (Generator, Option<Point>)> + '_ {
+        self.tile_group.neighbors(tile)
+    }
+
+    /// Tile an element takes the initial tile to, as looked up in `inverse_map`.
+    pub fn element_to_coset(&self, element: Point) -> Option<Point> {
+        *self.inverse_map.get(element.0 as usize)?
+    }
+
+    /// Compact binary encoding of the groups.
+    pub fn to_bincode(&self) -> Result<Vec<u8>, ()> {
+        bincode::serialize(self).map_err(|_| ())
+    }
+
+    pub fn from_bincode(bytes: &[u8]) -> Result<Self, ()> {
+        bincode::deserialize(bytes).map_err(|_| ())
+    }
+}
+
+/// Fields of a `QuotientGroup` as stored. Each group is checked as it's read, leaving
+/// whether they fit together.
+#[derive(Deserialize)]
+struct QuotientGroupData {
+    element_group: Group,
+    tile_group: Group,
+    inverse_map: Vec<Option<Point>>,
+}
+impl TryFrom<QuotientGroupData> for QuotientGroup {
+    type Error = InvalidGroup;
+
+    fn try_from(data: QuotientGroupData) -> Result<Self, Self::Error> {
+        let QuotientGroupData {
+            element_group,
+            tile_group,
+            inverse_map,
+        } = data;
+        if element_group.generator_count() != tile_group.generator_count()
+            || inverse_map.len() != element_group.point_count() as usize
+        {
+            return Err(InvalidGroup::Size);
+        }
+        if !(inverse_map.iter().flatten()).all(|p| p.0 < tile_group.point_count()) {
+            return Err(InvalidGroup::OutOfRange);
+        }
+        Ok(Self {
             element_group,
             tile_group,
             inverse_map,
@@ -72,10 +751,343 @@ impl Tiling {
     }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct QuotientGroup {
-    pub element_group: Group,
-    pub tile_group: Group,
-    /// Map from a group element E to C0 * E' in the coset group
-    pub inverse_map: Vec<Option<Point>>,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todd_coxeter::get_element_table;
+
+    /// Groups of the cube, {4,3}, with the faces as tiles.
+    fn cube() -> QuotientGroup {
+        let rels = vec![[0, 1].repeat(4), [0, 2].repeat(2), [1, 2].repeat(3)];
+        let element_group = get_element_table(3, &rels, 1000, Strategy::Hlt, None).unwrap();
+        element_group.quotient_by(&[Word(vec![Generator(0)]), Word(vec![Generator(1)])])
+    }
+
+    #[test]
+    fn quotient_group_bincode_round_trip() {
+        let quotient_group = cube();
+        assert_eq!(quotient_group.tile_group.point_count(), 6);
+        let loaded = QuotientGroup::from_bincode(&quotient_group.to_bincode().unwrap()).unwrap();
+        for (group, loaded) in [
+            (&quotient_group.element_group, &loaded.element_group),
+            (&quotient_group.tile_group, &loaded.tile_group),
+        ] {
+            assert_eq!(group.point_count(), loaded.point_count());
+            for p in group.points() {
+                for g in (0..3).map(Generator) {
+                    assert_eq!(group.mul_gen(&p, &g), loaded.mul_gen(&p, &g));
+                }
+            }
+        }
+        assert_eq!(quotient_group.inverse_map, loaded.inverse_map);
+    }
+
+    #[test]
+    fn malformed_quotient_group_rejected() {
+        let QuotientGroup {
+            element_group,
+            tile_group,
+            inverse_map,
+        } = cube();
+        let load = |inverse_map: &[Option<Point>]| {
+            let bytes = bincode::serialize(&(&element_group, &tile_group, inverse_map));
+            QuotientGroup::from_bincode(&bytes.unwrap())
+        };
+        assert!(load(&inverse_map).is_ok());
+        assert!(load(&inverse_map[1..]).is_err());
+        let mut out_of_range = inverse_map.clone();
+        out_of_range[0] = Some(Point(6));
+        assert!(load(&out_of_range).is_err());
+    }
+
+    #[test]
+    fn neighbors_match_mul_gen() {
+        let quotient_group = cube();
+        for tile in quotient_group.tile_group.points() {
+            let neighbors: Vec<_> = quotient_group.neighbors(tile).collect();
+            assert_eq!(neighbors.len(), 3);
+            for (g, neighbor) in neighbors {
+                assert_eq!(neighbor, quotient_group.tile_group.mul_gen(&tile, &g));
+            }
+        }
+        for element in quotient_group.element_group.points() {
+            assert_eq!(
+                quotient_group.element_to_coset(element),
+                quotient_group.inverse_map[element.0 as usize]
+            );
+        }
+        assert_eq!(quotient_group.element_to_coset(Point(48)), None);
+    }
+
+    #[test]
+    fn quotient_group_kept_per_limit() {
+        let settings = TilingSettings {
+            schlafli: "{4,3}".to_string(),
+            relations: vec![],
+            subgroup: "0;1".to_string(),
+        };
+        let tiling = settings.generate().unwrap();
+        let first = tiling.get_quotient_group(1000, None, None).unwrap();
+        let again = tiling.get_quotient_group(1000, None, None).unwrap();
+        assert!(Arc::ptr_eq(&first, &again));
+        let other_limit = tiling.get_quotient_group(2000, None, None).unwrap();
+        assert!(!Arc::ptr_eq(&first, &other_limit));
+        // A regenerated tiling starts without any
+        let regenerated = settings.generate().unwrap();
+        let fresh = regenerated.get_quotient_group(1000, None, None).unwrap();
+        assert!(!Arc::ptr_eq(&first, &fresh));
+    }
+
+    #[test]
+    fn order_estimate_grows_only_when_infinite() {
+        let settings = |schlafli: &str| TilingSettings {
+            schlafli: schlafli.to_string(),
+            relations: vec![],
+            subgroup: String::new(),
+        };
+        // The longest element of {4,3} takes 9 reflections
+        let cube = settings("{4,3}").generate().unwrap();
+        assert_eq!(cube.estimate_order(12, 10_000), 48);
+        assert_eq!(cube.estimate_order(20, 10_000), 48);
+
+        let heptagonal = settings("{7,3}").generate().unwrap();
+        let estimates: Vec<_> = (4..8)
+            .map(|depth| heptagonal.estimate_order(depth, 10_000))
+            .collect();
+        assert!(estimates.windows(2).all(|w| w[0] < w[1]), "{estimates:?}");
+    }
+
+    #[test]
+    fn permuted_generators_revalidated() {
+        let settings = |schlafli: &str, relations: &[&str], subgroup: &str| TilingSettings {
+            schlafli: schlafli.to_string(),
+            relations: relations.iter().map(|r| r.to_string()).collect(),
+            subgroup: subgroup.to_string(),
+        };
+        let cube = settings("{4,3}", &[], "0;1").generate().unwrap();
+        let octahedron = cube.dual().unwrap();
+        assert_eq!(octahedron.settings.schlafli, "{3,4}");
+        assert_eq!(octahedron.subgroup, vec![vec![2], vec![1]]);
+        assert_eq!(octahedron.edges, vec![true, false, false, true]);
+
+        // Dropping generator 2 leaves the relation and a subgroup word out of range
+        let klein = settings("{7,3}", &["0,2,1;8"], "0;2").generate().unwrap();
+        let error = klein.permute_generators(&[1, 0]).unwrap_err();
+        assert_eq!(
+            error,
+            TilingError::Generators(InvalidGenerators {
+                relations: vec![3],
+                subgroup: vec![1],
+            })
+        );
+        assert_eq!(
+            error.to_string(),
+            "relations [3] and subgroup words [1] use generators the tiling doesn't have"
+        );
+        // Generators 0 and 1 don't commute, so can't be pulled apart
+        let error = klein.permute_generators(&[0, 2, 1]).unwrap_err();
+        assert_eq!(error, TilingError::Permutation);
+        assert!(klein.permute_generators(&[0, 0, 1]).is_err());
+    }
+
+    #[test]
+    fn point_maps_to_its_tile() {
+        let settings = TilingSettings {
+            schlafli: "{4,3}".to_string(),
+            relations: vec![],
+            subgroup: "0;1".to_string(),
+        };
+        let tiling = settings.generate().unwrap();
+        let tile_group = &tiling
+            .get_quotient_group(1000, None, None)
+            .unwrap()
+            .tile_group;
+        let center = tiling.fundamental_center();
+        assert_eq!(tiling.tile_at(tile_group, center, 20), Some(Point::INIT));
+        // Mirror 0 is a symmetry of the face, so its image stays on it
+        let same_face = tiling.mirrors[0].sandwich(center);
+        assert_eq!(tiling.tile_at(tile_group, same_face, 20), Some(Point::INIT));
+        // Mirror 2 is an edge of the face, so its image lands on the neighbour
+        let neighbor = tiling.mirrors[2].sandwich(center);
+        assert_eq!(
+            tiling.tile_at(tile_group, neighbor, 20),
+            tile_group.mul_gen(&Point::INIT, &Generator(2))
+        );
+        assert_ne!(tiling.tile_at(tile_group, neighbor, 20), Some(Point::INIT));
+    }
+
+    #[test]
+    fn duplicate_relations_collapse() {
+        let settings = |relations: &[&str]| TilingSettings {
+            schlafli: "{7,3}".to_string(),
+            relations: relations.iter().map(|r| r.to_string()).collect(),
+            subgroup: "0;1".to_string(),
+        };
+        let klein = settings(&["0,2,1;8"]).generate().unwrap();
+        let twice = settings(&["0,2,1;8", "0,2,1;8"]).generate().unwrap();
+        assert_eq!(twice.relations, klein.relations);
+        assert_eq!(twice.redundant_relations, vec![1]);
+        assert_eq!(twice.presentation_key(), klein.presentation_key());
+
+        // A rotation is flagged but kept, while a copy of one of the symbol's is dropped
+        let tiling = settings(&["0,2,1;8", "1,2,0;8", "0,1;7"])
+            .generate()
+            .unwrap();
+        assert_eq!(tiling.relations.len(), klein.relations.len() + 1);
+        assert_eq!(tiling.redundant_relations, vec![1, 2]);
+    }
+
+    #[test]
+    fn bad_symbols_give_their_error() {
+        let generate = |schlafli: &str| {
+            let settings = TilingSettings {
+                schlafli: schlafli.to_string(),
+                relations: vec![],
+                subgroup: String::new(),
+            };
+            settings.generate().unwrap_err()
+        };
+        assert_eq!(generate("{4,3"), TilingError::Schlafli);
+        assert_eq!(generate("{4,3,3,5}"), TilingError::Schlafli);
+        // The tesseract's group is finite, so its mirrors would need to be spheres
+        let error = generate("{4,3,3}");
+        assert_eq!(error, TilingError::Mirrors(MirrorError::SphericalRank4));
+        assert_eq!(error.to_string(), "finite rank 4 groups would need spheres");
+        assert_eq!(
+            MirrorError::Unrealizable { entry: 2 }.to_string(),
+            "entry 3 can't be realized alongside the ones before it in any geometry"
+        );
+    }
+
+    #[test]
+    fn fundamental_area_by_gauss_bonnet() {
+        use std::f64::consts::PI;
+
+        let area = |schlafli: &str| {
+            let settings = TilingSettings {
+                schlafli: schlafli.to_string(),
+                relations: vec![],
+                subgroup: String::new(),
+            };
+            settings.generate().unwrap().fundamental_area()
+        };
+        // The (2,3,7) triangle
+        assert!((area("{7,3}").unwrap() - PI / 42.).abs() < 1e-12);
+        // 48 regions cover the sphere's area of 4π
+        assert!((area("{4,3}").unwrap() - PI / 12.).abs() < 1e-12);
+        assert_eq!(area("{6,3}"), None);
+        assert_eq!(area("{5,3,4}"), None);
+    }
+
+    #[test]
+    fn schlafli_polygons_have_entry_vertices() {
+        for (schlafli, entries) in [("{4,3}", [4, 3]), ("{6,3}", [6, 3]), ("{7,3}", [7, 3])] {
+            let settings = TilingSettings {
+                schlafli: schlafli.to_string(),
+                relations: vec![],
+                subgroup: String::new(),
+            };
+            let tiling = settings.generate().unwrap();
+            for (entry, p) in entries.into_iter().enumerate() {
+                let vertices: Vec<_> = (tiling.schlafli_polygon(entry).unwrap().iter())
+                    .map(|v| v.unpack_point())
+                    .collect();
+                assert_eq!(vertices.len(), p, "{schlafli} entry {entry}");
+                for (i, a) in vertices.iter().enumerate() {
+                    for b in &vertices[i + 1..] {
+                        let apart = (a.0 - b.0).hypot(a.1 - b.1);
+                        assert!(apart > 1e-6, "{schlafli} entry {entry}: repeated vertex");
+                    }
+                }
+            }
+        }
+        let settings = TilingSettings {
+            schlafli: "{5,i}".to_string(),
+            relations: vec![],
+            subgroup: String::new(),
+        };
+        assert!(settings.generate().unwrap().schlafli_polygon(1).is_none());
+    }
+
+    #[test]
+    fn dual_edges_start_at_cell_centres() {
+        let settings = TilingSettings {
+            schlafli: "{4,4}".to_string(),
+            relations: vec![],
+            subgroup: String::new(),
+        };
+        let tiling = settings.generate().unwrap();
+        let ms = &tiling.mirrors;
+        let near = |a: (f64, f64), b: (f64, f64)| (a.0 - b.0).hypot(a.1 - b.1) < 1e-9;
+        let meeting = |a: cga2d::Blade3, b: cga2d::Blade3| {
+            let points = (a & b).unpack_point_pair().unwrap();
+            (points.into_iter())
+                .find(|&p| ms.iter().all(|&m| tiling.tolerances.is_inside(m, p)))
+                .unwrap()
+                .unpack_point()
+        };
+        let primal = tiling.edge_path(false).unwrap();
+        let dual = tiling.edge_path(true).unwrap();
+        let ends = |path: &[cga2d::Blade1]| {
+            let (first, last) = (path[0], path[path.len() - 1]);
+            (first.unpack_point(), last.unpack_point())
+        };
+        let (vertex, midpoint) = ends(&primal);
+        let (centre, dual_midpoint) = ends(&dual);
+        // The cell centre is where the face's two mirrors meet
+        assert!(near(centre, meeting(ms[0], ms[1])));
+        assert!(near(vertex, meeting(ms[1], ms[2])));
+        assert!(!near(centre, vertex));
+        // Both edges cross at the middle of the primal edge
+        assert!(near(midpoint, dual_midpoint));
+        assert!(near(midpoint, meeting(ms[0], ms[2])));
+    }
+
+    #[test]
+    fn outline_per_active_edge() {
+        let settings = |subgroup: &str| TilingSettings {
+            schlafli: "{7,3}".to_string(),
+            relations: vec![],
+            subgroup: subgroup.to_string(),
+        };
+        let angle = std::f64::consts::PI / 30.;
+        let faces = settings("0;1").generate().unwrap();
+        assert_eq!(faces.edges, vec![false, false, true, true]);
+        // The fourth edge is past the rank, so only mirror 2 is outlined
+        assert_eq!(faces.outlines(&faces.edges, angle, angle).len(), 1);
+        let regions = settings("").generate().unwrap();
+        assert_eq!(regions.outlines(&regions.edges, angle, angle).len(), 3);
+        assert_eq!(
+            regions.outlines(&[true, false, true], angle, angle).len(),
+            2
+        );
+        assert!(regions.outlines(&[false; 3], angle, angle).is_empty());
+    }
+
+    #[test]
+    fn locate_folds_into_fundamental_region() {
+        let settings = TilingSettings {
+            schlafli: "{7,3}".to_string(),
+            relations: vec![],
+            subgroup: String::new(),
+        };
+        let tiling = settings.generate().unwrap();
+        let center = tiling.fundamental_center();
+        assert_eq!(tiling.locate(center, 20), (Word(vec![]), false));
+        for g in 0..3 {
+            let image = tiling.mirrors[g].sandwich(center);
+            assert_eq!(
+                tiling.locate(image, 20),
+                (Word(vec![Generator(g as u8)]), true)
+            );
+        }
+        // Folding back along the word returns the point to where it was
+        let word = Word([0, 1, 2, 1, 0, 2].map(Generator).to_vec());
+        let point = tiling.chamber_point(center, &word);
+        let (found, mirrored) = tiling.locate(point, 20);
+        assert!(!mirrored);
+        let folded = tiling.chamber_point(center, &found);
+        let (a, b) = (point.unpack_point(), folded.unpack_point());
+        assert!((a.0 - b.0).hypot(a.1 - b.1) < 1e-9);
+    }
 }
@@ -1,112 +1,369 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::VecDeque,
     fmt,
     ops::{Index, IndexMut},
 };
 
 use crate::group::{Generator, Group, Point, Word};
 
-pub(crate) fn get_element_table(gen_count: usize, rels: &Vec<Vec<u8>>, limit: u32) -> Group {
-    get_coset_table(gen_count, rels, &vec![], limit)
+/// Reasons coset enumeration can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TCError {
+    /// The iteration limit ran out before every coset was found
+    LimitReached,
+    /// The progress callback asked to stop
+    Cancelled,
 }
 
-pub(crate) fn get_coset_table(
+/// An enumeration that stopped early, with the part of the table found before it did.
+#[derive(Debug, Clone)]
+pub struct Truncated {
+    pub error: TCError,
+    /// Cosets defined so far, with results missing where the table wasn't filled in
+    pub group: Group,
+}
+
+/// Number of new cosets defined between calls to the progress callback.
+const PROGRESS_INTERVAL: u32 = 64;
+
+/// Whether an enumeration driven by `Tables::step` has more to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnumState {
+    InProgress,
+    /// Every coset table value is known
+    Done,
+}
+
+/// How relations are scanned for deductions as cosets are defined.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Scan each relation from its start at every coset
+    #[default]
+    Hlt,
+    /// Scan only the relation conjugates starting with each newly defined generator, at
+    /// the cosets either side of the new definition
+    Felsch,
+}
+
+pub fn get_element_table(
     gen_count: usize,
-    rels: &Vec<Vec<u8>>,
-    subgroup: &Vec<u8>,
+    rels: &[Vec<u8>],
     limit: u32,
-) -> Group {
-    let mut tables = Tables::new(gen_count, rels, subgroup);
-    let mut i = 0;
-    while (i < limit) && tables.discover_next_unknown() {
-        i += 1
+    strategy: Strategy,
+    progress: Option<&mut dyn FnMut(u32) -> bool>,
+) -> Result<Group, Truncated> {
+    get_coset_table(gen_count, rels, &vec![], limit, strategy, progress)
+}
+
+pub fn get_coset_table(
+    gen_count: usize,
+    rels: &[Vec<u8>],
+    subgroup: &Vec<Vec<u8>>,
+    limit: u32,
+    strategy: Strategy,
+    progress: Option<&mut dyn FnMut(u32) -> bool>,
+) -> Result<Group, Truncated> {
+    let mut tables = Tables::new(gen_count, rels, subgroup, strategy);
+    let result = tables.continue_to(limit, progress);
+    let group = tables.coset_group();
+    match result {
+        Ok(()) => Ok(group),
+        Err(error) => Err(Truncated { error, group }),
     }
+}
+
+/// Partial element table of everything within `max_word_len` generators of the identity,
+/// for previewing groups too large to enumerate. Stops once `limit` elements are defined.
+pub fn get_element_table_bounded(
+    gen_count: usize,
+    rels: &[Vec<u8>],
+    max_word_len: usize,
+    limit: u32,
+) -> Group {
+    let mut tables = Tables::new(gen_count, rels, &vec![], Strategy::default());
+    tables.continue_within(max_word_len, limit);
     tables.coset_group()
 }
 
-pub(crate) struct Tables {
+/// Every distinct rotation of each relation and its inverse, grouped by first generator.
+fn cyclic_conjugates(rels: &[Vec<u8>], gen_count: usize) -> Vec<Vec<Vec<u8>>> {
+    let mut out: Vec<Vec<Vec<u8>>> = vec![vec![]; gen_count];
+    for rel in rels {
+        // Every generator is an involution, so the inverse is the reverse
+        let inverse: Vec<u8> = rel.iter().rev().copied().collect();
+        for word in [rel, &inverse] {
+            for k in 0..word.len() {
+                let mut conjugate = word.clone();
+                conjugate.rotate_left(k);
+                let starting = &mut out[conjugate[0] as usize];
+                if !starting.contains(&conjugate) {
+                    starting.push(conjugate);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Trace a relation from `coset` back to itself from both ends. If exactly one step is
+/// left unknown, returns the fact that would close it.
+fn scan(
+    coset_table: &CosetTable,
+    relation: &[u8],
+    coset: CosetIndex,
+) -> Option<(CosetIndex, u8, CosetIndex)> {
+    let (mut left, mut left_rel_index) = (coset, 0);
+    let (mut right, mut right_rel_index) = (coset, relation.len() - 1);
+    while left_rel_index < right_rel_index {
+        let Some(result) = coset_table[left][relation[left_rel_index] as usize] else {
+            break;
+        };
+        left = result;
+        left_rel_index += 1;
+    }
+    while left_rel_index < right_rel_index {
+        let Some(result) = coset_table[right][relation[right_rel_index] as usize] else {
+            break;
+        };
+        right = result;
+        right_rel_index -= 1;
+    }
+    (left_rel_index == right_rel_index).then(|| (left, relation[left_rel_index], right))
+}
+
+pub struct Tables {
     coset_table: CosetTable,
     relation_tables: Vec<RelationTable>,
+    /// Relation conjugates by first generator, scanned instead of the relation tables
+    /// under `Strategy::Felsch`
+    felsch_rels: Vec<Vec<Vec<u8>>>,
     word_table: WordTable,
-    //subgroup_tables: Vec<Table>,
+    /// Traces of each subgroup word of length 2 or more, from coset 0 back to itself
+    subgroup_tables: Vec<RelationTable>,
+    /// Number of cosets defined so far, counting ones later found to coincide
+    iterations: u32,
+    /// Number of pairs of cosets found to be equal and merged
+    coincidences: u32,
+    /// Coset table entries before this are all filled in, so needn't be searched again
+    first_unknown: usize,
 }
 impl Tables {
-    /// Initialise a new set of tables. Assumes subgroup generators are group generators.
-    pub fn new(gen_count: usize, rels: &Vec<Vec<u8>>, subgroup: &Vec<u8>) -> Self {
+    /// Initialise a new set of tables, with the subgroup generated by the given words.
+    pub fn new(
+        gen_count: usize,
+        rels: &[Vec<u8>],
+        subgroup: &Vec<Vec<u8>>,
+        strategy: Strategy,
+    ) -> Self {
+        let rels: Vec<Vec<u8>> = rels.iter().filter(|rel| !rel.is_empty()).cloned().collect();
+        let (relation_tables, felsch_rels) = match strategy {
+            Strategy::Hlt => (
+                rels.iter().map(|rel| RelationTable::new(rel)).collect(),
+                vec![],
+            ),
+            Strategy::Felsch => (vec![], cyclic_conjugates(&rels, gen_count)),
+        };
         let mut out = Self {
             coset_table: CosetTable::new(gen_count),
-            relation_tables: rels.iter().map(|rel| RelationTable::new(rel)).collect(),
+            relation_tables,
+            felsch_rels,
             word_table: WordTable::new(),
-            //subgroup_tables: subgroup.iter().map(|gen| Table::new(gen.len())).collect(),
+            subgroup_tables: vec![],
+            iterations: 0,
+            coincidences: 0,
+            first_unknown: 0,
         };
-        for &sub_gen in subgroup {
-            out.deduce(CosetIndex(0), sub_gen, CosetIndex(0));
-            // println!("{}", out.coset_table.to_string())
+        for word in subgroup {
+            match word[..] {
+                [] => (),
+                [sub_gen] => out.deduce(CosetIndex(0), sub_gen, CosetIndex(0)),
+                _ => {
+                    // Deduced from like a relation, but only ever at coset 0
+                    out.subgroup_tables.push(RelationTable::new(word));
+                    out.deduce_from_subgroup();
+                }
+            }
         }
         out
     }
 
     /// Fill in tables based on a new result.
     fn deduce(&mut self, coset: CosetIndex, generator: u8, result: CosetIndex) {
-        let mut new_friends = VecDeque::from(vec![(coset, generator, result)]);
-        while let Some((mut coset, generator, mut result)) = new_friends.pop_front() {
-            coset = self.coset_table.redirect_index(coset);
-            result = self.coset_table.redirect_index(result);
-            if let Some(res) = self.coset_table[coset][generator as usize] {
-                if res != result {
-                    // Coincidence
-                    let replace = res.max(result);
-                    result = res.min(result);
-
-                    self.resolve_coincidence(result, replace);
+        let mut new_facts = VecDeque::from(vec![(coset, generator, result)]);
+        let mut merged = vec![];
+        loop {
+            while let Some((coset, generator, result)) = new_facts.pop_front() {
+                let coset = self.coset_table.find(coset);
+                let result = self.coset_table.find(result);
+                let g = generator as usize;
+                let forward = self.coset_table[coset][g].map(|c| self.coset_table.find(c));
+                let backward = self.coset_table[result][g].map(|c| self.coset_table.find(c));
+                match (forward, backward) {
+                    (Some(f), _) if f != result => {
+                        merged.push(self.resolve_coincidence(f, result, &mut new_facts));
+                        continue;
+                    }
+                    (_, Some(b)) if b != coset => {
+                        merged.push(self.resolve_coincidence(b, coset, &mut new_facts));
+                        continue;
+                    }
+                    (Some(_), Some(_)) => continue,
+                    _ => (),
                 }
-            }
 
-            self.coset_table[coset][generator as usize] =
-                Some(self.coset_table.redirect_index(result));
-            self.coset_table[result][generator as usize] =
-                Some(self.coset_table.redirect_index(coset)); // inverse
+                self.coset_table[coset][g] = Some(result);
+                self.coset_table[result][g] = Some(coset); // inverse
 
-            for rel_table in &mut self.relation_tables {
-                rel_table.update(&self.coset_table, &mut new_friends);
+                for rel_table in &mut self.relation_tables {
+                    rel_table.update(&self.coset_table, &mut new_facts);
+                }
+                for rel in self.felsch_rels.get(g).into_iter().flatten() {
+                    for c in [coset, result] {
+                        new_facts.extend(scan(&self.coset_table, rel, c));
+                    }
+                }
+                for sub_table in &mut self.subgroup_tables {
+                    sub_table.update(&self.coset_table, &mut new_facts);
+                }
+            }
+            // A trace through a merged coset can get further along the kept coset's row
+            // without any new definition on it, so rescan everything through it
+            let Some(coset) = merged.pop() else {
+                break;
+            };
+            let coset = self.coset_table.find(coset);
+            for rel in self.felsch_rels.iter().flatten() {
+                new_facts.extend(scan(&self.coset_table, rel, coset));
             }
         }
     }
 
-    /// Fix a duplicate result.
-    fn resolve_coincidence(&mut self, keep: CosetIndex, replace: CosetIndex) {
-        self.coset_table.tombstones[replace.0 as usize] = Some(keep);
+    /// Apply anything the subgroup tables can already deduce.
+    fn deduce_from_subgroup(&mut self) {
+        let mut new_facts = VecDeque::new();
+        for sub_table in &mut self.subgroup_tables {
+            sub_table.update(&self.coset_table, &mut new_facts);
+        }
+        for (coset, generator, result) in new_facts {
+            self.deduce(coset, generator, result);
+        }
+    }
 
-        let replace_index = |c: CosetIndex| match c.cmp(&replace) {
-            std::cmp::Ordering::Equal => keep,
-            _ => c,
-        };
+    /// Merge two cosets found to be equal. The later one's row is queued as facts about the
+    /// earlier one before it is redirected, so any further coincidences those facts imply
+    /// are found as the queue is worked through. Returns the kept coset. References to the merged coset are left
+    /// in place and followed through its tombstone until the tables are next compacted.
+    fn resolve_coincidence(
+        &mut self,
+        a: CosetIndex,
+        b: CosetIndex,
+        new_facts: &mut VecDeque<(CosetIndex, u8, CosetIndex)>,
+    ) -> CosetIndex {
+        let a = self.coset_table.find(a);
+        let b = self.coset_table.find(b);
+        if a == b {
+            return a;
+        }
+        let (keep, replace) = (a.min(b), a.max(b));
+        let row = self.coset_table.entries[self.coset_table.row_range(replace)].to_vec();
+        self.coset_table.tombstones[replace.0 as usize] = Some(keep);
+        self.coset_table.merged += 1;
+        self.coincidences += 1;
 
-        self.coset_table
-            .tombstones
-            .iter_mut()
-            .for_each(|i| *i = i.map(replace_index));
-        self.coset_table
-            .entries
-            .iter_mut()
-            .for_each(|i| *i = i.map(replace_index));
+        for (g, res) in row.into_iter().enumerate() {
+            if let Some(res) = res {
+                new_facts.push_back((keep, g as u8, res));
+            }
+        }
+        // Relation rows stuck on the merged coset may now continue along `keep`'s row
         for rel_table in &mut self.relation_tables {
-            for row in &mut rel_table.rows {
-                row.left_coset = replace_index(row.left_coset);
-                row.right_coset = replace_index(row.right_coset);
+            rel_table.update(&self.coset_table, new_facts);
+        }
+        for sub_table in &mut self.subgroup_tables {
+            sub_table.update(&self.coset_table, new_facts);
+        }
+        keep
+    }
+
+    /// Keep defining cosets until `limit` have been defined in total. Picks up where any
+    /// previous call stopped, giving the same result as enumerating to `limit` in one go.
+    /// `progress` is called with the current coset count; returning false stops enumeration.
+    pub fn continue_to(
+        &mut self,
+        limit: u32,
+        mut progress: Option<&mut dyn FnMut(u32) -> bool>,
+    ) -> Result<(), TCError> {
+        while (self.iterations < limit) && self.discover_next_unknown() {
+            self.iterations += 1;
+            if let Some(progress) = &mut progress {
+                if self.iterations.is_multiple_of(PROGRESS_INTERVAL)
+                    && !progress(self.coset_table.row_count() as u32)
+                {
+                    return Err(TCError::Cancelled);
+                }
             }
         }
+        if !self.is_complete() {
+            return Err(TCError::LimitReached);
+        }
+        Ok(())
+    }
+
+    /// Keep defining cosets no more than `max_word_len` generators from coset 0, until
+    /// there are none left or `limit` have been defined in total.
+    pub fn continue_within(&mut self, max_word_len: usize, limit: u32) {
+        while (self.iterations < limit) && self.discover_next_unknown_within(max_word_len) {
+            self.iterations += 1;
+        }
+    }
 
-        (0..self.coset_table.gen_count).for_each(|g| {
-            if let Some(res) = self.coset_table[replace][g] {
-                self.deduce(keep, g as u8, res);
+    /// Define at most `budget` more cosets, for spreading an enumeration over several calls.
+    /// Any number of steps gives the same tables as `continue_to` with the same total.
+    pub fn step(&mut self, budget: u32) -> EnumState {
+        for _ in 0..budget {
+            if !self.discover_next_unknown() {
+                return EnumState::Done;
             }
-        });
+            self.iterations += 1;
+        }
+        match self.is_complete() {
+            true => EnumState::Done,
+            false => EnumState::InProgress,
+        }
+    }
+
+    /// Number of cosets defined so far.
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    /// Number of coincidences resolved so far.
+    pub fn coincidences(&self) -> u32 {
+        self.coincidences
+    }
+
+    /// Whether every coset table value is known.
+    pub fn is_complete(&self) -> bool {
+        self.coset_table.entries.iter().all(|e| e.is_some())
     }
 
     /// Fill in next empty coset table value with a new coset
     pub fn discover_next_unknown(&mut self) -> bool {
-        let Some(i) = self.coset_table.entries.iter().position(|e| e.is_none()) else {
+        self.discover_next_unknown_within(usize::MAX)
+    }
+
+    /// Fill in the next empty coset table value whose new coset would be no more than
+    /// `max_word_len` generators from coset 0.
+    pub fn discover_next_unknown_within(&mut self, max_word_len: usize) -> bool {
+        let gen_count = self.coset_table.gen_count;
+        let entries = &self.coset_table.entries;
+        while entries.get(self.first_unknown).is_some_and(|e| e.is_some()) {
+            self.first_unknown += 1;
+        }
+        let Some(i) = (entries.iter().enumerate().skip(self.first_unknown)).find_map(|(i, e)| {
+            (e.is_none() && self.word_table.words[i / gen_count].0.len() < max_word_len)
+                .then_some(i)
+        }) else {
             return false;
         };
         let (coset, generator) = self.coset_table.unpack_index(i);
@@ -115,15 +372,25 @@ impl Tables {
         self.word_table.push(new_word);
         self.deduce(coset, generator as u8, result);
 
+        if self.coset_table.merged == 0 {
+            return true;
+        }
+        self.first_unknown = 0;
+
         let mut fresh_indices = 0..;
-        let index_replacements: Vec<CosetIndex> = self
-            .coset_table
-            .tombstones
-            .iter()
-            .map(|replacement| {
-                replacement.unwrap_or_else(|| CosetIndex(fresh_indices.next().unwrap()))
+        let fresh: Vec<Option<CosetIndex>> = (self.coset_table.tombstones.iter())
+            .map(|t| match t {
+                Some(_) => None,
+                None => Some(CosetIndex(fresh_indices.next().unwrap())),
             })
             .collect();
+        let replace_index = |c: CosetIndex| {
+            fresh[self.coset_table.redirect_index(c).0 as usize]
+                .expect("redirected coset should be live")
+        };
+        let index_replacements: Vec<CosetIndex> = (0..fresh.len())
+            .map(|c| replace_index(CosetIndex(c as u32)))
+            .collect();
         let replace_index = |c: CosetIndex| index_replacements[c.0 as usize];
 
         // Reindex everyone down
@@ -138,6 +405,13 @@ impl Tables {
             }
             rel_table.remove_redirected(&self.coset_table.tombstones);
         }
+        // Subgroup tables only have a row for coset 0, which is never replaced
+        for sub_table in &mut self.subgroup_tables {
+            for row in &mut sub_table.rows {
+                row.left_coset = replace_index(row.left_coset);
+                row.right_coset = replace_index(row.right_coset);
+            }
+        }
         self.word_table
             .remove_redirected(&self.coset_table.tombstones);
 
@@ -157,13 +431,12 @@ impl Tables {
     }
 
     pub fn coset_group(&self) -> Group {
-        let mut mul_table = HashMap::new();
-        for (i, e) in self.coset_table.entries.iter().enumerate() {
-            let (coset, gen) = self.coset_table.unpack_index(i);
-            mul_table.insert((Point(coset.0), Generator(gen as _)), e.map(|e| Point(e.0)));
-        }
+        // Entries are already laid out row by row, as the group expects
+        let mul_table = (self.coset_table.entries.iter())
+            .map(|e| e.map(|e| Point(e.0)))
+            .collect();
         Group::new(
-            self.coset_table.row_count() as u16,
+            self.coset_table.row_count() as u32,
             self.coset_table.gen_count as u8,
             mul_table,
             self.word_table.words.clone(),
@@ -172,11 +445,13 @@ impl Tables {
 }
 
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
-struct CosetIndex(u16);
+struct CosetIndex(u32);
 
 struct CosetTable {
     entries: Vec<Option<CosetIndex>>,
     tombstones: Vec<Option<CosetIndex>>,
+    /// Number of rows redirected by a tombstone, waiting to be removed
+    merged: usize,
     gen_count: usize,
 }
 impl CosetTable {
@@ -185,6 +460,7 @@ impl CosetTable {
         Self {
             entries: vec![None; gen_count],
             tombstones: vec![None; gen_count],
+            merged: 0,
             gen_count,
         }
     }
@@ -196,7 +472,7 @@ impl CosetTable {
 
     /// Convert a linear index into (row,column) form based on column count.
     fn unpack_index_with_count(index: usize, col_count: usize) -> (CosetIndex, usize) {
-        let coset_index = CosetIndex((index / col_count) as u16);
+        let coset_index = CosetIndex((index / col_count) as u32);
         let column = index % col_count;
         (coset_index, column)
     }
@@ -210,7 +486,7 @@ impl CosetTable {
     fn add_row(&mut self) -> CosetIndex {
         self.entries.extend((0..self.gen_count).map(|_| None));
         self.tombstones.push(None);
-        CosetIndex((self.row_count() - 1) as u16)
+        CosetIndex((self.row_count() - 1) as u32)
     }
 
     fn remove(&mut self, index: CosetIndex) -> Vec<Option<CosetIndex>> {
@@ -223,6 +499,18 @@ impl CosetTable {
         i..i + self.gen_count
     }
 
+    /// Find the live coset a coset has been merged into, shortening the path of
+    /// tombstones along the way.
+    fn find(&mut self, index: CosetIndex) -> CosetIndex {
+        let root = self.redirect_index(index);
+        let mut index = index;
+        while let Some(next) = self.tombstones[index.0 as usize] {
+            self.tombstones[index.0 as usize] = Some(root);
+            index = next;
+        }
+        root
+    }
+
     /// Cascade a coset through any reindexings
     fn redirect_index(&self, mut index: CosetIndex) -> CosetIndex {
         while let Some(redirect) = self.tombstones[index.0 as usize] {
@@ -237,6 +525,7 @@ impl CosetTable {
         let Self {
             entries,
             tombstones,
+            merged,
             gen_count,
         } = self;
 
@@ -248,6 +537,7 @@ impl CosetTable {
             }
         }
         tombstones.retain(|t| t.is_none());
+        *merged = 0;
     }
 }
 impl Index<CosetIndex> for CosetTable {
@@ -414,3 +704,233 @@ impl IndexMut<CosetIndex> for WordTable {
         &mut self.words[index.0 as usize]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Relations of the Coxeter group with the given linear Schläfli symbol, followed by
+    /// each `(word, power)` extra relation.
+    fn rels(schlafli: &[usize], extra: &[(&[u8], usize)]) -> Vec<Vec<u8>> {
+        let mut rels = vec![];
+        for (i, &val) in schlafli.iter().enumerate() {
+            for x in 0..i {
+                rels.push(vec![x as u8, i as u8 + 1, x as u8, i as u8 + 1]);
+            }
+            rels.push((0..val).flat_map(|_| [i as u8, i as u8 + 1]).collect());
+        }
+        for &(word, power) in extra {
+            rels.push(word.repeat(power));
+        }
+        rels
+    }
+
+    fn order(rels: &Vec<Vec<u8>>, gen_count: usize, strategy: Strategy) -> (u32, u32) {
+        let mut tables = Tables::new(gen_count, rels, &vec![], strategy);
+        tables.continue_to(100_000, None).unwrap();
+        let group = tables.coset_group();
+        for p in group.points() {
+            for rel in rels {
+                let word = Word(rel.iter().map(|&g| Generator(g)).collect());
+                assert_eq!(group.mul_word(&p, &word), Some(p), "{rel:?} fails at {p:?}");
+            }
+        }
+        (group.point_count(), tables.coincidences())
+    }
+
+    #[test]
+    fn coincidences_merge_rows() {
+        for strategy in [Strategy::Hlt, Strategy::Felsch] {
+            // (12)^15 and (12)^10 leave (12)^5, so Z2 × D5
+            let dihedral = rels(&[2, 15], &[(&[1, 2], 10)]);
+            let (count, coincidences) = order(&dihedral, 3, strategy);
+            assert_eq!(count, 20);
+            assert!(coincidences > 10);
+
+            // 0 = 1 in {5,3,3}, which then collapses every mirror onto one
+            let collapsing = rels(&[5, 3, 3], &[(&[0, 1], 3)]);
+            let (count, coincidences) = order(&collapsing, 4, strategy);
+            assert_eq!(count, 2);
+            assert!(coincidences > 20);
+        }
+    }
+
+    #[test]
+    fn strategies_agree() {
+        let default_extra: [(&[u8], usize); 3] = [
+            (&[0, 1], 3),
+            (&[1, 3, 2], 5),
+            (&[1, 0, 1, 2, 1, 0, 2, 1, 0, 2, 1, 0, 2, 1, 2], 1),
+        ];
+        let presentations = [
+            (rels(&[7, 3], &[(&[0, 2, 1], 8)]), 3, 336),
+            (rels(&[8, 3], &[(&[0, 2, 1], 6)]), 3, 192),
+            (rels(&[6, 5, 3], &default_extra), 4, 660),
+            (rels(&[3, 4, 3], &[]), 4, 1152),
+            (rels(&[5, 3, 3], &[(&[0, 1, 2, 3], 3)]), 4, 2),
+        ];
+        for (rels, gen_count, expected) in presentations {
+            assert_eq!(order(&rels, gen_count, Strategy::Hlt).0, expected);
+            assert_eq!(order(&rels, gen_count, Strategy::Felsch).0, expected);
+        }
+
+        let default = rels(&[6, 5, 3], &default_extra);
+        let subgroup = vec![vec![0], vec![1], vec![2]];
+        let [hlt, felsch] = [Strategy::Hlt, Strategy::Felsch]
+            .map(|s| get_coset_table(4, &default, &subgroup, 10_000, s, None).unwrap());
+        assert_eq!(hlt.point_count(), felsch.point_count());
+    }
+
+    #[test]
+    fn resuming_matches_one_shot() {
+        let rels = rels(
+            &[6, 5, 3],
+            &[
+                (&[0, 1], 3),
+                (&[1, 3, 2], 5),
+                (&[1, 0, 1, 2, 1, 0, 2, 1, 0, 2, 1, 0, 2, 1, 2], 1),
+            ],
+        );
+        for limit in [500, 1000] {
+            let mut resumed = Tables::new(4, &rels, &vec![], Strategy::Hlt);
+            assert_eq!(resumed.continue_to(200, None), Err(TCError::LimitReached));
+            let resumed_result = resumed.continue_to(limit, None);
+
+            let mut one_shot = Tables::new(4, &rels, &vec![], Strategy::Hlt);
+            assert_eq!(one_shot.continue_to(limit, None), resumed_result);
+            assert_eq!(
+                resumed.coset_group().to_bincode(),
+                one_shot.coset_group().to_bincode()
+            );
+        }
+    }
+
+    #[test]
+    fn infinite_group_reaches_limit() {
+        let rels = rels(&[7, 3], &[]);
+        for strategy in [Strategy::Hlt, Strategy::Felsch] {
+            let result = get_element_table(3, &rels, 100, strategy, None);
+            assert_eq!(result.unwrap_err().error, TCError::LimitReached);
+            let result = get_coset_table(3, &rels, &vec![vec![0], vec![1]], 100, strategy, None);
+            assert_eq!(result.unwrap_err().error, TCError::LimitReached);
+        }
+    }
+
+    #[test]
+    fn cancelled_enumeration_keeps_partial_table() {
+        let rels = rels(&[7, 3], &[]);
+        let limit = 10_000;
+        for strategy in [Strategy::Hlt, Strategy::Felsch] {
+            let mut calls = 0;
+            let mut progress = |_| {
+                calls += 1;
+                calls < 4
+            };
+            let truncated =
+                get_element_table(3, &rels, limit, strategy, Some(&mut progress)).unwrap_err();
+            assert_eq!(truncated.error, TCError::Cancelled);
+            assert_eq!(calls, 4);
+            // Stopped at the fourth report, after 4 * PROGRESS_INTERVAL definitions
+            let count = truncated.group.point_count();
+            assert!(count > 0 && count < limit, "{count} cosets");
+            assert!(count <= 4 * PROGRESS_INTERVAL + 1);
+            assert!(!truncated.group.is_complete());
+            assert!(truncated
+                .group
+                .mul_gen(&Point::INIT, &Generator(0))
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn flat_table_matches_hashmap() {
+        use std::collections::HashMap;
+
+        let rels = rels(
+            &[6, 5, 3],
+            &[
+                (&[0, 1], 3),
+                (&[1, 3, 2], 5),
+                (&[1, 0, 1, 2, 1, 0, 2, 1, 0, 2, 1, 0, 2, 1, 2], 1),
+            ],
+        );
+        for subgroup in [vec![], vec![vec![0], vec![1], vec![2]]] {
+            let mut tables = Tables::new(4, &rels, &subgroup, Strategy::Hlt);
+            tables.continue_to(1000, None).unwrap();
+            // Keyed as the group used to store it
+            let mut mul_table = HashMap::new();
+            for (i, e) in tables.coset_table.entries.iter().enumerate() {
+                let (coset, gen) = tables.coset_table.unpack_index(i);
+                mul_table.insert(
+                    (Point(coset.0), Generator(gen as u8)),
+                    e.map(|e| Point(e.0)),
+                );
+            }
+            let group = tables.coset_group();
+            for p in group.points() {
+                for g in (0..4).map(Generator) {
+                    assert_eq!(group.mul_gen(&p, &g), mul_table[&(p, g)]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn enumerates_past_u16_points() {
+        // {7,3} is infinite, so this stops with the table only partly filled in. Felsch
+        // only scans relations at new definitions, keeping this quick at this size
+        let rels = rels(&[7, 3], &[]);
+        let limit = 70_000;
+        let truncated = get_element_table(3, &rels, limit, Strategy::Felsch, None).unwrap_err();
+        let group = truncated.group;
+        assert!(group.point_count() > u16::MAX as u32);
+        for p in group.points() {
+            let word = &group.word_table[p.0 as usize];
+            assert_eq!(group.mul_word(&Point::INIT, word), Some(p));
+        }
+    }
+
+    #[test]
+    fn quotient_by_word_subgroup() {
+        // The Klein quartic, {7,3} with 336 elements
+        let rels = rels(&[7, 3], &[(&[0, 2, 1], 8)]);
+        for strategy in [Strategy::Hlt, Strategy::Felsch] {
+            // Rotations about a vertex, of order 3, and about an edge, of order 2
+            for (word, count) in [(vec![1, 2], 112), (vec![0, 2], 168)] {
+                let group = get_coset_table(3, &rels, &vec![word], 10_000, strategy, None);
+                assert_eq!(group.unwrap().point_count(), count);
+            }
+        }
+    }
+
+    #[test]
+    fn bounded_table_of_one_generator() {
+        let rels = rels(&[5, 3], &[]);
+        let group = get_element_table_bounded(3, &rels, 1, 1000);
+        assert_eq!(group.point_count(), 4);
+        assert!(!group.is_complete());
+        for g in (0..3).map(Generator) {
+            let p = group.mul_gen(&Point::INIT, &g).unwrap();
+            assert_eq!(group.word_table[p.0 as usize], Word(vec![g]));
+            assert_eq!(group.mul_gen(&p, &g), Some(Point::INIT));
+        }
+    }
+
+    #[test]
+    fn single_steps_match_full_run() {
+        let rels = rels(&[7, 3], &[(&[0, 2, 1], 8)]);
+        for strategy in [Strategy::Hlt, Strategy::Felsch] {
+            let mut stepped = Tables::new(3, &rels, &vec![], strategy);
+            while stepped.step(1) == EnumState::InProgress {}
+
+            let mut full = Tables::new(3, &rels, &vec![], strategy);
+            full.continue_to(10_000, None).unwrap();
+            assert_eq!(stepped.iterations(), full.iterations());
+            assert_eq!(stepped.coset_group().point_count(), 336);
+            assert_eq!(
+                stepped.coset_group().to_bincode(),
+                full.coset_group().to_bincode()
+            );
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! Drives a puzzle through the library alone, with no window or GPU.
+
+use std::sync::Arc;
+
+use discrete::{
+    group::{Generator, Point},
+    presets,
+    puzzle::GripSignature,
+    PuzzleDefinition,
+};
+
+#[test]
+fn scramble_and_solve() {
+    let preset = (presets::builtin().into_iter())
+        .find(|p| p.name == "Cube")
+        .unwrap();
+    let tiling = Arc::new(preset.tiling_settings.generate().unwrap());
+    let quotient_group = tiling.get_quotient_group(1000, None, None).unwrap();
+    assert_eq!(quotient_group.tile_group.point_count(), 6);
+
+    // Centres and edges
+    let neighbor = (quotient_group.tile_group)
+        .mul_gen(&Point::INIT, &Generator(2))
+        .unwrap();
+    let mut definition = PuzzleDefinition::new(tiling, quotient_group);
+    (definition.piece_types).push(GripSignature::new(vec![Point::INIT, neighbor]));
+    let mut puzzle = definition.generate_puzzle().unwrap();
+    assert_eq!(puzzle.stats().pieces, 6 + 12);
+
+    for (attitude, twist, inverse) in puzzle.parse_moves("A2 B' C").unwrap() {
+        puzzle.apply_move(attitude, twist, inverse).unwrap();
+    }
+    assert!(!puzzle.is_solved());
+    assert_eq!(puzzle.move_log().len(), 4);
+
+    let solution = puzzle.solve_bfs(4).unwrap();
+    assert!(solution.len() <= 4);
+    for (attitude, twist, inverse) in solution {
+        puzzle.apply_move(attitude, twist, inverse).unwrap();
+    }
+    assert!(puzzle.is_solved());
+}